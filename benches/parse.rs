@@ -0,0 +1,47 @@
+//! Parser benchmarks: `cargo bench --features bench`.
+//!
+//! Each stage runs on the same generated document and reports the mean time
+//! per iteration.
+
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use jtl_rs::{bench, generate, parse, parse_borrowed, GenOptions};
+
+fn run(name: &str, mut f: impl FnMut()) {
+    // Warm up, then time as many iterations as fit in about a second.
+    f();
+    let start = Instant::now();
+    let mut iterations = 0u32;
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        iterations += 1;
+    }
+    println!("{:<20} {:>12.2?}/iter", name, start.elapsed() / iterations);
+}
+
+fn main() {
+    let mut options = GenOptions::default();
+    options.elements = 1_000;
+    let document = generate(&options);
+    let text = document.to_jtl().unwrap();
+    let decl = text.lines().nth(2).unwrap().to_string();
+    let env = HashMap::from([("host".to_string(), "localhost".to_string())]);
+
+    run("lex", || {
+        black_box(bench::lex(black_box(&text)));
+    });
+    run("parse_attributes", || {
+        black_box(bench::parse_attributes(black_box(&decl)).unwrap());
+    });
+    run("resolve_env", || {
+        black_box(bench::resolve_env(black_box("http://${env:host}/"), &env).unwrap());
+    });
+    run("parse", || {
+        black_box(parse(black_box(&text)).unwrap());
+    });
+    run("parse_borrowed", || {
+        black_box(parse_borrowed(black_box(&text)).unwrap());
+    });
+}
@@ -0,0 +1,244 @@
+//! `#[derive(JtlElement)]` for jtl-rs. Use it through the `derive` feature of
+//! jtl-rs, which re-exports it; see `jtl_rs::ElementMapping` for the mapping.
+
+use proc_macro::{Delimiter, Group, TokenStream, TokenTree};
+
+/// Implements `jtl_rs::ElementMapping` for a struct with named fields.
+#[proc_macro_derive(JtlElement, attributes(jtl))]
+pub fn derive_jtl_element(input: TokenStream) -> TokenStream {
+    let code = match parse_struct(input) {
+        Ok(item) => expand(&item),
+        Err(message) => format!("::core::compile_error!({:?});", message),
+    };
+    code.parse().expect("generated code is valid Rust")
+}
+
+struct Struct {
+    name: String,
+    fields: Vec<Field>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Key,
+    Content,
+    Attr,
+}
+
+struct Field {
+    ident: String,
+    name: String,
+    role: Role,
+    optional: bool,
+}
+
+fn parse_struct(input: TokenStream) -> Result<Struct, String> {
+    let mut tokens = input.into_iter().peekable();
+    let mut name = None;
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident.to_string() == "struct" => {
+                name = tokens.next().map(|token| token.to_string());
+            }
+            TokenTree::Ident(ident)
+                if ident.to_string() == "enum" || ident.to_string() == "union" =>
+            {
+                return Err("#[derive(JtlElement)] only supports structs".into());
+            }
+            TokenTree::Punct(punct) if punct.as_char() == '<' && name.is_some() => {
+                return Err("#[derive(JtlElement)] does not support generic structs".into());
+            }
+            TokenTree::Group(group) if name.is_some() => {
+                if group.delimiter() != Delimiter::Brace {
+                    return Err("#[derive(JtlElement)] needs a struct with named fields".into());
+                }
+                let fields = parse_fields(group)?;
+                return check(Struct {
+                    name: name.unwrap_or_default(),
+                    fields,
+                });
+            }
+            _ => {}
+        }
+    }
+    Err("#[derive(JtlElement)] needs a struct with named fields".into())
+}
+
+fn parse_fields(group: Group) -> Result<Vec<Field>, String> {
+    let mut fields = Vec::new();
+    let mut tokens = group.stream().into_iter().peekable();
+    loop {
+        let mut role = Role::Attr;
+        let mut rename = None;
+        // Attributes and visibility, up to the field name.
+        let ident = loop {
+            match tokens.next() {
+                None => return Ok(fields),
+                Some(TokenTree::Punct(punct)) if punct.as_char() == '#' => {
+                    if let Some(TokenTree::Group(attr)) = tokens.next() {
+                        parse_attr(attr, &mut role, &mut rename)?;
+                    }
+                }
+                Some(TokenTree::Ident(ident)) if ident.to_string() == "pub" => {
+                    if let Some(TokenTree::Group(group)) = tokens.peek() {
+                        if group.delimiter() == Delimiter::Parenthesis {
+                            tokens.next();
+                        }
+                    }
+                }
+                Some(TokenTree::Ident(ident)) => break ident.to_string(),
+                Some(other) => return Err(format!("unexpected `{}` in struct fields", other)),
+            }
+        };
+        tokens.next(); // The `:`.
+
+        // The type runs to the next `,` outside angle brackets.
+        let mut ty = Vec::new();
+        let mut depth = 0usize;
+        for token in tokens.by_ref() {
+            if let TokenTree::Punct(punct) = &token {
+                match punct.as_char() {
+                    '<' => depth += 1,
+                    '>' => depth = depth.saturating_sub(1),
+                    ',' if depth == 0 => break,
+                    _ => {}
+                }
+            }
+            ty.push(token.to_string());
+        }
+        let optional = ty.first().is_some_and(|first| first == "Option")
+            || ty.len() > 4 && ty[..5].join("") == "::core::option::Option"
+            || ty.len() > 4 && ty[..5].join("") == "::std::option::Option";
+        let ident = ident.strip_prefix("r#").unwrap_or(&ident).to_string();
+        fields.push(Field {
+            name: rename.unwrap_or_else(|| ident.clone()),
+            ident,
+            role,
+            optional,
+        });
+    }
+}
+
+/// Reads `jtl(key)`, `jtl(content)` and `jtl(rename = "name")`.
+fn parse_attr(attr: Group, role: &mut Role, rename: &mut Option<String>) -> Result<(), String> {
+    let mut tokens = attr.stream().into_iter();
+    match tokens.next() {
+        Some(TokenTree::Ident(ident)) if ident.to_string() == "jtl" => {}
+        _ => return Ok(()),
+    }
+    let Some(TokenTree::Group(args)) = tokens.next() else {
+        return Err("expected #[jtl(key)], #[jtl(content)] or #[jtl(rename = \"name\")]".into());
+    };
+    let mut args = args.stream().into_iter();
+    while let Some(arg) = args.next() {
+        match arg.to_string().as_str() {
+            "key" => *role = Role::Key,
+            "content" => *role = Role::Content,
+            "rename" => {
+                args.next(); // The `=`.
+                let literal = args
+                    .next()
+                    .map(|token| token.to_string())
+                    .unwrap_or_default();
+                let name = literal
+                    .strip_prefix('"')
+                    .and_then(|literal| literal.strip_suffix('"'))
+                    .ok_or("#[jtl(rename = ...)] needs a string literal")?;
+                *rename = Some(name.to_string());
+            }
+            "," => {}
+            other => return Err(format!("unknown jtl attribute `{}`", other)),
+        }
+    }
+    Ok(())
+}
+
+fn check(item: Struct) -> Result<Struct, String> {
+    let count = |role| {
+        item.fields
+            .iter()
+            .filter(|field| field.role == role)
+            .count()
+    };
+    if count(Role::Content) != 1 {
+        return Err("#[derive(JtlElement)] needs exactly one #[jtl(content)] field".into());
+    }
+    if count(Role::Key) > 1 {
+        return Err("#[derive(JtlElement)] allows at most one #[jtl(key)] field".into());
+    }
+    if let Some(field) = item
+        .fields
+        .iter()
+        .find(|field| field.optional && field.role != Role::Attr)
+    {
+        return Err(format!(
+            "`{}`: the key and content cannot be optional",
+            field.ident
+        ));
+    }
+    Ok(item)
+}
+
+fn expand(item: &Struct) -> String {
+    let mut from = String::new();
+    let mut to = String::new();
+    let mut key = format!("{:?}", item.name);
+    let mut content = String::new();
+    for field in &item.fields {
+        let ident = &field.ident;
+        let name = &field.name;
+        match field.role {
+            Role::Key => {
+                from.push_str(&format!(
+                    "{}: ::jtl_rs::__parse_field(element, \"KEY\", &element.key)?,",
+                    ident
+                ));
+                key = format!("::std::string::ToString::to_string(&self.{})", ident);
+            }
+            Role::Content => {
+                from.push_str(&format!(
+                    "{}: ::jtl_rs::__parse_field(element, \"Content\", &element.content)?,",
+                    ident
+                ));
+                content = format!("::std::string::ToString::to_string(&self.{})", ident);
+            }
+            Role::Attr if field.optional => {
+                from.push_str(&format!(
+                    "{ident}: match element.attr({name:?}) {{ \
+                         ::core::option::Option::Some(value) => ::core::option::Option::Some(\
+                             ::jtl_rs::__parse_field(element, {name:?}, value)?), \
+                         ::core::option::Option::None => ::core::option::Option::None, \
+                     }},"
+                ));
+                to.push_str(&format!(
+                    "if let ::core::option::Option::Some(value) = &self.{ident} {{ \
+                         element = element.with_attr({name:?}, ::std::string::ToString::to_string(value)); \
+                     }}"
+                ));
+            }
+            Role::Attr => {
+                from.push_str(&format!(
+                    "{ident}: ::jtl_rs::__parse_field(element, {name:?}, \
+                         ::jtl_rs::__required_attr(element, {name:?})?)?,"
+                ));
+                to.push_str(&format!(
+                    "element = element.with_attr({name:?}, ::std::string::ToString::to_string(&self.{ident}));"
+                ));
+            }
+        }
+    }
+    format!(
+        "impl ::jtl_rs::ElementMapping for {name} {{ \
+             fn from_element(element: &::jtl_rs::JtlElement) \
+                 -> ::core::result::Result<Self, ::std::string::String> {{ \
+                 ::core::result::Result::Ok({name} {{ {from} }}) \
+             }} \
+             fn to_element(&self) -> ::jtl_rs::JtlElement {{ \
+                 let mut element = ::jtl_rs::JtlElement::new({key}, {content}); \
+                 {to} \
+                 element \
+             }} \
+         }}",
+        name = item.name,
+    )
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+use std::ops::{Bound, RangeBounds};
+use std::str::FromStr;
+
+use crate::JtlElement;
+
+/// An attribute that is missing or does not hold the requested type, as
+/// returned by [`JtlElement::get`] and the other typed accessors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttrError {
+    key: String,
+    line: Option<usize>,
+    attr: String,
+    message: String,
+}
+
+impl AttrError {
+    fn new(element: &JtlElement, attr: &str, message: String) -> Self {
+        AttrError {
+            key: element.key.clone(),
+            line: element.line,
+            attr: attr.to_string(),
+            message,
+        }
+    }
+
+    /// Returns the key of the element the attribute was read from.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the line the element was declared on, if known.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// Returns the name of the attribute.
+    pub fn attr(&self) -> &str {
+        &self.attr
+    }
+}
+
+impl fmt::Display for AttrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid element `{}`", self.key)?;
+        if let Some(line) = self.line {
+            write!(f, " on line {}", line)?;
+        }
+        write!(f, ": attribute `{}` {}", self.attr, self.message)
+    }
+}
+
+impl std::error::Error for AttrError {}
+
+impl JtlElement {
+    /// Parses attribute `name` as a `T`, failing if it is missing or does not
+    /// parse.
+    pub fn get<T>(&self, name: &str) -> Result<T, AttrError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = self
+            .attr(name)
+            .ok_or_else(|| AttrError::new(self, name, "is missing".to_string()))?;
+        value
+            .parse()
+            .map_err(|err| AttrError::new(self, name, format!("`{}` is invalid: {}", value, err)))
+    }
+
+    /// Parses attribute `name` as a `T` and checks that it lies in `range`,
+    /// such as `0..=100`.
+    pub fn get_range<T>(&self, name: &str, range: impl RangeBounds<T>) -> Result<T, AttrError>
+    where
+        T: FromStr + PartialOrd + fmt::Display,
+        T::Err: fmt::Display,
+    {
+        let value: T = self.get(name)?;
+        if range.contains(&value) {
+            return Ok(value);
+        }
+        let bound = |bound: Bound<&T>| match bound {
+            Bound::Included(value) | Bound::Excluded(value) => value.to_string(),
+            Bound::Unbounded => String::new(),
+        };
+        let dots = match range.end_bound() {
+            Bound::Included(_) => "..=",
+            _ => "..",
+        };
+        Err(AttrError::new(
+            self,
+            name,
+            format!(
+                "`{}` is out of range {}{}{}",
+                value,
+                bound(range.start_bound()),
+                dots,
+                bound(range.end_bound())
+            ),
+        ))
+    }
+
+    /// Parses attribute `name` as a `bool`, written `true` or `false`.
+    pub fn get_bool(&self, name: &str) -> Result<bool, AttrError> {
+        self.get(name)
+    }
+
+    /// Parses attribute `name` as a `u16`, such as a port.
+    pub fn get_u16(&self, name: &str) -> Result<u16, AttrError> {
+        self.get(name)
+    }
+
+    /// Parses attribute `name` as a `u32`.
+    pub fn get_u32(&self, name: &str) -> Result<u32, AttrError> {
+        self.get(name)
+    }
+
+    /// Parses attribute `name` as a `u64`.
+    pub fn get_u64(&self, name: &str) -> Result<u64, AttrError> {
+        self.get(name)
+    }
+
+    /// Parses attribute `name` as an `i64`.
+    pub fn get_i64(&self, name: &str) -> Result<i64, AttrError> {
+        self.get(name)
+    }
+
+    /// Parses attribute `name` as an `f64`.
+    pub fn get_f64(&self, name: &str) -> Result<f64, AttrError> {
+        self.get(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::JtlDocument;
+
+    #[test]
+    fn test_typed_accessors() {
+        let text =
+            "DOCTYPE=JTL\n>>>BEGIN;\n>port=\"8080\" pct=\"150\" tls=\"yes\">web>nginx;\n>>>END;";
+        let doc = JtlDocument::parse(text).unwrap();
+        let web = &doc.elements[0];
+        assert_eq!(web.line, Some(3));
+        assert_eq!(web.get_u16("port"), Ok(8080));
+        assert_eq!(web.get_range("port", 1..=65535), Ok(8080u32));
+        assert_eq!(
+            web.get_range("pct", 0..=100u8).unwrap_err().to_string(),
+            "invalid element `web` on line 3: attribute `pct` `150` is out of range 0..=100"
+        );
+        assert_eq!(
+            web.get_bool("tls").unwrap_err().to_string(),
+            "invalid element `web` on line 3: attribute `tls` `yes` is invalid: \
+             provided string was not `true` or `false`"
+        );
+        let err = web
+            .clone()
+            .with_attr("big", "70000")
+            .get_u16("big")
+            .unwrap_err();
+        assert_eq!(err.attr(), "big");
+        assert!(err.to_string().contains("too large"));
+
+        let built = crate::JtlElement::new("db", "pg").with_attr("n", "1");
+        assert_eq!(
+            built.get_u16("port").unwrap_err().to_string(),
+            "invalid element `db`: attribute `port` is missing"
+        );
+        assert_eq!(built.get_range("n", ..1).unwrap_err().line(), None);
+    }
+}
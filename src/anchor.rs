@@ -0,0 +1,73 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Applies YAML-style anchors to an element's raw content.
+///
+/// Content of the form `&name payload` defines the anchor `name` and is
+/// replaced by `payload`; content that is exactly `*name` is replaced by the
+/// payload of an anchor defined earlier in the document. Anything else is
+/// returned unchanged.
+pub(crate) fn resolve<'a>(
+    content: &'a str,
+    anchors: &mut HashMap<String, String>,
+) -> Result<Cow<'a, str>, String> {
+    if let Some(definition) = content.strip_prefix('&') {
+        let (name, payload) = definition
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| format!("anchor `&{}` has no content", definition))?;
+        check_name(name)?;
+        anchors.insert(name.to_string(), payload.to_string());
+        return Ok(Cow::Borrowed(payload));
+    }
+    match content.strip_prefix('*') {
+        Some(name) if is_name(name) => anchors
+            .get(name)
+            .map(|payload| Cow::Owned(payload.clone()))
+            .ok_or_else(|| format!("undefined anchor `*{}`", name)),
+        _ => Ok(Cow::Borrowed(content)),
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    if is_name(name) {
+        Ok(())
+    } else {
+        Err(format!("invalid anchor name `{}`", name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let mut anchors = HashMap::new();
+        assert_eq!(
+            resolve("&) payload", &mut anchors).unwrap_err(),
+            "invalid anchor name `)`"
+        );
+        assert_eq!(
+            resolve("&cert -----BEGIN-----", &mut anchors).unwrap(),
+            "-----BEGIN-----"
+        );
+        assert_eq!(resolve("*cert", &mut anchors).unwrap(), "-----BEGIN-----");
+        assert_eq!(
+            resolve("*nope", &mut anchors).unwrap_err(),
+            "undefined anchor `*nope`"
+        );
+        assert_eq!(
+            resolve("&cert", &mut anchors).unwrap_err(),
+            "anchor `&cert` has no content"
+        );
+        assert_eq!(resolve("2 * 3", &mut anchors).unwrap(), "2 * 3");
+        assert_eq!(
+            resolve("*not an alias", &mut anchors).unwrap(),
+            "*not an alias"
+        );
+    }
+}
@@ -0,0 +1,56 @@
+/// Reads the `@name` and `@name=value` annotations in the text of a `>//>`
+/// comment. Words that don't start with `@` are ignored, so annotations can
+/// be mixed with ordinary remarks.
+pub(crate) fn parse_annotations(comment: &str) -> impl Iterator<Item = (String, String)> + '_ {
+    comment
+        .trim()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .filter_map(|word| word.strip_prefix('@'))
+        .map(|word| word.split_once('=').unwrap_or((word, "")))
+        .filter(|(name, _)| is_annotation_name(name))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+}
+
+/// Returns whether `name` can be written as an annotation name.
+pub(crate) fn is_annotation_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Renders annotations as a single `>//>` comment line.
+pub(crate) fn annotation_line(annotations: &[(String, String)]) -> String {
+    let words: Vec<String> = annotations
+        .iter()
+        .map(|(name, value)| match value.as_str() {
+            "" => format!("@{}", name),
+            _ => format!("@{}={}", name, value),
+        })
+        .collect();
+    format!(">//> {}", words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_annotations() {
+        let annotations: Vec<(String, String)> =
+            parse_annotations(" @deprecated use v2 @owner=team-x;").collect();
+        assert_eq!(
+            annotations,
+            [
+                ("deprecated".to_string(), String::new()),
+                ("owner".to_string(), "team-x".to_string()),
+            ]
+        );
+        assert_eq!(
+            annotation_line(&annotations),
+            ">//> @deprecated @owner=team-x"
+        );
+        assert_eq!(parse_annotations("plain remark @ @a!b").count(), 0);
+    }
+}
@@ -0,0 +1,308 @@
+use std::error::Error;
+use std::ops::Range;
+
+use crate::{JtlDocument, JtlElement};
+
+const MAGIC: &[u8; 4] = b"JTLA";
+const HEADER: usize = 16;
+const PAIR: usize = 16;
+const ELEMENT: usize = 24;
+
+/// Encodes a document as an archive that [`ArchivedDocument`] can query in
+/// place.
+///
+/// The layout is a header, fixed-size tables for env variables (sorted by
+/// name), elements and attributes, then all strings. Integers are
+/// little-endian `u32`s. Annotations and the text format are not stored.
+///
+/// # Panics
+///
+/// Panics if the document has 4 GiB of strings or more.
+pub fn to_archive(document: &JtlDocument) -> Vec<u8> {
+    let mut env: Vec<(&String, &String)> = document.env.iter().collect();
+    env.sort();
+    let attrs: usize = document.elements.iter().map(|e| e.attrs.len()).sum();
+
+    let mut tables = Vec::new();
+    let mut strings = Vec::new();
+    let mut string = |tables: &mut Vec<u8>, s: &str| {
+        put(tables, strings.len());
+        put(tables, s.len());
+        strings.extend_from_slice(s.as_bytes());
+    };
+    for (name, value) in &env {
+        string(&mut tables, name);
+        string(&mut tables, value);
+    }
+    let mut first_attr = 0;
+    for element in &document.elements {
+        string(&mut tables, &element.key);
+        string(&mut tables, &element.content);
+        put(&mut tables, first_attr);
+        put(&mut tables, element.attrs.len());
+        first_attr += element.attrs.len();
+    }
+    for (name, value) in document.elements.iter().flat_map(|e| &e.attrs) {
+        string(&mut tables, name);
+        string(&mut tables, value);
+    }
+
+    let mut out = MAGIC.to_vec();
+    put(&mut out, env.len());
+    put(&mut out, document.elements.len());
+    put(&mut out, attrs);
+    out.extend_from_slice(&tables);
+    out.extend_from_slice(&strings);
+    out
+}
+
+fn put(out: &mut Vec<u8>, n: usize) {
+    let n = u32::try_from(n).expect("archive fields fit in u32");
+    out.extend_from_slice(&n.to_le_bytes());
+}
+
+fn get(bytes: &[u8], at: usize) -> usize {
+    let word: [u8; 4] = bytes[at..at + 4].try_into().expect("four bytes");
+    u32::from_le_bytes(word) as usize
+}
+
+/// A document read straight out of an archive written by [`to_archive`],
+/// such as a memory-mapped file.
+///
+/// Opening an archive checks it once; after that, lookups slice the
+/// underlying bytes and never copy or allocate. Env lookups are a binary
+/// search, element lookups are by index.
+#[derive(Debug, Clone, Copy)]
+pub struct ArchivedDocument<'a> {
+    bytes: &'a [u8],
+    envs: usize,
+    elements: usize,
+    attrs: usize,
+}
+
+impl<'a> ArchivedDocument<'a> {
+    /// Checks `bytes` and opens them as an archive.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Box<dyn Error>> {
+        if bytes.len() < HEADER || &bytes[..4] != MAGIC {
+            return Err("invalid JTL archive: bad header".into());
+        }
+        let archive = ArchivedDocument {
+            bytes,
+            envs: get(bytes, 4),
+            elements: get(bytes, 8),
+            attrs: get(bytes, 12),
+        };
+        let truncated = || "invalid JTL archive: truncated".to_string();
+        let strings = archive
+            .envs
+            .checked_mul(PAIR)
+            .zip(archive.elements.checked_mul(ELEMENT))
+            .zip(archive.attrs.checked_mul(PAIR))
+            .and_then(|((envs, elements), attrs)| {
+                HEADER
+                    .checked_add(envs)?
+                    .checked_add(elements)?
+                    .checked_add(attrs)
+            })
+            .filter(|strings| *strings <= bytes.len())
+            .ok_or_else(truncated)?;
+
+        let mut refs: Vec<usize> = Vec::new();
+        for index in 0..archive.envs {
+            let at = archive.env_entry(index);
+            refs.extend([at, at + 8]);
+        }
+        for index in 0..archive.elements {
+            let at = archive.element_entry(index);
+            refs.extend([at, at + 8]);
+            let (start, count) = (get(bytes, at + 16), get(bytes, at + 20));
+            if start
+                .checked_add(count)
+                .is_none_or(|end| end > archive.attrs)
+            {
+                return Err(
+                    format!("invalid JTL archive: element {} has bad attributes", index).into(),
+                );
+            }
+        }
+        for index in 0..archive.attrs {
+            let at = archive.attr_entry(index);
+            refs.extend([at, at + 8]);
+        }
+        for at in refs {
+            let (start, len) = (get(bytes, at), get(bytes, at + 4));
+            let range = strings
+                .checked_add(start)
+                .and_then(|start| Some(start..start.checked_add(len)?))
+                .filter(|range| range.end <= bytes.len())
+                .ok_or_else(truncated)?;
+            std::str::from_utf8(&bytes[range])
+                .map_err(|err| format!("invalid JTL archive: {}", err))?;
+        }
+        if (1..archive.envs).any(|index| archive.env_at(index - 1).0 >= archive.env_at(index).0) {
+            return Err("invalid JTL archive: env variables are not sorted".into());
+        }
+        Ok(archive)
+    }
+
+    /// Returns the value of env variable `name`.
+    pub fn env(&self, name: &str) -> Option<&'a str> {
+        let (mut low, mut high) = (0, self.envs);
+        while low < high {
+            let middle = (low + high) / 2;
+            let (found, value) = self.env_at(middle);
+            match found.cmp(name) {
+                std::cmp::Ordering::Equal => return Some(value),
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+            }
+        }
+        None
+    }
+
+    /// Iterates over the env variables, sorted by name.
+    pub fn env_vars(&self) -> impl Iterator<Item = (&'a str, &'a str)> + '_ {
+        (0..self.envs).map(|index| self.env_at(index))
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.elements
+    }
+
+    /// Returns whether the document has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.elements == 0
+    }
+
+    /// Returns the element at `index`.
+    pub fn element(&self, index: usize) -> Option<ArchivedElement<'a>> {
+        (index < self.elements).then_some(ArchivedElement {
+            archive: *self,
+            at: self.element_entry(index),
+        })
+    }
+
+    /// Iterates over the elements in document order.
+    pub fn elements(&self) -> impl Iterator<Item = ArchivedElement<'a>> + '_ {
+        (0..self.elements).filter_map(|index| self.element(index))
+    }
+
+    /// Copies the archive back into an owned document.
+    pub fn to_document(&self) -> JtlDocument {
+        let mut document = JtlDocument::new();
+        document.env = self
+            .env_vars()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        document.elements = self.elements().map(|e| e.to_element()).collect();
+        document
+    }
+
+    fn env_entry(&self, index: usize) -> usize {
+        HEADER + index * PAIR
+    }
+
+    fn element_entry(&self, index: usize) -> usize {
+        HEADER + self.envs * PAIR + index * ELEMENT
+    }
+
+    fn attr_entry(&self, index: usize) -> usize {
+        HEADER + self.envs * PAIR + self.elements * ELEMENT + index * PAIR
+    }
+
+    fn env_at(&self, index: usize) -> (&'a str, &'a str) {
+        let at = self.env_entry(index);
+        (self.str_at(at), self.str_at(at + 8))
+    }
+
+    fn attrs_range(&self, at: usize) -> Range<usize> {
+        let start = get(self.bytes, at + 16);
+        start..start + get(self.bytes, at + 20)
+    }
+
+    /// Reads the string whose offset and length are stored at `at`.
+    fn str_at(&self, at: usize) -> &'a str {
+        let start = self.attr_entry(self.attrs) + get(self.bytes, at);
+        let bytes = &self.bytes[start..start + get(self.bytes, at + 4)];
+        // SAFETY: `new` is the only way to build an archive, and it checks that
+        // every string reference in the tables points at valid UTF-8 in the
+        // string section. `at` is always one of those references.
+        unsafe { std::str::from_utf8_unchecked(bytes) }
+    }
+}
+
+/// An element inside an [`ArchivedDocument`].
+#[derive(Debug, Clone, Copy)]
+pub struct ArchivedElement<'a> {
+    archive: ArchivedDocument<'a>,
+    at: usize,
+}
+
+impl<'a> ArchivedElement<'a> {
+    /// Returns the element key.
+    pub fn key(&self) -> &'a str {
+        self.archive.str_at(self.at)
+    }
+
+    /// Returns the element content.
+    pub fn content(&self) -> &'a str {
+        self.archive.str_at(self.at + 8)
+    }
+
+    /// Returns the value of the attribute called `name`; the last one wins
+    /// if it is repeated.
+    pub fn attr(&self, name: &str) -> Option<&'a str> {
+        self.attrs()
+            .filter(|(found, _)| *found == name)
+            .last()
+            .map(|(_, value)| value)
+    }
+
+    /// Iterates over the attributes in source order.
+    pub fn attrs(&self) -> impl Iterator<Item = (&'a str, &'a str)> + '_ {
+        self.archive.attrs_range(self.at).map(|index| {
+            let at = self.archive.attr_entry(index);
+            (self.archive.str_at(at), self.archive.str_at(at + 8))
+        })
+    }
+
+    /// Copies the element into an owned [`JtlElement`].
+    pub fn to_element(&self) -> JtlElement {
+        let mut element = JtlElement::new(self.key(), self.content());
+        for (name, value) in self.attrs() {
+            element = element.with_attr(name, value);
+        }
+        element
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive() {
+        let document = JtlDocument::parse(
+            "DOCTYPE=JTL\n>>>ENV;\n>>>port=80;\n>>>host=localhost;\n>>>BEGIN;\n\
+             >port=\"80\" tls=\"no\">web>$env:host;\n>n=\"1\">db>postgres;\n>>>END;",
+        )
+        .unwrap();
+        let bytes = to_archive(&document);
+        let archive = ArchivedDocument::new(&bytes).unwrap();
+        assert_eq!(archive.env("host"), Some("localhost"));
+        assert_eq!(archive.env("missing"), None);
+        assert_eq!(archive.len(), 2);
+        let web = archive.element(0).unwrap();
+        assert_eq!((web.key(), web.content()), ("web", "localhost"));
+        assert_eq!(web.attr("tls"), Some("no"));
+        assert_eq!(archive.element(1).unwrap().attrs().count(), 1);
+        assert!(archive.element(2).is_none());
+        assert_eq!(archive.to_document(), document);
+
+        assert!(ArchivedDocument::new(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ArchivedDocument::new(b"JTLB").is_err());
+        let empty = to_archive(&JtlDocument::empty());
+        assert!(ArchivedDocument::new(&empty).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::poll_fn;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::element::JtlElement;
+use crate::stream::LineParser;
+
+/// An asynchronous buffered reader, shaped like `AsyncBufRead` in `tokio` and
+/// `futures` so any runtime's reader can implement it.
+///
+/// The crate has no runtime dependency; to parse a `tokio` reader, wrap it in
+/// a newtype whose methods forward to `tokio::io::AsyncBufRead`.
+pub trait AsyncBufRead {
+    /// Returns the buffered bytes, reading more if the buffer is empty. An
+    /// empty slice means the end of the input.
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>>;
+
+    /// Marks `amt` bytes of the buffer as read.
+    fn consume(self: Pin<&mut Self>, amt: usize);
+}
+
+impl AsyncBufRead for &[u8] {
+    fn poll_fill_buf(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Poll::Ready(Ok(self.get_mut()))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        *this = &this[amt..];
+    }
+}
+
+impl<R: AsyncBufRead + Unpin + ?Sized> AsyncBufRead for &mut R {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut **self.get_mut()).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut **self.get_mut()).consume(amt)
+    }
+}
+
+impl<R: AsyncBufRead + Unpin + ?Sized> AsyncBufRead for Box<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        Pin::new(&mut **self.get_mut()).poll_fill_buf(cx)
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        Pin::new(&mut **self.get_mut()).consume(amt)
+    }
+}
+
+/// Parses JTL from an asynchronous `reader` one element at a time.
+///
+/// This is [`parse_reader`](crate::parse_reader) for async code: the elements,
+/// errors and environment are the same, but reading waits on the runtime
+/// instead of blocking the thread.
+///
+/// ```
+/// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+/// let body: &[u8] = b"DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">a>x;\n>>>END;\n";
+/// let mut elements = jtl_rs::parse_async(body);
+/// while let Some(element) = elements.next().await {
+///     assert_eq!(element?.key, "a");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_async<R: AsyncBufRead + Unpin>(reader: R) -> AsyncElements<R> {
+    AsyncElements {
+        reader,
+        line: Vec::new(),
+        parser: LineParser::default(),
+        done: false,
+    }
+}
+
+/// Elements read by [`parse_async`].
+#[derive(Debug)]
+pub struct AsyncElements<R> {
+    reader: R,
+    line: Vec<u8>,
+    parser: LineParser,
+    done: bool,
+}
+
+impl<R> AsyncElements<R> {
+    /// Returns the variables declared so far.
+    pub fn env(&self) -> &HashMap<String, String> {
+        &self.parser.env
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncElements<R> {
+    /// Returns the next element, or `None` at the end of the input. Nothing is
+    /// returned after the first error.
+    pub async fn next(&mut self) -> Option<Result<JtlElement, Box<dyn Error>>> {
+        poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    /// Polls for the next element, for use in hand-written futures and
+    /// streams.
+    pub fn poll_next(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<JtlElement, Box<dyn Error>>>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        let result = match self.poll_element(cx) {
+            Poll::Ready(result) => result.transpose(),
+            Poll::Pending => return Poll::Pending,
+        };
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        Poll::Ready(result)
+    }
+
+    fn poll_element(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<JtlElement>, Box<dyn Error>>> {
+        loop {
+            match self.parser.next_pending() {
+                Ok(None) => {}
+                result => return Poll::Ready(result),
+            }
+            let ended = match self.poll_line(cx) {
+                Poll::Ready(Ok(ended)) => ended,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err.into())),
+                Poll::Pending => return Poll::Pending,
+            };
+            if ended {
+                return Poll::Ready(self.parser.finish().map(|()| None));
+            }
+            let line = std::mem::take(&mut self.line);
+            let text = String::from_utf8(line).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                )
+            });
+            if let Err(err) = text.map(|text| self.parser.push_line(&text)) {
+                return Poll::Ready(Err(err.into()));
+            }
+        }
+    }
+
+    /// Reads the next line into `self.line`, returning whether the input
+    /// ended before any of it.
+    fn poll_line(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        loop {
+            let mut reader = Pin::new(&mut self.reader);
+            let buf = match reader.as_mut().poll_fill_buf(cx) {
+                Poll::Ready(Ok(buf)) => buf,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+            if buf.is_empty() {
+                return Poll::Ready(Ok(self.line.is_empty()));
+            }
+            let (used, complete) = match buf.iter().position(|&byte| byte == b'\n') {
+                Some(end) => (end + 1, true),
+                None => (buf.len(), false),
+            };
+            self.line.extend_from_slice(&buf[..used]);
+            reader.consume(used);
+            if complete {
+                return Poll::Ready(Ok(false));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::task::Waker;
+
+    /// Runs a future to completion on the current thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    /// Hands out its input a few bytes at a time, pending before each piece.
+    struct Trickle<'a> {
+        input: &'a [u8],
+        ready: bool,
+    }
+
+    impl AsyncBufRead for Trickle<'_> {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+            let this = self.get_mut();
+            if !this.ready {
+                this.ready = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Poll::Ready(Ok(&this.input[..this.input.len().min(3)]))
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            let this = self.get_mut();
+            this.input = &this.input[amt..];
+            this.ready = false;
+        }
+    }
+
+    #[test]
+    fn test_parse_async() {
+        let text = "DOCTYPE=JTL\r\n>>>ENV;\n>>>foo=bar;\n>>>BEGIN;\n>n=\"1\">a>$env:foo; >n=\"2\">b>x;\n>bad;";
+        let reader = Trickle {
+            input: text.as_bytes(),
+            ready: false,
+        };
+        let mut elements = parse_async(reader);
+        let a = block_on(elements.next()).unwrap().unwrap();
+        assert_eq!((a.content.as_str(), a.line), ("bar", Some(5)));
+        assert_eq!(elements.env()["foo"], "bar");
+        assert_eq!(block_on(elements.next()).unwrap().unwrap().key, "b");
+        assert!(block_on(elements.next()).unwrap().is_err());
+        assert!(block_on(elements.next()).is_none());
+
+        let sync: Vec<JtlElement> = crate::parse_reader(text.as_bytes())
+            .take(2)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let mut elements = parse_async(text.as_bytes());
+        let first = block_on(elements.next()).unwrap().unwrap();
+        assert_eq!(first, sync[0]);
+    }
+
+    #[test]
+    fn test_parse_async_errors() {
+        let mut elements = parse_async(&b"nope\n"[..]);
+        let err = block_on(elements.next()).unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "invalid JTL document: missing DOCTYPE");
+
+        let mut elements = parse_async(&b"DOCTYPE=JTL\n>>>BEGIN;\n\xff\n"[..]);
+        let err = block_on(elements.next()).unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "stream did not contain valid UTF-8");
+
+        fn assert_send<T: Send>(_: &T) {}
+        let mut elements = parse_async(&b""[..]);
+        assert_send(&elements.next());
+    }
+}
@@ -0,0 +1,171 @@
+use crate::element::JtlElement;
+use serde_json::{json, Value};
+use std::error::Error;
+
+/// An Avro record schema derived from a set of elements sharing one attribute layout.
+///
+/// Every field is an Avro `string`: `KEY`, `Content`, then the attributes in the
+/// order they appear on the first element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AvroSchema {
+    name: String,
+    fields: Vec<String>,
+}
+
+impl AvroSchema {
+    /// Derives a record schema named `name` from `elements`.
+    ///
+    /// Fails if the elements don't all have the same attribute names, or if a
+    /// name isn't a valid Avro name.
+    pub fn derive(name: &str, elements: &[JtlElement]) -> Result<Self, Box<dyn Error>> {
+        check_name(name)?;
+        let first = elements
+            .first()
+            .ok_or("avro: cannot derive a schema from no elements")?;
+
+        let mut fields = vec!["KEY".to_string(), "Content".to_string()];
+        for (attr, _) in &first.attrs {
+            check_name(attr)?;
+            if fields.contains(attr) {
+                return Err(format!("avro: duplicate field `{}`", attr).into());
+            }
+            fields.push(attr.clone());
+        }
+
+        let mut expected: Vec<&str> = fields[2..].iter().map(String::as_str).collect();
+        expected.sort_unstable();
+        for element in elements {
+            let mut names: Vec<&str> = element
+                .attrs
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect();
+            names.sort_unstable();
+            if names != expected {
+                return Err(format!(
+                    "avro: element `{}` does not have the same attributes as `{}`",
+                    element.key, first.key
+                )
+                .into());
+            }
+        }
+
+        Ok(AvroSchema {
+            name: name.to_string(),
+            fields,
+        })
+    }
+
+    /// Returns the field names, in record order.
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// Returns the schema as Avro JSON schema.
+    pub fn to_json(&self) -> Value {
+        let fields: Vec<Value> = self
+            .fields
+            .iter()
+            .map(|field| json!({ "name": field, "type": "string" }))
+            .collect();
+        json!({ "type": "record", "name": self.name, "fields": fields })
+    }
+
+    /// Encodes one element as an Avro binary record.
+    pub fn encode(&self, element: &JtlElement) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut out = Vec::new();
+        write_string(&mut out, &element.key);
+        write_string(&mut out, &element.content);
+        for field in &self.fields[2..] {
+            let value = element.attr(field).ok_or_else(|| {
+                format!(
+                    "avro: element `{}` is missing field `{}`",
+                    element.key, field
+                )
+            })?;
+            write_string(&mut out, value);
+        }
+        Ok(out)
+    }
+}
+
+fn check_name(name: &str) -> Result<(), Box<dyn Error>> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("avro: `{}` is not a valid Avro name", name).into())
+    }
+}
+
+fn write_string(out: &mut Vec<u8>, s: &str) {
+    write_long(out, s.len() as i64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_long(out: &mut Vec<u8>, n: i64) {
+    // Zig-zag encoding followed by a base-128 varint.
+    let mut n = ((n << 1) ^ (n >> 63)) as u64;
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_and_encode() {
+        let elements = vec![
+            JtlElement::new("alice", "admin")
+                .with_attr("id", "1")
+                .with_attr("team", "core"),
+            JtlElement::new("bob", "user")
+                .with_attr("team", "web")
+                .with_attr("id", "2"),
+        ];
+        let schema = AvroSchema::derive("User", &elements).unwrap();
+        assert_eq!(schema.fields(), ["KEY", "Content", "id", "team"]);
+        assert_eq!(
+            schema.to_json(),
+            json!({
+                "type": "record",
+                "name": "User",
+                "fields": [
+                    { "name": "KEY", "type": "string" },
+                    { "name": "Content", "type": "string" },
+                    { "name": "id", "type": "string" },
+                    { "name": "team", "type": "string" },
+                ]
+            })
+        );
+        assert_eq!(
+            schema.encode(&elements[1]).unwrap(),
+            b"\x06bob\x08user\x022\x06web".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_derive_rejects_mixed_elements() {
+        let elements = vec![
+            JtlElement::new("a", "x").with_attr("id", "1"),
+            JtlElement::new("b", "x").with_attr("name", "b"),
+        ];
+        let err = AvroSchema::derive("Row", &elements).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "avro: element `b` does not have the same attributes as `a`"
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::scan::Scanner;
+use crate::{JtlElement, ParseOptions};
+
+/// Splits `text` into declarations, as the parser does before reading them,
+/// and returns how many there are.
+pub fn lex(text: &str) -> usize {
+    let mut scanner = Scanner::default();
+    text.lines()
+        .map(|line| scanner.scan_line(line).count())
+        .sum()
+}
+
+/// Splits one element declaration into key, attributes and content, without
+/// touching the environment.
+pub fn parse_attributes(decl: &str) -> Result<JtlElement, Box<dyn Error>> {
+    let raw = crate::parse_raw_element(decl)?;
+    let mut element = JtlElement::new(raw.key, raw.content);
+    for (name, value) in raw.attrs {
+        element = element.with_attr(name, value);
+    }
+    Ok(element)
+}
+
+/// Resolves `$env:NAME` and `${env:NAME}` references in one content.
+pub fn resolve_env(content: &str, env: &HashMap<String, String>) -> Result<String, Box<dyn Error>> {
+    if let Some(value) = crate::env_reference(content).and_then(|name| env.get(name)) {
+        return Ok(value.clone());
+    }
+    Ok(crate::interp::interpolate(content, env, &ParseOptions::default())?.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stages() {
+        let text =
+            "DOCTYPE=JTL\n>>>ENV;\n>>>host=db;\n>>>BEGIN;\n>a=\"1\">k>v;>b=\"2\">l>w;\n>>>END;";
+        assert_eq!(lex(text), 3);
+        assert_eq!(
+            parse_attributes(">a=\"1\">k>v;").unwrap().attr("a"),
+            Some("1")
+        );
+        let env = HashMap::from([("host".to_string(), "db".to_string())]);
+        assert_eq!(resolve_env("$env:host", &env).unwrap(), "db");
+        assert_eq!(resolve_env("at ${env:host}", &env).unwrap(), "at db");
+    }
+}
@@ -0,0 +1,543 @@
+//! Command-line tools for JTL documents.
+
+use jtl_rs::{
+    diff, doctor, env_references, merge, parse_with_warnings, DuplicateEnv, JtlDocument,
+    JtlElement, Matcher, ParseOptions,
+};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::fs;
+use std::io::IsTerminal;
+use std::process::ExitCode;
+
+#[cfg(feature = "repl")]
+mod repl;
+
+const USAGE: &str = "usage:
+  jtl check <file.jtl>...
+  jtl doctor <file.jtl>
+  jtl diff <a.jtl> <b.jtl>
+  jtl merge <base.jtl> <overlay.jtl> [-o <out.jtl>]
+  jtl env <file.jtl> [--set NAME=VALUE]... [--os-env]
+  jtl stats <file.jtl>
+  jtl grep <pattern> <file.jtl> [--regex]
+  jtl repl <file.jtl>   (with the `repl` feature)
+  jtl completions <bash|zsh|fish>
+
+Every subcommand but repl and completions accepts --output json.";
+
+const COMMANDS: &[&str] = &[
+    "check",
+    "doctor",
+    "diff",
+    "merge",
+    "env",
+    "stats",
+    "grep",
+    "repl",
+    "completions",
+    "help",
+];
+const OPTIONS: &[&str] = &["--output", "-o", "--set", "--os-env", "--regex"];
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(code) => code,
+        Err(err) => {
+            if args.windows(2).any(|pair| pair == ["--output", "json"]) {
+                println!("{}", json!({ "error": err.to_string() }));
+            } else {
+                eprintln!("jtl: {}", err);
+            }
+            ExitCode::from(2)
+        }
+    }
+}
+
+/// Runs one subcommand. `check` and `doctor` exit with 1 when a document has
+/// errors, `diff` when the documents differ, and `grep` when nothing matches.
+///
+/// With `--output json`, each subcommand prints one JSON value to stdout:
+/// - `check`: an array of `{"file", "severity", "line", "message"}`, where
+///   `severity` is `error` or `warning` and `line` may be null;
+/// - `doctor`: `{"file", "healthy", "elements", "env", "findings"}`, where each
+///   finding is `{"category", "severity", "line", "message"}`;
+/// - `diff`: an array of [`Difference::to_json`](jtl_rs::Difference::to_json) objects;
+/// - `merge`: `{"env", "elements"}` for the merged document, or
+///   `{"written", "elements"}` when `-o` is given;
+/// - `env`: an array of `{"name", "value", "source", "references"}`;
+/// - `stats`: `{"elements", "env", "keys", "bytes"}`;
+/// - `grep`: an array of `{"index", "key", "content", "spans"}`, where
+///   `spans` holds `[start, end]` byte offsets into `content`.
+///
+/// Failures print `{"error"}` instead of a message on stderr.
+fn run(args: &[String]) -> Result<ExitCode, Box<dyn Error>> {
+    let (command, rest) = args.split_first().ok_or(USAGE)?;
+    let args = Args::parse(rest)?;
+    match command.as_str() {
+        "check" => {
+            let mut diagnostics = Vec::new();
+            for file in &args.files {
+                diagnostics.extend(check(file));
+            }
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&diagnostics)?);
+            } else {
+                for diagnostic in &diagnostics {
+                    let location = match diagnostic["line"].as_u64() {
+                        Some(line) => format!(
+                            "{}:{}",
+                            diagnostic["file"].as_str().unwrap_or_default(),
+                            line
+                        ),
+                        None => diagnostic["file"].as_str().unwrap_or_default().to_string(),
+                    };
+                    println!(
+                        "{}: {}: {}",
+                        location,
+                        diagnostic["severity"].as_str().unwrap_or_default(),
+                        diagnostic["message"].as_str().unwrap_or_default()
+                    );
+                }
+            }
+            let failed = diagnostics.iter().any(|d| d["severity"] == "error");
+            Ok(ExitCode::from(u8::from(failed)))
+        }
+        "doctor" => {
+            let [file] = args.files()?;
+            let report = doctor(&fs::read_to_string(file)?);
+            if args.json {
+                let findings: Vec<Value> = report
+                    .findings
+                    .iter()
+                    .map(|finding| {
+                        json!({
+                            "category": finding.category.as_str(),
+                            "severity": finding.severity.as_str(),
+                            "line": finding.line,
+                            "message": finding.message,
+                        })
+                    })
+                    .collect();
+                let summary = json!({
+                    "file": file,
+                    "healthy": report.is_healthy(),
+                    "elements": report.elements,
+                    "env": report.env,
+                    "findings": findings,
+                });
+                println!("{}", serde_json::to_string_pretty(&summary)?);
+            } else {
+                for finding in &report.findings {
+                    println!("{}: {}", file, finding);
+                }
+                println!(
+                    "{}: {} elements, {} env variables: {} errors, {} warnings",
+                    file,
+                    report.elements,
+                    report.env,
+                    report.errors().count(),
+                    report.warnings().count()
+                );
+            }
+            Ok(ExitCode::from(u8::from(!report.is_healthy())))
+        }
+        "diff" => {
+            let [a, b] = args.files()?;
+            let differences = diff(&JtlDocument::load(a)?, &JtlDocument::load(b)?);
+            if args.json {
+                let values: Vec<Value> = differences.iter().map(|d| d.to_json()).collect();
+                println!("{}", serde_json::to_string_pretty(&values)?);
+            } else {
+                for difference in &differences {
+                    println!("{}", difference);
+                }
+            }
+            Ok(ExitCode::from(u8::from(!differences.is_empty())))
+        }
+        "merge" => {
+            let [base, overlay] = args.files()?;
+            let merged = merge(&JtlDocument::load(base)?, &JtlDocument::load(overlay)?);
+            match &args.out {
+                Some(path) => {
+                    merged.save_atomic(path)?;
+                    if args.json {
+                        let summary = json!({ "written": path, "elements": merged.elements.len() });
+                        println!("{}", summary);
+                    }
+                }
+                None if args.json => {
+                    let elements: Vec<Value> = merged.elements.iter().map(element_json).collect();
+                    let document = json!({ "env": merged.env, "elements": elements });
+                    println!("{}", serde_json::to_string_pretty(&document)?);
+                }
+                None => drop(merged.write_to(std::io::stdout().lock())?),
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        "env" => {
+            let [file] = args.files()?;
+            let text = fs::read_to_string(file)?;
+            let entries = resolve_env(&text, &args.overrides, args.os_env)?;
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else {
+                for entry in &entries {
+                    let value = entry["value"].as_str().unwrap_or("(unset)");
+                    println!(
+                        "{}={}  [{}, {} references]",
+                        entry["name"].as_str().unwrap_or_default(),
+                        value,
+                        entry["source"].as_str().unwrap_or_default(),
+                        entry["references"]
+                    );
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        "stats" => {
+            let [file] = args.files()?;
+            let text = fs::read_to_string(file)?;
+            let document = JtlDocument::parse(&text)?;
+            let keys: BTreeSet<&str> = document.elements.iter().map(|e| e.key.as_str()).collect();
+            let stats = json!({
+                "elements": document.elements.len(),
+                "env": document.env.len(),
+                "keys": keys.len(),
+                "bytes": text.len(),
+            });
+            if args.json {
+                println!("{}", serde_json::to_string_pretty(&stats)?);
+            } else {
+                for name in ["elements", "env", "keys", "bytes"] {
+                    println!("{:<9}{}", name, stats[name]);
+                }
+            }
+            Ok(ExitCode::SUCCESS)
+        }
+        "grep" => {
+            let [pattern, file] = args.files()?;
+            let document = JtlDocument::load(file)?;
+            let matcher = if args.regex {
+                Matcher::Regex(pattern.to_string())
+            } else {
+                Matcher::Text(pattern.to_string())
+            };
+            let hits = document.search(matcher)?;
+            if args.json {
+                let values: Vec<Value> = hits
+                    .iter()
+                    .map(|hit| {
+                        let spans: Vec<[usize; 2]> = hit
+                            .spans
+                            .iter()
+                            .map(|span| [span.start, span.end])
+                            .collect();
+                        json!({
+                            "index": hit.index,
+                            "key": hit.element.key,
+                            "content": hit.element.content,
+                            "spans": spans,
+                        })
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&values)?);
+            } else {
+                let (open, close) = if std::io::stdout().is_terminal() {
+                    ("\x1b[1m", "\x1b[0m")
+                } else {
+                    ("", "")
+                };
+                for hit in &hits {
+                    println!(
+                        "{}: {}: {}",
+                        hit.index,
+                        hit.element.key,
+                        hit.highlight(open, close)
+                    );
+                }
+            }
+            Ok(ExitCode::from(u8::from(hits.is_empty())))
+        }
+        "completions" => {
+            let [shell] = args.files()?;
+            print!("{}", completions(shell)?);
+            Ok(ExitCode::SUCCESS)
+        }
+        #[cfg(feature = "repl")]
+        "repl" => {
+            let [file] = args.files()?;
+            let stdin = std::io::stdin();
+            repl::run(file.as_ref(), stdin.lock(), std::io::stdout())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(ExitCode::SUCCESS)
+        }
+        _ => Err(format!("unknown command `{}`\n{}", command, USAGE).into()),
+    }
+}
+
+/// Parses `file`, returning its error or warnings as diagnostics.
+fn check(file: &str) -> Vec<Value> {
+    let diagnostic = |severity: &str, line: Option<usize>, message: String| json!({ "file": file, "severity": severity, "line": line, "message": message });
+    let options = ParseOptions::new().duplicate_env(DuplicateEnv::Warn);
+    let parsed = fs::read_to_string(file)
+        .map_err(Box::<dyn Error>::from)
+        .and_then(|text| parse_with_warnings(&text, &options));
+    match parsed {
+        Ok((_, warnings)) => warnings
+            .into_iter()
+            .map(|warning| diagnostic("warning", Some(warning.line), warning.message))
+            .collect(),
+        Err(err) => vec![diagnostic("error", None, err.to_string())],
+    }
+}
+
+/// Converts an element to the JSON shape produced by [`jtl_rs::parse`].
+fn element_json(element: &JtlElement) -> Value {
+    let mut map = serde_json::Map::new();
+    for (name, value) in &element.attrs {
+        map.insert(name.clone(), json!(value));
+    }
+    map.insert("KEY".to_string(), json!(element.key));
+    map.insert("Content".to_string(), json!(element.content));
+    map.insert("Contents".to_string(), json!(element.content));
+    Value::Object(map)
+}
+
+/// Generates a completion script for `shell`.
+fn completions(shell: &str) -> Result<String, String> {
+    let commands = COMMANDS.join(" ");
+    let options = OPTIONS.join(" ");
+    match shell {
+        "bash" => Ok(format!(
+            "_jtl() {{
+    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"
+    if [ \"$COMP_CWORD\" -eq 1 ]; then
+        COMPREPLY=($(compgen -W \"{commands}\" -- \"$cur\"))
+    elif [ \"$prev\" = \"--output\" ]; then
+        COMPREPLY=($(compgen -W \"text json\" -- \"$cur\"))
+    elif [ \"$prev\" = \"completions\" ]; then
+        COMPREPLY=($(compgen -W \"bash zsh fish\" -- \"$cur\"))
+    elif [[ \"$cur\" == -* ]]; then
+        COMPREPLY=($(compgen -W \"{options}\" -- \"$cur\"))
+    else
+        COMPREPLY=($(compgen -f -- \"$cur\"))
+    fi
+}}
+complete -F _jtl jtl
+"
+        )),
+        "zsh" => Ok(format!(
+            "#compdef jtl
+_jtl() {{
+    if (( CURRENT == 2 )); then
+        compadd {commands}
+    elif [[ $words[CURRENT-1] == --output ]]; then
+        compadd text json
+    elif [[ $words[CURRENT-1] == completions ]]; then
+        compadd bash zsh fish
+    elif [[ $PREFIX == -* ]]; then
+        compadd -- {options}
+    else
+        _files
+    fi
+}}
+compdef _jtl jtl
+"
+        )),
+        "fish" => {
+            let mut script = String::from("complete -c jtl -f\n");
+            for command in COMMANDS {
+                script.push_str(&format!(
+                    "complete -c jtl -n __fish_use_subcommand -a {}\n",
+                    command
+                ));
+            }
+            script.push_str("complete -c jtl -n 'not __fish_use_subcommand' -F\n");
+            script.push_str("complete -c jtl -l output -x -a 'text json'\n");
+            script.push_str("complete -c jtl -s o -r\n");
+            script.push_str("complete -c jtl -l set -x\n");
+            script.push_str("complete -c jtl -l os-env\n");
+            script.push_str("complete -c jtl -l regex\n");
+            Ok(script)
+        }
+        _ => Err(format!(
+            "unsupported shell `{}`, expected bash, zsh or fish",
+            shell
+        )),
+    }
+}
+
+/// Lists every declared or referenced variable with its resolved value, where
+/// the value came from (`file`, `override`, `os` or `unset`), and how many
+/// elements reference it.
+fn resolve_env(
+    text: &str,
+    overrides: &HashMap<String, String>,
+    os_env: bool,
+) -> Result<Vec<Value>, Box<dyn Error>> {
+    let declared = JtlDocument::parse(text)?.env;
+    let references = env_references(text)?;
+    let names: BTreeSet<&String> = declared
+        .keys()
+        .chain(references.keys())
+        .chain(overrides.keys())
+        .collect();
+
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            let (value, source) = if let Some(value) = overrides.get(name) {
+                (Some(value.clone()), "override")
+            } else if let Some(value) = declared.get(name) {
+                (Some(value.clone()), "file")
+            } else if let Some(value) = std::env::var(name).ok().filter(|_| os_env) {
+                (Some(value), "os")
+            } else {
+                (None, "unset")
+            };
+            json!({
+                "name": name,
+                "value": value,
+                "source": source,
+                "references": references.get(name).copied().unwrap_or(0),
+            })
+        })
+        .collect())
+}
+
+/// Options shared by the subcommands.
+#[derive(Debug, Default, PartialEq)]
+struct Args {
+    files: Vec<String>,
+    json: bool,
+    out: Option<String>,
+    overrides: HashMap<String, String>,
+    os_env: bool,
+    regex: bool,
+}
+
+impl Args {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--output" => match args.next().map(String::as_str) {
+                    Some("json") => parsed.json = true,
+                    Some("text") => parsed.json = false,
+                    _ => return Err("--output takes `text` or `json`".into()),
+                },
+                "-o" => parsed.out = Some(args.next().ok_or("-o takes a path")?.clone()),
+                "--set" => {
+                    let (name, value) = args
+                        .next()
+                        .and_then(|arg| arg.split_once('='))
+                        .ok_or("--set takes NAME=VALUE")?;
+                    parsed.overrides.insert(name.to_string(), value.to_string());
+                }
+                "--os-env" => parsed.os_env = true,
+                "--regex" => parsed.regex = true,
+                flag if flag.starts_with('-') && flag != "-" => {
+                    return Err(format!("unknown option `{}`", flag))
+                }
+                file => parsed.files.push(file.to_string()),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn files<const N: usize>(&self) -> Result<[&str; N], String> {
+        let files: Vec<&str> = self.files.iter().map(String::as_str).collect();
+        files
+            .try_into()
+            .map_err(|_| format!("expected {} files\n{}", N, USAGE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = Args::parse(&strings(&[
+            "a.jtl", "--output", "json", "-o", "out.jtl", "b.jtl",
+        ]))
+        .unwrap();
+        assert_eq!(args.files, ["a.jtl", "b.jtl"]);
+        assert!(args.json);
+        assert_eq!(args.out.as_deref(), Some("out.jtl"));
+        assert_eq!(args.files::<2>().unwrap(), ["a.jtl", "b.jtl"]);
+        assert!(args.files::<1>().is_err());
+
+        assert_eq!(
+            Args::parse(&strings(&["--bogus"])).unwrap_err(),
+            "unknown option `--bogus`"
+        );
+        assert!(run(&strings(&["frobnicate"])).is_err());
+    }
+
+    #[test]
+    fn test_resolve_env() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>host=localhost;\n>>>port=80;\n>>>BEGIN;\n\
+                    >n=\"1\">web>$env:host;\n>n=\"2\">home>$env:JTL_TEST_HOME;\n>>>END;";
+        let overrides = HashMap::from([("port".to_string(), "8080".to_string())]);
+        std::env::set_var("JTL_TEST_HOME", "/srv");
+
+        let entries = resolve_env(text, &overrides, false).unwrap();
+        assert_eq!(
+            entries,
+            [
+                json!({ "name": "JTL_TEST_HOME", "value": null, "source": "unset", "references": 1 }),
+                json!({ "name": "host", "value": "localhost", "source": "file", "references": 1 }),
+                json!({ "name": "port", "value": "8080", "source": "override", "references": 0 }),
+            ]
+        );
+        let entries = resolve_env(text, &overrides, true).unwrap();
+        assert_eq!(entries[0]["value"], "/srv");
+        assert_eq!(entries[0]["source"], "os");
+    }
+
+    #[test]
+    fn test_check_and_completions() {
+        let path = std::env::temp_dir().join(format!("jtl-check-{}.jtl", std::process::id()));
+        fs::write(
+            &path,
+            "DOCTYPE=JTL\n>>>ENV;\n>>>a=1;\n>>>a=2;\n>>>BEGIN;\n>n=\"1\">k>v;\n>>>END;\n",
+        )
+        .unwrap();
+        let file = path.to_str().unwrap();
+        assert_eq!(
+            check(file),
+            [
+                json!({ "file": file, "severity": "warning", "line": 4, "message": "env variable `a` redefined" })
+            ]
+        );
+        fs::write(&path, "no doctype\n").unwrap();
+        assert_eq!(
+            check(file)[0]["message"],
+            "invalid JTL document: missing DOCTYPE"
+        );
+        fs::remove_file(&path).unwrap();
+
+        for shell in ["bash", "zsh", "fish"] {
+            let script = completions(shell).unwrap();
+            assert!(
+                script.contains("merge") && script.contains("json"),
+                "{}",
+                shell
+            );
+        }
+        assert!(completions("tcsh").is_err());
+    }
+}
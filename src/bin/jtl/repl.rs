@@ -0,0 +1,182 @@
+//! `jtl repl`: an interactive shell over one document.
+
+use jtl_rs::{JtlDocument, JtlElement};
+use std::error::Error;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+const HELP: &str = "commands:
+  list                 list elements with their index and key
+  show <index>         print one element in full
+  get <key>            print the content of every element with this key
+  find <name>[=value]  list elements with a matching attribute, KEY or Content
+  env [name]           print the environment, or one variable
+  reload               re-read the file
+  quit                 leave the shell";
+
+/// The document being explored, reloaded whenever the file changes.
+struct Session {
+    path: PathBuf,
+    document: JtlDocument,
+    modified: Option<SystemTime>,
+}
+
+impl Session {
+    fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(Session {
+            path: path.to_path_buf(),
+            document: JtlDocument::load(path)?,
+            modified: modified(path),
+        })
+    }
+
+    /// Reloads the document if the file changed, returning whether it did.
+    fn refresh(&mut self) -> Result<bool, Box<dyn Error>> {
+        if modified(&self.path) == self.modified {
+            return Ok(false);
+        }
+        *self = Session::open(&self.path)?;
+        Ok(true)
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Reads commands from `input` until `quit` or end of input.
+pub(crate) fn run<R: BufRead, W: Write>(
+    path: &Path,
+    input: R,
+    mut out: W,
+) -> Result<(), Box<dyn Error>> {
+    let mut session = Session::open(path)?;
+    writeln!(
+        out,
+        "{}: {} elements, type `help` for commands",
+        path.display(),
+        session.document.elements.len()
+    )?;
+    write!(out, "jtl> ")?;
+    out.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        match session.refresh() {
+            Ok(true) => writeln!(out, "(reloaded {})", path.display())?,
+            Ok(false) => {}
+            Err(err) => writeln!(out, "error: reload failed: {}", err)?,
+        }
+        let (command, argument) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        let argument = argument.trim();
+        match command {
+            "" => {}
+            "quit" | "exit" => return Ok(()),
+            "help" => writeln!(out, "{}", HELP)?,
+            "list" => {
+                for (index, element) in session.document.elements.iter().enumerate() {
+                    writeln!(out, "{:>4}  {}", index, element.key)?;
+                }
+            }
+            "show" => match argument
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| session.document.elements.get(index))
+            {
+                Some(element) => show(&mut out, element)?,
+                None => writeln!(out, "error: no element at index `{}`", argument)?,
+            },
+            "get" => {
+                for element in session
+                    .document
+                    .elements
+                    .iter()
+                    .filter(|e| e.key == argument)
+                {
+                    writeln!(out, "{}", element.content)?;
+                }
+            }
+            "find" => {
+                let (name, value) = match argument.split_once('=') {
+                    Some((name, value)) => (name, Some(value)),
+                    None => (argument, None),
+                };
+                for (index, element) in session.document.elements.iter().enumerate() {
+                    let found =
+                        field(element, name).is_some_and(|found| value.is_none_or(|v| v == found));
+                    if found {
+                        writeln!(out, "{:>4}  {}", index, element.key)?;
+                    }
+                }
+            }
+            "env" if argument.is_empty() => {
+                let mut names: Vec<&String> = session.document.env.keys().collect();
+                names.sort();
+                for name in names {
+                    writeln!(out, "{}={}", name, session.document.env[name])?;
+                }
+            }
+            "env" => match session.document.env.get(argument) {
+                Some(value) => writeln!(out, "{}", value)?,
+                None => writeln!(out, "error: `{}` is not declared", argument)?,
+            },
+            "reload" => match Session::open(path) {
+                Ok(reloaded) => session = reloaded,
+                Err(err) => writeln!(out, "error: reload failed: {}", err)?,
+            },
+            _ => writeln!(out, "error: unknown command `{}`, type `help`", command)?,
+        }
+        write!(out, "jtl> ")?;
+        out.flush()?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+fn field<'a>(element: &'a JtlElement, name: &str) -> Option<&'a str> {
+    match name {
+        "KEY" => Some(&element.key),
+        "Content" => Some(&element.content),
+        _ => element.attr(name),
+    }
+}
+
+fn show<W: Write>(out: &mut W, element: &JtlElement) -> std::io::Result<()> {
+    writeln!(out, "KEY      {}", element.key)?;
+    for (name, value) in &element.attrs {
+        writeln!(out, "{:<8} {}", name, value)?;
+    }
+    writeln!(out, "Content  {}", element.content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repl_session() {
+        let path = std::env::temp_dir().join(format!("jtl-repl-{}.jtl", std::process::id()));
+        fs::write(
+            &path,
+            "DOCTYPE=JTL\n>>>ENV;\n>>>host=localhost;\n>>>BEGIN;\n\
+             >role=\"web\">web>$env:host;\n>role=\"db\">db>postgres;\n>>>END;\n",
+        )
+        .unwrap();
+        let input = "list\nfind role=db\nget web\nenv host\nshow 1\nshow 9\nbogus\nquit\nlist\n";
+        let mut out = Vec::new();
+        run(&path, input.as_bytes(), &mut out).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let expected = "jtl>    0  web\n   1  db\n\
+                        jtl>    1  db\n\
+                        jtl> localhost\n\
+                        jtl> localhost\n\
+                        jtl> KEY      db\nrole     db\nContent  postgres\n\
+                        jtl> error: no element at index `9`\n\
+                        jtl> error: unknown command `bogus`, type `help`\n\
+                        jtl> ";
+        assert!(out.ends_with(expected), "{}", out);
+    }
+}
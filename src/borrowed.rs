@@ -0,0 +1,159 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::encoding;
+use crate::error::JtlError;
+use crate::interp;
+use crate::options::ParseOptions;
+use crate::scan::{self, Decl, Defaults, Scanner};
+use crate::JtlElement;
+
+/// An element that borrows its text from the document it was parsed from,
+/// returned by [`parse_borrowed`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BorrowedElement<'a> {
+    /// The element's identifier.
+    pub key: &'a str,
+    /// Attributes in source order, including `>>>DEFAULTS`.
+    pub attrs: Vec<(Cow<'a, str>, Cow<'a, str>)>,
+    /// The element's content, after env substitution.
+    pub content: Cow<'a, str>,
+    /// The 1-based line the element was declared on.
+    pub line: usize,
+}
+
+impl BorrowedElement<'_> {
+    /// Returns the value of the attribute named `name`, if any. When the
+    /// name is repeated the last value wins, as with [`JtlElement::attr`].
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .rev()
+            .find(|(attr, _)| attr == name)
+            .map(|(_, value)| value.as_ref())
+    }
+
+    /// Copies the element into an owned [`JtlElement`].
+    pub fn into_owned(self) -> JtlElement {
+        let mut element = JtlElement::new(self.key, self.content);
+        element.attrs = self
+            .attrs
+            .into_iter()
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        element.line = Some(self.line);
+        element
+    }
+}
+
+/// Parses JTL text into elements that borrow from it instead of allocating
+/// a `String` per field.
+///
+/// Keys and attributes are always slices of `text`, and so is content, even
+/// when it is a `$env:NAME` reference, since the value is a slice of the
+/// `>>>ENV;` block. Only content using `${env:...}` interpolation is
+/// allocated. Annotations are not collected; otherwise the elements are the
+/// same as with [`parse_document`](crate::parse_document).
+///
+/// ```
+/// use std::borrow::Cow;
+///
+/// let text = "DOCTYPE=JTL\n>>>ENV;\n>>>host=a;\n>>>BEGIN;\n>n=\"1\">web>$env:host;\n>>>END;";
+/// let elements = jtl_rs::parse_borrowed(text)?;
+/// assert!(matches!(elements[0].content, Cow::Borrowed("a")));
+/// # Ok::<(), jtl_rs::JtlError>(())
+/// ```
+pub fn parse_borrowed(text: &str) -> Result<Vec<BorrowedElement<'_>>, JtlError> {
+    let text = text.strip_prefix(encoding::BOM).unwrap_or(text);
+    scan::check_doctype(text.lines().next()).map_err(|err| JtlError::new(err, None))?;
+
+    let options = ParseOptions::default();
+    let mut env: HashMap<&str, &str> = HashMap::new();
+    // Interpolation needs owned values; it is only built if some content uses it.
+    let mut owned_env: Option<HashMap<String, String>> = None;
+    let mut defaults: Defaults<&str> = Defaults::default();
+    let mut scanner = Scanner::default();
+    let mut elements = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let error = |message: String| JtlError::new(message, Some(number));
+        for decl in scanner.scan_line(line) {
+            match decl {
+                Decl::Env(name, value) => {
+                    env.insert(name, value);
+                    owned_env = None;
+                }
+                Decl::Defaults(attrs) => defaults
+                    .extend(attrs)
+                    .map_err(|err| error(format!("{} on line {}", err, number)))?,
+                Decl::Element(decl) => {
+                    let mut raw =
+                        crate::parse_raw_element(decl).map_err(|err| error(err.to_string()))?;
+                    defaults.apply_borrowed(&mut raw.attrs);
+                    let content =
+                        match crate::env_reference(raw.content).and_then(|name| env.get(name)) {
+                            Some(value) => Cow::Borrowed(*value),
+                            None if raw.content.contains("${env:") => {
+                                let owned = owned_env.get_or_insert_with(|| {
+                                    env.iter()
+                                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                                        .collect()
+                                });
+                                let content = interp::interpolate(raw.content, owned, &options)
+                                    .map_err(|err| {
+                                        error(format!("invalid element `{}`: {}", raw.key, err))
+                                    })?;
+                                Cow::Owned(content.into_owned())
+                            }
+                            None => Cow::Borrowed(raw.content),
+                        };
+                    elements.push(BorrowedElement {
+                        key: raw.key,
+                        attrs: raw
+                            .attrs
+                            .into_iter()
+                            .map(|(name, value)| (Cow::Borrowed(name), Cow::Borrowed(value)))
+                            .collect(),
+                        content,
+                        line: number,
+                    });
+                }
+            }
+        }
+    }
+    Ok(elements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_borrowed() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>host=a;\n>>>DEFAULTS role=\"web\";\n>>>BEGIN;\n\
+                    >n=\"1\">plain>x; >n=\"2\">env>$env:host;\n\
+                    >n=\"3\">url>http://${env:host|upper}/;\n>>>END;";
+        let elements = parse_borrowed(text).unwrap();
+        assert!(matches!(elements[0].content, Cow::Borrowed("x")));
+        assert!(matches!(elements[1].content, Cow::Borrowed("a")));
+        assert!(matches!(elements[2].content, Cow::Owned(_)));
+        assert!(elements[0]
+            .attrs
+            .iter()
+            .all(|(name, value)| matches!((name, value), (Cow::Borrowed(_), Cow::Borrowed(_)))));
+        assert_eq!(elements[1].attr("role"), Some("web"));
+        assert_eq!(elements[2].line, 7);
+
+        let owned: Vec<JtlElement> = elements
+            .into_iter()
+            .map(BorrowedElement::into_owned)
+            .collect();
+        assert_eq!(owned, crate::parse_document(text).unwrap().elements);
+
+        let repeated = parse_borrowed("DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\" n=\"2\">k>v;\n>>>END;");
+        assert_eq!(repeated.unwrap()[0].attr("n"), Some("2"));
+
+        let err = parse_borrowed("DOCTYPE=JTL\n>>>BEGIN;\n>bad;\n").unwrap_err();
+        assert_eq!(err.line(), Some(3));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{JtlDocument, JtlElement};
+
+/// Builds a [`JtlDocument`] in code.
+///
+/// ```
+/// use jtl_rs::DocumentBuilder;
+///
+/// let document = DocumentBuilder::new()
+///     .env("foo", "bar")
+///     .element("id", &[("key", "value")], "$env:foo")
+///     .build();
+/// let text = document.to_jtl()?;
+/// let parsed = jtl_rs::JtlDocument::parse(&text).unwrap();
+/// assert_eq!(parsed.elements[0].content, "bar");
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// Nothing is checked until the document is written, which fails on values
+/// the parser could not read back.
+#[derive(Debug, Clone, Default)]
+pub struct DocumentBuilder {
+    document: JtlDocument,
+}
+
+impl DocumentBuilder {
+    /// Creates a builder for an empty document.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares an env variable, replacing any earlier value.
+    pub fn env(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.document.env.insert(name.into(), value.into());
+        self
+    }
+
+    /// Appends an element with the given attributes, in order.
+    pub fn element(
+        mut self,
+        key: impl Into<String>,
+        attrs: &[(&str, &str)],
+        content: impl Into<String>,
+    ) -> Self {
+        let mut element = JtlElement::new(key, content);
+        element.attrs = attrs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        self.document.elements.push(element);
+        self
+    }
+
+    /// Appends an element built separately.
+    pub fn push(mut self, element: JtlElement) -> Self {
+        self.document.elements.push(element);
+        self
+    }
+
+    /// Returns the document.
+    pub fn build(self) -> JtlDocument {
+        self.document
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let document = DocumentBuilder::new()
+            .env("host", "localhost")
+            .element("web", &[("port", "80"), ("tls", "no")], "$env:host")
+            .push(JtlElement::new("db", "postgres").with_attr("port", "5432"))
+            .build();
+        assert_eq!(document.env["host"], "localhost");
+        assert_eq!(document.elements[0].attr("tls"), Some("no"));
+
+        let parsed = JtlDocument::parse(&document.to_jtl().unwrap()).unwrap();
+        assert_eq!(parsed.elements[0].content, "localhost");
+        assert_eq!(parsed.elements[1], document.elements[1]);
+
+        let unwritable = DocumentBuilder::new().element("empty", &[], "x").build();
+        assert!(unwritable.to_jtl().is_err());
+    }
+}
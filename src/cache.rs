@@ -0,0 +1,187 @@
+use crate::document::JtlDocument;
+use crate::element::JtlElement;
+use std::error::Error;
+
+const MAGIC: &[u8; 4] = b"JTLC";
+const VERSION: u8 = 2;
+
+impl JtlDocument {
+    /// Encodes the document in the crate's compact binary cache format.
+    ///
+    /// The bytes start with a magic number and a format version, so caches
+    /// written by a different version of the crate are rejected by
+    /// [`from_cache_bytes`](JtlDocument::from_cache_bytes) instead of being
+    /// misread. Env variables are stored sorted by name, so equal documents
+    /// encode to equal bytes.
+    pub fn to_cache_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+
+        let mut env: Vec<(&String, &String)> = self.env.iter().collect();
+        env.sort();
+        write_len(&mut out, env.len());
+        for (name, value) in env {
+            write_str(&mut out, name);
+            write_str(&mut out, value);
+        }
+
+        write_len(&mut out, self.elements.len());
+        for element in &self.elements {
+            write_str(&mut out, &element.key);
+            write_str(&mut out, &element.content);
+            write_len(&mut out, element.attrs.len());
+            for (name, value) in &element.attrs {
+                write_str(&mut out, name);
+                write_str(&mut out, value);
+            }
+            write_len(&mut out, element.annotations.len());
+            for (name, value) in &element.annotations {
+                write_str(&mut out, name);
+                write_str(&mut out, value);
+            }
+        }
+        out
+    }
+
+    /// Decodes bytes produced by [`to_cache_bytes`](JtlDocument::to_cache_bytes).
+    pub fn from_cache_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err("invalid JTL cache: bad magic number".into());
+        }
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(format!("invalid JTL cache: unsupported version {}", version).into());
+        }
+
+        let mut document = JtlDocument::new();
+        for _ in 0..reader.len()? {
+            let name = reader.string()?;
+            let value = reader.string()?;
+            document.env.insert(name, value);
+        }
+        let count = reader.len()?;
+        for _ in 0..count {
+            let key = reader.string()?;
+            let content = reader.string()?;
+            let mut element = JtlElement::new(key, content);
+            for _ in 0..reader.len()? {
+                let name = reader.string()?;
+                let value = reader.string()?;
+                element.attrs.push((name, value));
+            }
+            for _ in 0..reader.len()? {
+                let name = reader.string()?;
+                let value = reader.string()?;
+                element.annotations.push((name, value));
+            }
+            document.elements.push(element);
+        }
+
+        if reader.pos != bytes.len() {
+            return Err("invalid JTL cache: trailing bytes".into());
+        }
+        Ok(document)
+    }
+}
+
+fn write_len(out: &mut Vec<u8>, mut len: usize) {
+    // Unsigned LEB128.
+    loop {
+        let byte = (len & 0x7f) as u8;
+        len >>= 7;
+        if len == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        let end = self
+            .pos
+            .checked_add(n)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or("invalid JTL cache: unexpected end of data")?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn len(&mut self) -> Result<usize, Box<dyn Error>> {
+        let mut len: usize = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.take(1)?[0];
+            if shift >= usize::BITS {
+                return Err("invalid JTL cache: length overflow".into());
+            }
+            len |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(len);
+            }
+            shift += 7;
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Box<dyn Error>> {
+        let len = self.len()?;
+        let bytes = self.take(len)?;
+        Ok(std::str::from_utf8(bytes)?.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_round_trip() {
+        let mut doc = JtlDocument::new();
+        doc.env.insert("foo".to_string(), "bar".to_string());
+        doc.env.insert("long".to_string(), "x".repeat(300));
+        doc.elements.push(
+            JtlElement::new("id", "héllo")
+                .with_attr("b", "2")
+                .with_attr("a", "1")
+                .with_annotation("owner", "ops")
+                .with_annotation("primary", ""),
+        );
+        doc.elements.push(JtlElement::new("empty", ""));
+
+        let bytes = doc.to_cache_bytes();
+        assert_eq!(&bytes[..5], b"JTLC\x02");
+        let decoded = JtlDocument::from_cache_bytes(&bytes).unwrap();
+        assert_eq!(decoded, doc);
+        assert_eq!(decoded.elements[0].annotations, doc.elements[0].annotations);
+        assert_eq!(doc.clone().to_cache_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_cache_rejects_bad_input() {
+        let bytes = JtlDocument::new().to_cache_bytes();
+
+        let mut newer = bytes.clone();
+        newer[4] = 3;
+        let err = JtlDocument::from_cache_bytes(&newer).unwrap_err();
+        assert_eq!(err.to_string(), "invalid JTL cache: unsupported version 3");
+
+        let err = JtlDocument::from_cache_bytes(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(err.to_string(), "invalid JTL cache: unexpected end of data");
+
+        assert!(JtlDocument::from_cache_bytes(b"nope").is_err());
+    }
+}
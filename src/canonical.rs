@@ -0,0 +1,100 @@
+use std::error::Error;
+
+use crate::scan::{Decl, Defaults, Scanner};
+use crate::{AttrOrder, JtlDocument, JtlElement, JtlWriter};
+
+/// Rewrites `text` in a normal form, so that documents which parse the same
+/// way come out byte-equal.
+///
+/// Env variables are sorted by name, attributes are sorted by name, lines end
+/// in `\n` and comments, annotations and layout are dropped. `>>>DEFAULTS`
+/// attributes are written out on every element they apply to. With
+/// `resolve_env`, env references in contents are replaced by their values;
+/// otherwise they are kept as written, so documents that differ only in how a
+/// value is spelled stay distinct.
+pub fn canonicalize(text: &str, resolve_env: bool) -> Result<String, Box<dyn Error>> {
+    let document = JtlDocument::parse(text)?;
+    let mut elements = if resolve_env {
+        document.elements
+    } else {
+        let mut elements = Vec::new();
+        let mut scanner = Scanner::default();
+        let mut defaults: Defaults<&str> = Defaults::default();
+        for line in text.lines().skip(1) {
+            for decl in scanner.scan_line(line) {
+                match decl {
+                    Decl::Defaults(attrs) => defaults.extend(attrs)?,
+                    Decl::Element(decl) => {
+                        let mut raw = crate::parse_raw_element(decl)?;
+                        defaults.apply_borrowed(&mut raw.attrs);
+                        let mut element = JtlElement::new(raw.key, raw.content);
+                        for (name, value) in raw.attrs {
+                            element = element.with_attr(name, value);
+                        }
+                        elements.push(element);
+                    }
+                    Decl::Env(..) => {}
+                }
+            }
+        }
+        elements
+    };
+
+    let mut names: Vec<&String> = document.env.keys().collect();
+    names.sort();
+    let mut writer = JtlWriter::new(Vec::new()).attr_order(AttrOrder::Alphabetical);
+    for name in names {
+        writer.write_env(name, &document.env[name])?;
+    }
+    writer.write_begin()?;
+    for element in &mut elements {
+        element.annotations.clear();
+        writer.write_element(element)?;
+    }
+    writer.write_end()?;
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize() {
+        let a = "DOCTYPE=JTL\r\n>>>ENV;\r\n>>>port=80;>>>host=localhost;\r\n>>>BEGIN;\r\n\
+                 >//> @primary\r\n>b=\"2\" a=\"1\">web>$env:host;\r\n>>>END;\r\n";
+        let b = "DOCTYPE=JTL\n>>>ENV;\n>>>host=localhost;\n>>>port=80;\n>>>BEGIN;\n\
+                 /* the web server */\n>a=\"1\"  b=\"2\">web>$env:host;\n>>>END;\n";
+        let canonical = canonicalize(a, false).unwrap();
+        assert_eq!(canonical, canonicalize(b, false).unwrap());
+        assert_eq!(
+            canonical,
+            "DOCTYPE=JTL\n>>>ENV;\n>>>host=localhost;\n>>>port=80;\n>>>BEGIN;\n\
+             >a=\"1\" b=\"2\">web>$env:host;\n>>>END;\n"
+        );
+
+        let literal = b.replace("$env:host", "localhost");
+        assert_ne!(canonical, canonicalize(&literal, false).unwrap());
+        assert_eq!(
+            canonicalize(b, true).unwrap(),
+            canonicalize(&literal, true).unwrap()
+        );
+        assert_eq!(canonicalize(&canonical, false).unwrap(), canonical);
+    }
+
+    #[test]
+    fn test_canonicalize_defaults() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>host=db;\n>>>DEFAULTS env=\"prod\";\n>>>BEGIN;\n\
+                    >a=\"1\">web>$env:host;\n>a=\"2\" env=\"dev\">cache>redis;\n>>>END;\n";
+        let canonical = canonicalize(text, false).unwrap();
+        assert_eq!(
+            canonical,
+            "DOCTYPE=JTL\n>>>ENV;\n>>>host=db;\n>>>BEGIN;\n>a=\"1\" env=\"prod\">web>$env:host;\n\
+             >a=\"2\" env=\"dev\">cache>redis;\n>>>END;\n"
+        );
+        assert_eq!(
+            JtlDocument::parse(&canonical).unwrap(),
+            JtlDocument::parse(text).unwrap()
+        );
+    }
+}
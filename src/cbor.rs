@@ -0,0 +1,109 @@
+use serde_json::Value;
+
+/// Encodes parsed elements as CBOR (RFC 8949).
+///
+/// The output is an array of maps with exactly the shape [`stringify`](crate::stringify)
+/// produces as JSON.
+pub fn to_cbor(parsed: &[Value]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_head(&mut out, 4, parsed.len() as u64);
+    for value in parsed {
+        write_value(&mut out, value);
+    }
+    out
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0xf6),
+        Value::Bool(false) => out.push(0xf4),
+        Value::Bool(true) => out.push(0xf5),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                write_head(out, 0, u);
+            } else if let Some(i) = n.as_i64() {
+                // Negative integers encode -1 - n.
+                write_head(out, 1, !(i as u64));
+            } else {
+                out.push(0xfb);
+                out.extend_from_slice(&n.as_f64().unwrap_or(f64::NAN).to_be_bytes());
+            }
+        }
+        Value::String(s) => {
+            write_head(out, 3, s.len() as u64);
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(items) => {
+            write_head(out, 4, items.len() as u64);
+            for item in items {
+                write_value(out, item);
+            }
+        }
+        Value::Object(map) => {
+            write_head(out, 5, map.len() as u64);
+            for (key, item) in map {
+                write_head(out, 3, key.len() as u64);
+                out.extend_from_slice(key.as_bytes());
+                write_value(out, item);
+            }
+        }
+    }
+}
+
+fn write_head(out: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    if n < 24 {
+        out.push(major | n as u8);
+    } else if n <= u8::MAX as u64 {
+        out.extend_from_slice(&[major | 24, n as u8]);
+    } else if n <= u16::MAX as u64 {
+        out.push(major | 25);
+        out.extend_from_slice(&(n as u16).to_be_bytes());
+    } else if n <= u32::MAX as u64 {
+        out.push(major | 26);
+        out.extend_from_slice(&(n as u32).to_be_bytes());
+    } else {
+        out.push(major | 27);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use serde_json::json;
+
+    #[test]
+    fn test_to_cbor() {
+        let parsed = parse("DOCTYPE=JTL\n>>>BEGIN;\n>a=\"1\">id>hi;\n>>>END;").unwrap();
+        let bytes = to_cbor(&parsed);
+
+        let mut expected = vec![0x81, 0xa4];
+        for (key, value) in [
+            ("Content", "hi"),
+            ("Contents", "hi"),
+            ("KEY", "id"),
+            ("a", "1"),
+        ] {
+            expected.push(0x60 | key.len() as u8);
+            expected.extend_from_slice(key.as_bytes());
+            expected.push(0x60 | value.len() as u8);
+            expected.extend_from_slice(value.as_bytes());
+        }
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn test_cbor_scalars() {
+        let mut out = Vec::new();
+        write_value(&mut out, &json!([null, false, 500, -1, -500, 1.5]));
+        assert_eq!(
+            out,
+            [
+                0x86, 0xf6, 0xf4, 0x19, 0x01, 0xf4, 0x20, 0x39, 0x01, 0xf3, 0xfb, 0x3f, 0xf8, 0, 0,
+                0, 0, 0, 0
+            ]
+        );
+    }
+}
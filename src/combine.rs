@@ -0,0 +1,222 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::annotation::annotation_line;
+use crate::write::element_line;
+use crate::{JtlDocument, JtlElement};
+
+/// How [`concat`] resolves a variable that documents define differently.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EnvConflict {
+    /// Fail, naming the variable.
+    #[default]
+    Error,
+    /// Keep the value from the earliest document.
+    FirstWins,
+    /// Keep the value from the latest document.
+    LastWins,
+}
+
+/// Joins documents into one, keeping every element in order.
+///
+/// The environments are merged; a variable defined with the same value in
+/// several documents is not a conflict.
+pub fn concat(documents: &[JtlDocument], policy: EnvConflict) -> Result<JtlDocument, String> {
+    let mut joined = JtlDocument::new();
+    for document in documents {
+        for (name, value) in &document.env {
+            match joined.env.entry(name.clone()) {
+                Entry::Vacant(entry) => {
+                    entry.insert(value.clone());
+                    copy_origin(&mut joined, document, name);
+                }
+                Entry::Occupied(entry) if entry.get() == value => {}
+                Entry::Occupied(mut entry) => match policy {
+                    EnvConflict::Error => {
+                        return Err(format!(
+                            "env variable `{}` conflicts: `{}` and `{}`",
+                            name,
+                            entry.get(),
+                            value
+                        ))
+                    }
+                    EnvConflict::FirstWins => {}
+                    EnvConflict::LastWins => {
+                        entry.insert(value.clone());
+                        copy_origin(&mut joined, document, name);
+                    }
+                },
+            }
+        }
+        joined.elements.extend(document.elements.iter().cloned());
+    }
+    Ok(joined)
+}
+
+/// Copies where `from` declared variable `name` over to `to`.
+pub(crate) fn copy_origin(to: &mut JtlDocument, from: &JtlDocument, name: &str) {
+    match from.env_origins.get(name) {
+        Some(origin) => to.env_origins.insert(name.to_string(), origin.clone()),
+        None => to.env_origins.remove(name),
+    };
+}
+
+/// Splits a document into one document per bucket, as chosen by `bucket`.
+///
+/// Every part gets a copy of the full environment. Parts are returned in the
+/// order their buckets first appear, and keep the elements' relative order.
+pub fn split_by<K: Hash + Eq + Clone>(
+    document: &JtlDocument,
+    mut bucket: impl FnMut(&JtlElement) -> K,
+) -> Vec<(K, JtlDocument)> {
+    let mut parts: Vec<(K, JtlDocument)> = Vec::new();
+    let mut positions: HashMap<K, usize> = HashMap::new();
+    for element in &document.elements {
+        let key = bucket(element);
+        let position = *positions.entry(key.clone()).or_insert_with(|| {
+            let mut part = JtlDocument::new();
+            part.env = document.env.clone();
+            part.env_origins = document.env_origins.clone();
+            parts.push((key, part));
+            parts.len() - 1
+        });
+        parts[position].1.elements.push(element.clone());
+    }
+    parts
+}
+
+/// Splits a document into parts that each take at most `max_bytes` when
+/// written with [`write_preserving`](JtlDocument::write_preserving), or with
+/// [`write_to`](JtlDocument::write_to) for documents using `\n` line endings.
+///
+/// Every part repeats the full environment and is a valid document on its
+/// own, and elements keep their order. Parts are filled greedily, so all but
+/// the last are close to the cap. Fails if the environment plus a single
+/// element is already over the cap, or an element cannot be written.
+pub fn shard(document: &JtlDocument, max_bytes: usize) -> Result<Vec<JtlDocument>, String> {
+    let mut empty = JtlDocument::new();
+    empty.env = document.env.clone();
+    empty.env_origins = document.env_origins.clone();
+    empty.format = document.format;
+    let header = empty
+        .write_preserving(Vec::new())
+        .map_err(|err| err.to_string())?
+        .len();
+
+    let newline = document.format.newline.as_str().len();
+    let mut parts = vec![empty.clone()];
+    let mut size = header;
+    for element in &document.elements {
+        let mut bytes = element_line(element)?.len() + newline;
+        if !element.annotations.is_empty() {
+            bytes += annotation_line(&element.annotations).len() + newline;
+        }
+        if header + bytes > max_bytes {
+            return Err(format!(
+                "element `{}` does not fit in {} bytes",
+                element.key, max_bytes
+            ));
+        }
+        if size + bytes > max_bytes && size > header {
+            parts.push(empty.clone());
+            size = header;
+        }
+        parts
+            .last_mut()
+            .expect("at least one part")
+            .elements
+            .push(element.clone());
+        size += bytes;
+    }
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(env: &[(&str, &str)], elements: &[(&str, &str)]) -> JtlDocument {
+        let mut document = JtlDocument::new();
+        for (name, value) in env {
+            document.env.insert(name.to_string(), value.to_string());
+        }
+        for (key, region) in elements {
+            document
+                .elements
+                .push(JtlElement::new(*key, "x").with_attr("region", *region));
+        }
+        document
+    }
+
+    #[test]
+    fn test_concat() {
+        let a = document(&[("host", "a"), ("port", "80")], &[("web", "eu")]);
+        let b = document(&[("host", "b"), ("port", "80")], &[("db", "us")]);
+        let docs = [a, b];
+        assert_eq!(
+            concat(&docs, EnvConflict::Error).unwrap_err(),
+            "env variable `host` conflicts: `a` and `b`"
+        );
+        assert_eq!(
+            concat(&docs, EnvConflict::FirstWins).unwrap().env["host"],
+            "a"
+        );
+        let joined = concat(&docs, EnvConflict::LastWins).unwrap();
+        assert_eq!(joined.env["host"], "b");
+        let keys: Vec<&str> = joined.elements.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["web", "db"]);
+    }
+
+    #[test]
+    fn test_split_by() {
+        let doc = document(
+            &[("host", "a")],
+            &[("web", "eu"), ("db", "us"), ("cache", "eu")],
+        );
+        let parts = split_by(&doc, |element| element.attrs[0].1.clone());
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].0, "eu");
+        let keys: Vec<&str> = parts[0].1.elements.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["web", "cache"]);
+        assert_eq!(parts[1].1.env["host"], "a");
+        assert_eq!(
+            concat(
+                &[parts[0].1.clone(), parts[1].1.clone()],
+                EnvConflict::Error
+            )
+            .unwrap()
+            .elements
+            .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_shard() {
+        let mut doc = document(&[("host", "localhost")], &[]);
+        for n in 0..10 {
+            doc.elements
+                .push(JtlElement::new(format!("e{}", n), "content").with_attr("n", n.to_string()));
+        }
+        let parts = shard(&doc, 120).unwrap();
+        assert!(parts.len() > 1);
+        let mut keys = Vec::new();
+        for part in &parts {
+            let text = part.to_jtl().unwrap();
+            assert!(text.len() <= 120, "{}", text.len());
+            let parsed = JtlDocument::parse(&text).unwrap();
+            assert_eq!(parsed.env["host"], "localhost");
+            keys.extend(parsed.elements.into_iter().map(|e| e.key));
+        }
+        assert_eq!(keys.len(), 10);
+        assert_eq!(keys[9], "e9");
+
+        assert_eq!(
+            shard(&doc, 50).unwrap_err(),
+            "element `e0` does not fit in 50 bytes"
+        );
+        assert_eq!(shard(&JtlDocument::new(), 100).unwrap().len(), 1);
+    }
+}
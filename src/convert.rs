@@ -0,0 +1,20 @@
+#[cfg(feature = "markdown")]
+pub use crate::markdown_to_html;
+#[cfg(feature = "cbor")]
+pub use crate::to_cbor;
+#[cfg(feature = "msgpack")]
+pub use crate::to_msgpack;
+#[cfg(feature = "avro")]
+pub use crate::AvroSchema;
+pub use crate::{
+    canonicalize, concat, extract, from_document, from_element, from_elements, from_str, inject,
+    merge, shard, split_by, to_element, to_elements, to_string, DeError, ElementMapping,
+    ElementsDeserializer, EnvConflict, JtlDeserializer, JtlSerializer, JtlValue, Position,
+    SerError,
+};
+#[cfg(feature = "json")]
+pub use crate::{convert_stream, from_json, Format};
+#[cfg(feature = "archive")]
+pub use crate::{to_archive, ArchivedDocument, ArchivedElement};
+#[cfg(feature = "protobuf")]
+pub use crate::{FieldType, MessageDescriptor};
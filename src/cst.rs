@@ -0,0 +1,485 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use crate::annotation::{annotation_line, parse_annotations};
+use crate::scan::is_skipped;
+use crate::write::{check_env, element_line};
+use crate::{JtlDocument, JtlElement};
+
+/// What a [`CstNode`] holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NodeKind {
+    /// The `DOCTYPE` line.
+    Doctype,
+    /// `>>>ENV;`, `>>>BEGIN;` or `>>>END;`.
+    Directive,
+    /// An empty or all-whitespace line.
+    Blank,
+    /// A `/*`, `*/` or `>//>` comment line, or an inline `>//>` segment.
+    Comment,
+    /// An env declaration, without its `;`.
+    Env,
+    /// An element declaration, without its `;`.
+    Element,
+    /// Text the parser ignores, such as a stray fragment after a `;`.
+    Other,
+}
+
+/// One piece of a [`CstDocument`], borrowed from its text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CstNode<'a> {
+    /// What the node is.
+    pub kind: NodeKind,
+    /// The 1-based line it is on.
+    pub line: usize,
+    /// Its text, without surrounding whitespace.
+    pub text: &'a str,
+    span: Range<usize>,
+}
+
+impl<'a> CstNode<'a> {
+    fn new(kind: NodeKind, line: usize, text: &'a str, start: usize) -> Self {
+        CstNode {
+            kind,
+            line,
+            text,
+            span: start..start + text.len(),
+        }
+    }
+}
+
+/// A lossless syntax tree of a JTL document, for editing it in place.
+///
+/// The document is kept as its original text, split into nodes on demand.
+/// Everything that is not edited, including comments, blank lines,
+/// indentation and line endings, is written back byte for byte. Edits touch
+/// only the declarations they name, and new lines copy the indentation and
+/// line ending around them. Elements are read and written as they appear in
+/// the source, with env references unresolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CstDocument {
+    /// Lines, each with its line ending.
+    lines: Vec<String>,
+}
+
+impl CstDocument {
+    /// Parses `text`, failing if it is not a valid document.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        JtlDocument::parse(text)?;
+        Ok(CstDocument {
+            lines: text.split_inclusive('\n').map(str::to_string).collect(),
+        })
+    }
+
+    /// Returns every node, in source order.
+    pub fn nodes(&self) -> Vec<CstNode<'_>> {
+        let mut nodes = Vec::new();
+        let (mut in_env, mut in_body) = (false, false);
+        for (index, raw) in self.lines.iter().enumerate() {
+            let trimmed = raw.trim();
+            let start = offset(raw, trimmed);
+            let node = |kind, text, start| CstNode::new(kind, index + 1, text, start);
+            let kind = match trimmed {
+                _ if index == 0 => NodeKind::Doctype,
+                "" => NodeKind::Blank,
+                ">>>ENV;" => {
+                    in_env = true;
+                    NodeKind::Directive
+                }
+                ">>>BEGIN;" => {
+                    (in_env, in_body) = (false, true);
+                    NodeKind::Directive
+                }
+                ">>>END;" => {
+                    in_body = false;
+                    NodeKind::Directive
+                }
+                _ if is_skipped(trimmed) => NodeKind::Comment,
+                _ => {
+                    for segment in trimmed.split(';') {
+                        let decl = segment.trim();
+                        if decl.is_empty() {
+                            continue;
+                        }
+                        let kind = if decl.starts_with(">//>") {
+                            NodeKind::Comment
+                        } else if in_env && decl.starts_with(">>>") && decl.contains('=') {
+                            NodeKind::Env
+                        } else if in_body && decl.starts_with('>') {
+                            NodeKind::Element
+                        } else {
+                            NodeKind::Other
+                        };
+                        nodes.push(node(kind, decl, offset(raw, decl)));
+                    }
+                    continue;
+                }
+            };
+            nodes.push(node(kind, trimmed, start));
+        }
+        nodes
+    }
+
+    /// Returns the value of env variable `name`, as the parser reads it.
+    pub fn env(&self, name: &str) -> Option<&str> {
+        self.env_nodes(name)
+            .pop()
+            .map(|node| env_parts(node.text).1)
+    }
+
+    /// Sets env variable `name`, rewriting only the value of every existing
+    /// declaration, or adding a declaration at the end of the `>>>ENV;` block.
+    pub fn set_env(&mut self, name: &str, value: &str) -> Result<(), String> {
+        check_env(name, value)?;
+        let existing: Vec<(usize, Range<usize>)> = self
+            .env_nodes(name)
+            .into_iter()
+            .map(|node| {
+                let old = env_parts(node.text).1;
+                let start = node.span.start + offset(node.text, old);
+                (node.line - 1, start..start + old.len())
+            })
+            .collect();
+        if !existing.is_empty() {
+            for (line, span) in existing.into_iter().rev() {
+                self.lines[line].replace_range(span, value);
+            }
+            return Ok(());
+        }
+
+        let declaration = format!(">>>{}={};", name, value);
+        let nodes = self.nodes();
+        let after_env = nodes
+            .iter()
+            .rev()
+            .find(|node| node.kind == NodeKind::Env)
+            .or_else(|| nodes.iter().find(|node| node.text == ">>>ENV;"));
+        if let Some(node) = after_env {
+            let line = node.line - 1;
+            let indent = indentation(&self.lines[line]).to_string();
+            self.insert_line(line + 1, format!("{}{}", indent, declaration));
+            return Ok(());
+        }
+        let begin = nodes
+            .iter()
+            .find(|node| node.text == ">>>BEGIN;")
+            .map_or(self.lines.len(), |node| node.line - 1);
+        self.insert_line(begin, ">>>ENV;".to_string());
+        self.insert_line(begin + 1, declaration);
+        Ok(())
+    }
+
+    /// Removes every declaration of env variable `name`, returning whether
+    /// there was one.
+    pub fn remove_env(&mut self, name: &str) -> bool {
+        let spans: Vec<(usize, Range<usize>)> = self
+            .env_nodes(name)
+            .into_iter()
+            .map(|node| (node.line - 1, node.span.clone()))
+            .collect();
+        let found = !spans.is_empty();
+        for (line, span) in spans.into_iter().rev() {
+            self.remove_span(line, span);
+        }
+        found
+    }
+
+    /// Returns the number of elements.
+    pub fn element_count(&self) -> usize {
+        self.element_nodes().len()
+    }
+
+    /// Returns the element at `index` as written, with env references
+    /// unresolved.
+    pub fn element(&self, index: usize) -> Option<JtlElement> {
+        let node = self.element_nodes().into_iter().nth(index)?;
+        let raw = crate::parse_raw_element(node.text).ok()?;
+        let mut element = JtlElement::new(raw.key, raw.content);
+        for (name, value) in raw.attrs {
+            element = element.with_attr(name, value);
+        }
+        Some(element)
+    }
+
+    /// Replaces the element at `index`, leaving the rest of its line alone.
+    /// Annotations on `element` are not written.
+    pub fn replace_element(&mut self, index: usize, element: &JtlElement) -> Result<(), String> {
+        let text = element_line(element)?;
+        let (line, span) = self.element_span(index)?;
+        self.lines[line].replace_range(span, text.trim_end_matches(';'));
+        Ok(())
+    }
+
+    /// Inserts an element before the one at `index`, above that element's
+    /// annotation lines, or at the end of the body if `index` is the element
+    /// count. Annotations on `element` are written on a line of their own,
+    /// so an annotated element can't be inserted in the middle of a line.
+    pub fn insert_element(&mut self, index: usize, element: &JtlElement) -> Result<(), String> {
+        let text = element_line(element)?;
+        let elements = self.element_nodes();
+        if let Some(node) = elements.get(index) {
+            let line = node.line - 1;
+            let first_on_line = index == 0 || elements[index - 1].line != node.line;
+            if first_on_line {
+                let indent = indentation(&self.lines[line]).to_string();
+                let at = self
+                    .annotation_lines(index)
+                    .first()
+                    .copied()
+                    .unwrap_or(line);
+                self.insert_line(at, format!("{}{}", indent, text));
+                if !element.annotations.is_empty() {
+                    let annotations = annotation_line(&element.annotations);
+                    self.insert_line(at, format!("{}{}", indent, annotations));
+                }
+            } else if !element.annotations.is_empty() {
+                return Err(format!(
+                    "cannot insert annotated element `{}` in the middle of line {}",
+                    element.key, node.line
+                ));
+            } else {
+                let at = node.span.start;
+                self.lines[line].insert_str(at, &format!("{} ", text));
+            }
+            return Ok(());
+        }
+        if index > elements.len() {
+            return Err(format!("element index {} out of range", index));
+        }
+
+        let nodes = self.nodes();
+        let begin = nodes
+            .iter()
+            .position(|node| node.text == ">>>BEGIN;")
+            .ok_or("document has no >>>BEGIN; block")?;
+        let end = nodes[begin..]
+            .iter()
+            .find(|node| node.text == ">>>END;")
+            .map_or(self.lines.len(), |node| node.line - 1);
+        let indent = elements
+            .last()
+            .map(|node| indentation(&self.lines[node.line - 1]).to_string())
+            .unwrap_or_default();
+        self.insert_line(end, format!("{}{}", indent, text));
+        if !element.annotations.is_empty() {
+            let annotations = annotation_line(&element.annotations);
+            self.insert_line(end, format!("{}{}", indent, annotations));
+        }
+        Ok(())
+    }
+
+    /// Appends an element at the end of the body.
+    pub fn push_element(&mut self, element: &JtlElement) -> Result<(), String> {
+        self.insert_element(self.element_count(), element)
+    }
+
+    /// Removes the element at `index` and its annotation lines, returning
+    /// whether there was one. A line left with nothing on it is removed too.
+    pub fn remove_element(&mut self, index: usize) -> bool {
+        let annotations = self.annotation_lines(index);
+        match self.element_span(index) {
+            Ok((line, span)) => {
+                self.remove_span(line, span);
+                for line in annotations.into_iter().rev() {
+                    self.lines.remove(line);
+                }
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Parses the current text into a document.
+    pub fn to_document(&self) -> Result<JtlDocument, Box<dyn Error>> {
+        JtlDocument::parse(&self.to_string())
+    }
+
+    fn env_nodes(&self, name: &str) -> Vec<CstNode<'_>> {
+        self.nodes()
+            .into_iter()
+            .filter(|node| node.kind == NodeKind::Env && env_parts(node.text).0 == name)
+            .collect()
+    }
+
+    fn element_nodes(&self) -> Vec<CstNode<'_>> {
+        self.nodes()
+            .into_iter()
+            .filter(|node| node.kind == NodeKind::Element)
+            .collect()
+    }
+
+    /// Returns the 0-based `>//>` lines whose annotations the parser gives to
+    /// element `index`: those since the previous element, if the element
+    /// starts its line.
+    fn annotation_lines(&self, index: usize) -> Vec<usize> {
+        let elements = self.element_nodes();
+        let Some(node) = elements.get(index) else {
+            return Vec::new();
+        };
+        let start = match index.checked_sub(1).map(|previous| &elements[previous]) {
+            Some(previous) if previous.line == node.line => return Vec::new(),
+            Some(previous) => previous.line,
+            None => self
+                .lines
+                .iter()
+                .position(|line| line.trim() == ">>>BEGIN;")
+                .unwrap_or(0),
+        };
+        (start..node.line - 1)
+            .filter(|&line| {
+                self.lines[line]
+                    .trim()
+                    .strip_prefix(">//>")
+                    .is_some_and(|comment| parse_annotations(comment).next().is_some())
+            })
+            .collect()
+    }
+
+    /// Returns the 0-based line and the span of element `index`.
+    fn element_span(&self, index: usize) -> Result<(usize, Range<usize>), String> {
+        self.element_nodes()
+            .into_iter()
+            .nth(index)
+            .map(|node| (node.line - 1, node.span))
+            .ok_or_else(|| format!("element index {} out of range", index))
+    }
+
+    /// Removes a declaration and the `;` after it, and its line if nothing
+    /// else is left on it.
+    fn remove_span(&mut self, line: usize, span: Range<usize>) {
+        let text = &self.lines[line];
+        let after = &text[span.end..];
+        let end = match after.trim_start().strip_prefix(';') {
+            Some(rest) => text.len() - rest.len(),
+            None => span.end,
+        };
+        self.lines[line].replace_range(span.start..end, "");
+        if self.lines[line].trim().is_empty() {
+            self.lines.remove(line);
+        }
+    }
+
+    /// Inserts a line before line `at`, with the document's line ending.
+    fn insert_line(&mut self, at: usize, text: String) {
+        let newline = if self.lines.iter().any(|line| line.ends_with("\r\n")) {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        if let Some(previous) = at.checked_sub(1).and_then(|at| self.lines.get_mut(at)) {
+            if !previous.ends_with('\n') {
+                previous.push_str(newline);
+            }
+        }
+        self.lines.insert(at, text + newline);
+    }
+}
+
+/// Writes the document's text, byte for byte as parsed apart from edits.
+impl fmt::Display for CstDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.lines.iter().try_for_each(|line| f.write_str(line))
+    }
+}
+
+/// Splits an env declaration into its trimmed name and value.
+fn env_parts(decl: &str) -> (&str, &str) {
+    let content = &decl[3..];
+    let (name, value) = content.split_once('=').unwrap_or((content, ""));
+    (name.trim(), value.trim())
+}
+
+/// Byte offset of `inner` within `outer`, which it must be a slice of.
+fn offset(outer: &str, inner: &str) -> usize {
+    inner.as_ptr() as usize - outer.as_ptr() as usize
+}
+
+fn indentation(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "DOCTYPE=JTL\r\n/* settings */\r\n>>>ENV;\r\n  >>>host = db ;\r\n\r\n\
+                          >>>BEGIN;\r\n  >//> @primary\r\n  >a=\"1\">web>$env:host; >b=\"2\">cache>redis;\r\n\
+                          \r\n  >c=\"3\">log>on;\r\n>>>END;\r\n*/ trailing\r\n";
+
+    #[test]
+    fn test_cst_round_trip() {
+        let cst = CstDocument::parse(SOURCE).unwrap();
+        assert_eq!(cst.to_string(), SOURCE);
+        let kinds: Vec<NodeKind> = cst.nodes().iter().map(|node| node.kind).collect();
+        use NodeKind::*;
+        assert_eq!(
+            kinds,
+            [
+                Doctype, Comment, Directive, Env, Blank, Directive, Comment, Element, Element,
+                Blank, Element, Directive, Comment
+            ]
+        );
+        assert_eq!(cst.env("host"), Some("db"));
+        assert_eq!(cst.element(0).unwrap().content, "$env:host");
+        assert_eq!(
+            cst.to_document().unwrap(),
+            JtlDocument::parse(SOURCE).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_cst_edits() {
+        let mut cst = CstDocument::parse(SOURCE).unwrap();
+        cst.set_env("host", "primary").unwrap();
+        cst.set_env("port", "5432").unwrap();
+        let cache = JtlElement::new("cache", "memcached").with_attr("b", "2");
+        cst.replace_element(1, &cache).unwrap();
+        cst.insert_element(1, &JtlElement::new("queue", "rabbit").with_attr("q", "1"))
+            .unwrap();
+        cst.push_element(&JtlElement::new("tail", "x").with_attr("t", "1"))
+            .unwrap();
+        assert!(cst.remove_element(3));
+        assert!(!cst.remove_element(9));
+        assert!(cst.insert_element(9, &cache).is_err());
+        assert_eq!(
+            cst.to_string(),
+            "DOCTYPE=JTL\r\n/* settings */\r\n>>>ENV;\r\n  >>>host = primary ;\r\n  >>>port=5432;\r\n\r\n\
+             >>>BEGIN;\r\n  >//> @primary\r\n  >a=\"1\">web>$env:host; >q=\"1\">queue>rabbit; \
+             >b=\"2\">cache>memcached;\r\n\r\n  >t=\"1\">tail>x;\r\n>>>END;\r\n*/ trailing\r\n"
+        );
+
+        assert!(cst.remove_env("port"));
+        assert!(cst
+            .to_string()
+            .contains(">>>host = primary ;\r\n\r\n>>>BEGIN;"));
+        let document = cst.to_document().unwrap();
+        assert_eq!(document.elements[0].content, "primary");
+        assert_eq!(document.elements.len(), 4);
+    }
+
+    #[test]
+    fn test_cst_annotation_lines() {
+        let mut cst = CstDocument::parse(SOURCE).unwrap();
+        let first = JtlElement::new("first", "1")
+            .with_attr("f", "1")
+            .with_annotation("owner", "ops");
+        cst.insert_element(0, &first).unwrap();
+        assert!(cst.to_string().contains(
+            ">>>BEGIN;\r\n  >//> @owner=ops\r\n  >f=\"1\">first>1;\r\n  >//> @primary\r\n  >a=\"1\">web"
+        ));
+        assert!(cst.insert_element(2, &first).is_err());
+
+        let document = cst.to_document().unwrap();
+        assert_eq!(document.elements[0].annotations, first.annotations);
+        assert_eq!(document.elements[1].annotation("primary"), Some(""));
+
+        assert!(cst.remove_element(1));
+        assert!(cst.remove_element(0));
+        let document = cst.to_document().unwrap();
+        assert_eq!(document.elements[0].key, "cache");
+        assert!(document.elements[0].annotations.is_empty());
+        assert!(!cst.to_string().contains(">//>"));
+    }
+}
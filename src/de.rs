@@ -0,0 +1,782 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use serde::de::value::{BorrowedStrDeserializer, SeqDeserializer};
+use serde::de::{
+    self, DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+
+use crate::{BorrowedElement, JtlDocument, JtlElement};
+
+/// Error returned when an element does not fit the target type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeError {
+    message: String,
+}
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for DeError {}
+
+impl de::Error for DeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DeError {
+            message: msg.to_string(),
+        }
+    }
+}
+
+impl DeError {
+    fn in_element(self, key: &str) -> Self {
+        DeError {
+            message: format!("element `{}`: {}", key, self.message),
+        }
+    }
+}
+
+/// Deserializes an element into `T`.
+///
+/// The element is presented as a map of its attributes, followed by `KEY` and
+/// `Content`. Values are strings, parsed on demand when the target field is a
+/// number, `bool` or `char`.
+///
+/// A struct with a `#[serde(flatten)]` field is buffered by serde before its
+/// fields are read, and buffered values stay strings. All of its fields must
+/// then be string-typed (`String`, `&str`, `Cow<str>`, or a map of them), not
+/// only the flattened one; a `u16` field would fail with "invalid type".
+pub fn from_element<'de, T: de::Deserialize<'de>>(element: &'de JtlElement) -> Result<T, DeError> {
+    JtlDeserializer::new().from_element(element)
+}
+
+/// Deserializes every element into `T`, failing on the first that does not fit.
+///
+/// With an enum for `T`, each element's `KEY` picks the variant: a unit
+/// variant ignores the rest of the element, while newtype and struct variants
+/// receive it as a map, exactly as [`from_element`] would present it. Enums
+/// declared with `#[serde(tag = "KEY")]` also pick the variant by `KEY`, but
+/// serde buffers the element for them, so as with `#[serde(flatten)]` their
+/// fields must be string-typed.
+pub fn from_elements<'de, T: de::Deserialize<'de>>(
+    elements: &'de [JtlElement],
+) -> Result<Vec<T>, DeError> {
+    JtlDeserializer::new().from_elements(elements)
+}
+
+/// Deserializes a JTL document into `T`, which sees the elements as a
+/// sequence, as with [`from_document`].
+///
+/// The text is read as [`parse_document`](crate::parse_document) reads it,
+/// with `>>>DEFAULTS`, `$env:NAME` substitution and `${env:...}`
+/// interpolation. Keys, attributes and substituted content are handed to `T`
+/// as slices of `text`, so `&'de str` fields borrow from the input; content
+/// built by interpolation is owned and needs a `String` or `Cow` field.
+pub fn from_str<'de, T: de::Deserialize<'de>>(text: &'de str) -> Result<T, DeError> {
+    JtlDeserializer::new().from_str(text)
+}
+
+/// Deserializes a whole document into `T`, which sees the elements as a
+/// sequence.
+///
+/// Any collection of element types works, not only `Vec`: a `HashSet`, a
+/// fixed-size array or a tuple of different element types, for instance.
+pub fn from_document<'de, T: de::Deserialize<'de>>(
+    document: &'de JtlDocument,
+) -> Result<T, DeError> {
+    T::deserialize(JtlDeserializer::new().elements(&document.elements))
+}
+
+/// Deserialization settings, for structs that do not name their fields `KEY`
+/// and `Content`.
+///
+/// ```
+/// use jtl_rs::{JtlDeserializer, JtlElement};
+/// use std::collections::HashMap;
+///
+/// let element = JtlElement::new("web", "frontend").with_attr("port", "80");
+/// let map: HashMap<String, String> = JtlDeserializer::new()
+///     .key_field("id")
+///     .content_field("body")
+///     .from_element(&element)
+///     .unwrap();
+/// assert_eq!(map["id"], "web");
+/// assert_eq!(map["body"], "frontend");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JtlDeserializer {
+    key_field: &'static str,
+    content_field: &'static str,
+}
+
+impl Default for JtlDeserializer {
+    fn default() -> Self {
+        JtlDeserializer {
+            key_field: "KEY",
+            content_field: "Content",
+        }
+    }
+}
+
+impl JtlDeserializer {
+    /// Creates a deserializer presenting elements with `KEY` and `Content`.
+    pub fn new() -> Self {
+        JtlDeserializer::default()
+    }
+
+    /// Sets the field name the element key is presented under.
+    pub fn key_field(mut self, name: &'static str) -> Self {
+        self.key_field = name;
+        self
+    }
+
+    /// Sets the field name the element content is presented under.
+    pub fn content_field(mut self, name: &'static str) -> Self {
+        self.content_field = name;
+        self
+    }
+
+    /// Deserializes an element into `T`. See [`from_element`].
+    pub fn from_element<'de, T: de::Deserialize<'de>>(
+        &self,
+        element: &'de JtlElement,
+    ) -> Result<T, DeError> {
+        T::deserialize(self.element_deserializer(element))
+            .map_err(|err| err.in_element(&element.key))
+    }
+
+    /// Deserializes every element into `T`. See [`from_elements`].
+    pub fn from_elements<'de, T: de::Deserialize<'de>>(
+        &self,
+        elements: &'de [JtlElement],
+    ) -> Result<Vec<T>, DeError> {
+        elements
+            .iter()
+            .map(|element| self.from_element(element))
+            .collect()
+    }
+
+    /// Returns a [`Deserializer`] presenting `elements` as a sequence, each
+    /// element as [`from_element`] presents it. See [`from_document`].
+    pub fn elements<'de>(&self, elements: &'de [JtlElement]) -> ElementsDeserializer<'de> {
+        ElementsDeserializer {
+            settings: *self,
+            elements: elements.iter(),
+        }
+    }
+
+    /// Deserializes a JTL document into `T`. See [`from_str`].
+    pub fn from_str<'de, T: de::Deserialize<'de>>(&self, text: &'de str) -> Result<T, DeError> {
+        let elements = crate::parse_borrowed(text).map_err(de::Error::custom)?;
+        T::deserialize(BorrowedElementsDeserializer {
+            settings: *self,
+            elements: elements.into_iter(),
+        })
+    }
+
+    fn element_deserializer<'de>(&self, element: &'de JtlElement) -> ElementDeserializer<'de> {
+        let attrs = element
+            .attrs
+            .iter()
+            .map(|(name, value)| (Cow::Borrowed(name.as_str()), Cow::Borrowed(value.as_str())));
+        self.entries(&element.key, attrs, Cow::Borrowed(&element.content))
+    }
+
+    fn entries<'de>(
+        &self,
+        key: &'de str,
+        attrs: impl Iterator<Item = (Cow<'de, str>, Cow<'de, str>)>,
+        content: Cow<'de, str>,
+    ) -> ElementDeserializer<'de> {
+        let mut entries: Vec<_> = attrs.collect();
+        entries.push((Cow::Borrowed(self.key_field), Cow::Borrowed(key)));
+        entries.push((Cow::Borrowed(self.content_field), content));
+        ElementDeserializer { key, entries }
+    }
+}
+
+/// A [`Deserializer`] over a sequence of elements, returned by
+/// [`JtlDeserializer::elements`].
+#[derive(Debug, Clone)]
+pub struct ElementsDeserializer<'de> {
+    settings: JtlDeserializer,
+    elements: std::slice::Iter<'de, JtlElement>,
+}
+
+impl<'de> Deserializer<'de> for ElementsDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, DeError> {
+        let total = self.elements.len();
+        let value = visitor.visit_seq(&mut self)?;
+        match self.elements.len() {
+            0 => Ok(value),
+            left => Err(de::Error::invalid_length(
+                total,
+                &format!("{} elements", total - left).as_str(),
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> SeqAccess<'de> for ElementsDeserializer<'de> {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, DeError> {
+        let Some(element) = self.elements.next() else {
+            return Ok(None);
+        };
+        seed.deserialize(self.settings.element_deserializer(element))
+            .map(Some)
+            .map_err(|err| err.in_element(&element.key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+/// The elements parsed by [`JtlDeserializer::from_str`], as a sequence.
+struct BorrowedElementsDeserializer<'de> {
+    settings: JtlDeserializer,
+    elements: std::vec::IntoIter<BorrowedElement<'de>>,
+}
+
+impl<'de> Deserializer<'de> for BorrowedElementsDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, DeError> {
+        let total = self.elements.len();
+        let value = visitor.visit_seq(&mut self)?;
+        match self.elements.len() {
+            0 => Ok(value),
+            left => Err(de::Error::invalid_length(
+                total,
+                &format!("{} elements", total - left).as_str(),
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> SeqAccess<'de> for BorrowedElementsDeserializer<'de> {
+    type Error = DeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, DeError> {
+        let Some(element) = self.elements.next() else {
+            return Ok(None);
+        };
+        let key = element.key;
+        let deserializer = self
+            .settings
+            .entries(key, element.attrs.into_iter(), element.content);
+        seed.deserialize(deserializer)
+            .map(Some)
+            .map_err(|err| err.in_element(key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+/// Presents one element as a map, or as an enum variant named by its key.
+struct ElementDeserializer<'de> {
+    key: &'de str,
+    entries: Vec<(Cow<'de, str>, Cow<'de, str>)>,
+}
+
+impl<'de> Deserializer<'de> for ElementDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_map(Entries {
+            entries: self.entries.into_iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_enum(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+impl<'de> EnumAccess<'de> for ElementDeserializer<'de> {
+    type Error = DeError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), DeError> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::new(self.key))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for ElementDeserializer<'de> {
+    type Error = DeError;
+
+    fn unit_variant(self) -> Result<(), DeError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, DeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, _visitor: V) -> Result<V::Value, DeError> {
+        Err(de::Error::invalid_type(
+            de::Unexpected::Map,
+            &"a unit, newtype or struct variant",
+        ))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_any(visitor)
+    }
+}
+
+struct Entries<'de> {
+    entries: std::vec::IntoIter<(Cow<'de, str>, Cow<'de, str>)>,
+    value: Option<Cow<'de, str>>,
+}
+
+impl<'de> MapAccess<'de> for Entries<'de> {
+    type Error = DeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DeError> {
+        match self.entries.next() {
+            Some((name, value)) => {
+                self.value = Some(value);
+                seed.deserialize(ValueDeserializer(name)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DeError> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value requested before key"))?;
+        seed.deserialize(ValueDeserializer(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+/// A single attribute value, key or content.
+struct ValueDeserializer<'de>(Cow<'de, str>);
+
+macro_rules! parse_value {
+    ($($method:ident => $visit:ident: $ty:ty,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+                match self.0.parse::<$ty>() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => Err(de::Error::invalid_value(de::Unexpected::Str(&self.0), &visitor)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = DeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.0 {
+            Cow::Borrowed(value) => visitor.visit_borrowed_str(value),
+            Cow::Owned(value) => visitor.visit_string(value),
+        }
+    }
+
+    parse_value! {
+        deserialize_bool => visit_bool: bool,
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_i128 => visit_i128: i128,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_u128 => visit_u128: u128,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+        deserialize_char => visit_char: char,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Reads a list attribute, `[a, b, c]`.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        let items: Option<Vec<Cow<'de, str>>> = match &self.0 {
+            Cow::Borrowed(value) => crate::list::parse_list(value)
+                .map(|items| items.into_iter().map(Cow::Borrowed).collect()),
+            Cow::Owned(value) => crate::list::parse_list(value).map(|items| {
+                items
+                    .into_iter()
+                    .map(|item| Cow::Owned(item.to_string()))
+                    .collect()
+            }),
+        };
+        let items =
+            items.ok_or_else(|| de::Error::invalid_type(de::Unexpected::Str(&self.0), &visitor))?;
+        let mut seq = SeqDeserializer::new(items.into_iter().map(ValueDeserializer));
+        let value = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DeError> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct tuple_struct map
+        struct identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, DeError> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::Deserialize;
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+    /// A visitor that reads `port` as a number and collects the attributes it
+    /// does not name into a map. Derived code with `#[serde(flatten)]` would
+    /// buffer the element first and could not read `port` as a `u16`.
+    #[derive(Debug, PartialEq)]
+    struct Service {
+        name: String,
+        port: u16,
+        body: String,
+        extra: HashMap<String, String>,
+    }
+
+    impl<'de> Deserialize<'de> for Service {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct ServiceVisitor;
+
+            impl<'de> Visitor<'de> for ServiceVisitor {
+                type Value = Service;
+
+                fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str("struct Service")
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Service, A::Error> {
+                    let (mut name, mut port, mut body) = (None, None, None);
+                    let mut extra = HashMap::new();
+                    while let Some(key) = map.next_key::<String>()? {
+                        match key.as_str() {
+                            "name" => name = Some(map.next_value()?),
+                            "port" => port = Some(map.next_value()?),
+                            "Content" => body = Some(map.next_value()?),
+                            _ => {
+                                extra.insert(key, map.next_value()?);
+                            }
+                        }
+                    }
+                    Ok(Service {
+                        name: name.ok_or_else(|| de::Error::missing_field("name"))?,
+                        port: port.ok_or_else(|| de::Error::missing_field("port"))?,
+                        body: body.ok_or_else(|| de::Error::missing_field("Content"))?,
+                        extra,
+                    })
+                }
+            }
+
+            deserializer.deserialize_map(ServiceVisitor)
+        }
+    }
+
+    #[test]
+    fn test_from_element_extra_attrs() {
+        let element = JtlElement::new("web", "frontend")
+            .with_attr("name", "nginx")
+            .with_attr("port", "8080")
+            .with_attr("region", "eu");
+        let service: Service = from_element(&element).unwrap();
+        assert_eq!(service.name, "nginx");
+        assert_eq!(service.port, 8080);
+        assert_eq!(service.body, "frontend");
+        assert_eq!(service.extra.len(), 2);
+        assert_eq!(service.extra["region"], "eu");
+        assert_eq!(service.extra["KEY"], "web");
+
+        let map: HashMap<&str, &str> = from_element(&element).unwrap();
+        assert_eq!(map["Content"], "frontend");
+
+        let element = JtlElement::new("db", "postgres")
+            .with_attr("name", "pg")
+            .with_attr("port", "high");
+        let err = from_element::<Service>(&element).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "element `db`: invalid value: string \"high\", expected u16"
+        );
+    }
+
+    /// Buffers an element through `deserialize_any`, as serde does for
+    /// `#[serde(flatten)]` and `#[serde(tag = "KEY")]`, then reads it back.
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_from_element_buffered() {
+        let element = JtlElement::new("web", "frontend").with_attr("port", "8080");
+        let buffered: serde_json::Value = from_element(&element).unwrap();
+        assert_eq!(
+            buffered,
+            serde_json::json!({ "port": "8080", "KEY": "web", "Content": "frontend" })
+        );
+
+        let strings = HashMap::<String, String>::deserialize(&buffered).unwrap();
+        assert_eq!(strings["port"], "8080");
+        let err = u16::deserialize(&buffered["port"]).unwrap_err();
+        assert!(err.to_string().starts_with("invalid type: string \"8080\""));
+    }
+
+    /// What `#[derive(Deserialize)]` generates for
+    /// `enum Entry { Server { port: u16 }, Note(HashMap<String, String>), Separator }`.
+    #[derive(Debug, PartialEq)]
+    enum Entry {
+        Server { port: u16 },
+        Note(HashMap<String, String>),
+        Separator,
+    }
+
+    impl<'de> Deserialize<'de> for Entry {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            const VARIANTS: &[&str] = &["Server", "Note", "Separator"];
+
+            struct PortVisitor;
+
+            impl<'de> Visitor<'de> for PortVisitor {
+                type Value = Entry;
+
+                fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str("struct variant Entry::Server")
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Entry, A::Error> {
+                    let mut port = None;
+                    while let Some(key) = map.next_key::<&str>()? {
+                        if key == "port" {
+                            port = Some(map.next_value()?);
+                        } else {
+                            map.next_value::<de::IgnoredAny>()?;
+                        }
+                    }
+                    Ok(Entry::Server {
+                        port: port.ok_or_else(|| de::Error::missing_field("port"))?,
+                    })
+                }
+            }
+
+            struct EntryVisitor;
+
+            impl<'de> Visitor<'de> for EntryVisitor {
+                type Value = Entry;
+
+                fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str("enum Entry")
+                }
+
+                fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Entry, A::Error> {
+                    let (tag, variant) = data.variant::<&str>()?;
+                    match tag {
+                        "Server" => variant.struct_variant(&["port"], PortVisitor),
+                        "Note" => variant.newtype_variant().map(Entry::Note),
+                        "Separator" => variant.unit_variant().map(|()| Entry::Separator),
+                        _ => Err(de::Error::unknown_variant(tag, VARIANTS)),
+                    }
+                }
+            }
+
+            deserializer.deserialize_enum("Entry", VARIANTS, EntryVisitor)
+        }
+    }
+
+    #[test]
+    fn test_from_elements_enum() {
+        let document = crate::JtlDocument::parse(
+            r#"DOCTYPE=JTL
+>>>BEGIN;
+>port="80">Server>web;
+>author="ops">Note>check the logs;
+>n="1">Separator>-;
+>>>END;"#,
+        )
+        .unwrap();
+        let entries: Vec<Entry> = from_elements(&document.elements).unwrap();
+        assert_eq!(entries[0], Entry::Server { port: 80 });
+        assert!(matches!(&entries[1], Entry::Note(map) if map["author"] == "ops"));
+        assert_eq!(entries[2], Entry::Separator);
+
+        let unknown = [JtlElement::new("Widget", "x").with_attr("n", "1")];
+        let err = from_elements::<Entry>(&unknown).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "element `Widget`: unknown variant `Widget`, expected one of `Server`, `Note`, `Separator`"
+        );
+    }
+
+    #[test]
+    fn test_from_str_borrows() {
+        let text = r#"DOCTYPE=JTL
+>>>ENV;
+>>>greeting=hello;
+>>>BEGIN;
+>lang="en">intro>$env:greeting;
+>lang="fr">outro>au revoir;
+>>>END;"#;
+        let maps: Vec<HashMap<&str, &str>> = from_str(text).unwrap();
+        assert_eq!(maps[0]["Content"], "hello");
+        assert_eq!(maps[1]["lang"], "fr");
+        let range = text.as_bytes().as_ptr_range();
+        for value in maps.iter().flat_map(|map| map.values()) {
+            assert!(range.contains(&value.as_ptr()));
+        }
+
+        let err = from_str::<Vec<HashMap<&str, u8>>>(text).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "element `intro`: invalid value: string \"en\", expected u8"
+        );
+        let err =
+            from_str::<Vec<HashMap<&str, &str>>>("DOCTYPE=JTL\n>>>BEGIN;\n>a;\n").unwrap_err();
+        assert_eq!(err.to_string(), "invalid element format: too short");
+    }
+
+    #[test]
+    fn test_from_str_interpolates() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>host=db;\n>>>DEFAULTS port=\"5432\";\n>>>BEGIN;\n\
+                    >n=\"1\">url>postgres://${env:host}/;\n>>>END;";
+        let [map]: [HashMap<&str, Cow<str>>; 1] = from_str(text).unwrap();
+        assert_eq!(map["Content"], "postgres://db/");
+        assert_eq!(map["port"], "5432");
+
+        let err = from_str::<Vec<HashMap<&str, &str>>>(text).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("element `url`: invalid type: string"));
+    }
+
+    #[test]
+    fn test_from_document() {
+        let document = JtlDocument::parse(
+            "DOCTYPE=JTL\n>>>BEGIN;\n>port=\"80\">web>a;\n>port=\"80\">web>a;\n>n=\"2\">db>b;\n>>>END;",
+        )
+        .unwrap();
+        let array: [HashMap<&str, &str>; 3] = from_document(&document).unwrap();
+        assert_eq!(array[2]["KEY"], "db");
+        let unique: BTreeSet<BTreeMap<&str, &str>> = from_document(&document).unwrap();
+        assert_eq!(unique.len(), 2);
+
+        let err = from_document::<Vec<HashMap<&str, u8>>>(&document).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "element `web`: invalid value: string \"web\", expected u8"
+        );
+        let err = from_document::<[HashMap<&str, &str>; 2]>(&document).unwrap_err();
+        assert_eq!(err.to_string(), "invalid length 3, expected 2 elements");
+    }
+
+    #[test]
+    fn test_field_names() {
+        let element = JtlElement::new("web", "frontend").with_attr("port", "80");
+        let deserializer = JtlDeserializer::new().content_field("body");
+        let map: HashMap<&str, &str> = deserializer.from_element(&element).unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!((map["KEY"], map["body"]), ("web", "frontend"));
+
+        let maps: Vec<HashMap<&str, &str>> = deserializer
+            .key_field("id")
+            .from_str("DOCTYPE=JTL\n>>>BEGIN;\n>port=\"80\">web>frontend;\n>>>END;")
+            .unwrap();
+        assert_eq!(maps[0]["id"], "web");
+        assert!(!maps[0].contains_key("KEY"));
+    }
+
+    #[test]
+    fn test_list_value() {
+        let ports = Vec::<u16>::deserialize(ValueDeserializer("[80, 443]".into())).unwrap();
+        assert_eq!(ports, [80, 443]);
+        let pair = <(String, bool)>::deserialize(ValueDeserializer("[a, true]".into())).unwrap();
+        assert_eq!(pair, ("a".to_string(), true));
+        assert!(Vec::<u16>::deserialize(ValueDeserializer("80".into())).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::encoding::TextFormat;
+use crate::iter::parse_iter_with;
+use crate::options::ParseOptions;
+use crate::JtlDocument;
+
+/// Reported by [`parse_with_deadline`] when parsing ran out of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TimedOut {
+    /// How long parsing had run when it stopped.
+    pub elapsed: Duration,
+    /// The line of the first element that was not parsed.
+    pub line: usize,
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "parse timed out after {:?} at line {}",
+            self.elapsed, self.line
+        )
+    }
+}
+
+impl Error for TimedOut {}
+
+/// What [`parse_with_deadline`] parsed before it finished or ran out of time.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct PartialParse {
+    /// The environment and the elements parsed in time.
+    pub document: JtlDocument,
+    /// Set if parsing stopped at the deadline with elements left, in which
+    /// case `document` holds only the elements before the one at
+    /// [`TimedOut::line`].
+    pub timed_out: Option<TimedOut>,
+}
+
+impl PartialParse {
+    /// Returns the document if it was parsed completely, or the [`TimedOut`]
+    /// diagnostic.
+    pub fn complete(self) -> Result<JtlDocument, TimedOut> {
+        match self.timed_out {
+            Some(timed_out) => Err(timed_out),
+            None => Ok(self.document),
+        }
+    }
+}
+
+/// Parses JTL text, stopping once `budget` has elapsed and returning what was
+/// parsed so far.
+///
+/// The clock is checked before every element, so an element is either parsed
+/// whole or not at all, and parsing can overrun the budget by at most one
+/// element. Running out of time once no elements are left is not a timeout:
+/// the rest of the input is still read, for its env variables and errors.
+/// Malformed input found before the deadline is still an error.
+pub fn parse_with_deadline(text: &str, budget: Duration) -> Result<PartialParse, Box<dyn Error>> {
+    parse_with_deadline_options(text, budget, &ParseOptions::default())
+}
+
+/// Like [`parse_with_deadline`], applying `options`.
+pub fn parse_with_deadline_options(
+    text: &str,
+    budget: Duration,
+    options: &ParseOptions,
+) -> Result<PartialParse, Box<dyn Error>> {
+    let start = Instant::now();
+    let mut iter = parse_iter_with(text, options);
+    let mut elements = Vec::new();
+    let mut timed_out = None;
+    loop {
+        let elapsed = start.elapsed();
+        if elapsed >= budget {
+            if let Some(line) = iter.next_element_line() {
+                timed_out = Some(TimedOut { elapsed, line });
+                break;
+            }
+        }
+        match iter.next() {
+            Some(element) => elements.push(element?),
+            None => break,
+        }
+    }
+    Ok(PartialParse {
+        document: iter.into_document(elements, TextFormat::detect(text)),
+        timed_out,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate, GenOptions};
+
+    #[test]
+    fn test_parse_with_deadline() {
+        let text = generate(&GenOptions::default()).to_jtl().unwrap();
+        let partial = parse_with_deadline(&text, Duration::ZERO).unwrap();
+        let timed_out = partial.timed_out.unwrap();
+        assert!(partial.document.elements.is_empty());
+        let first = JtlDocument::parse(&text).unwrap().elements[0].line;
+        assert_eq!(Some(timed_out.line), first);
+        assert!(timed_out.to_string().starts_with("parse timed out after "));
+        assert!(partial.complete().is_err());
+
+        let full = parse_with_deadline(&text, Duration::from_secs(60)).unwrap();
+        assert_eq!(full.complete().unwrap().elements.len(), 100);
+        let bad = "DOCTYPE=JTL\n>>>BEGIN;\n>bad;";
+        assert!(parse_with_deadline(bad, Duration::from_secs(60)).is_err());
+    }
+
+    #[test]
+    fn test_deadline_after_last_element() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>a=1;\n>>>BEGIN;\n/* none */\n>>>END;\n";
+        let document = parse_with_deadline(text, Duration::ZERO)
+            .unwrap()
+            .complete()
+            .unwrap();
+        assert_eq!(document.env["a"], "1");
+
+        let text = "DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">a>x; >n=\"2\">b>y;\n>>>END;\n";
+        let options = ParseOptions::default();
+        let mut iter = parse_iter_with(text, &options);
+        assert_eq!(iter.next_element_line(), Some(3));
+        iter.next().unwrap().unwrap();
+        assert_eq!(iter.next_element_line(), Some(3));
+        iter.next().unwrap().unwrap();
+        assert_eq!(iter.next_element_line(), None);
+    }
+}
@@ -0,0 +1,266 @@
+#[cfg(feature = "json")]
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use crate::combine::copy_origin;
+use crate::{JtlDocument, JtlElement};
+
+/// One difference between two documents, as found by [`diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Difference {
+    /// A variable only the second document declares.
+    EnvAdded { name: String, value: String },
+    /// A variable only the first document declares.
+    EnvRemoved { name: String, value: String },
+    /// A variable both documents declare, with different values.
+    EnvChanged {
+        name: String,
+        old: String,
+        new: String,
+    },
+    /// An element only the second document has.
+    ElementAdded { element: JtlElement },
+    /// An element only the first document has.
+    ElementRemoved { element: JtlElement },
+    /// An element both documents have under the same key, with different
+    /// attributes or content.
+    ElementChanged { old: JtlElement, new: JtlElement },
+}
+
+impl Difference {
+    /// Converts the difference to JSON.
+    ///
+    /// Every object has `change` (`added`, `removed` or `changed`), `target`
+    /// (`env` or `element`) and `name` (the variable name or element key),
+    /// plus `old` and/or `new`. Elements are given in the shape produced by
+    /// [`parse`](crate::parse).
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> Value {
+        match self {
+            Difference::EnvAdded { name, value } => {
+                json!({ "change": "added", "target": "env", "name": name, "new": value })
+            }
+            Difference::EnvRemoved { name, value } => {
+                json!({ "change": "removed", "target": "env", "name": name, "old": value })
+            }
+            Difference::EnvChanged { name, old, new } => {
+                json!({ "change": "changed", "target": "env", "name": name, "old": old, "new": new })
+            }
+            Difference::ElementAdded { element } => json!({
+                "change": "added", "target": "element", "name": element.key,
+                "new": element.to_value()
+            }),
+            Difference::ElementRemoved { element } => json!({
+                "change": "removed", "target": "element", "name": element.key,
+                "old": element.to_value()
+            }),
+            Difference::ElementChanged { old, new } => json!({
+                "change": "changed", "target": "element", "name": old.key,
+                "old": old.to_value(), "new": new.to_value()
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::EnvAdded { name, value } => write!(f, "+ env {}={}", name, value),
+            Difference::EnvRemoved { name, value } => write!(f, "- env {}={}", name, value),
+            Difference::EnvChanged { name, old, new } => {
+                write!(f, "~ env {}: {} -> {}", name, old, new)
+            }
+            Difference::ElementAdded { element } => write!(f, "+ {}", describe(element)),
+            Difference::ElementRemoved { element } => write!(f, "- {}", describe(element)),
+            Difference::ElementChanged { old, new } => {
+                write!(f, "~ {}\n  {}", describe(old), describe(new))
+            }
+        }
+    }
+}
+
+/// Formats an element as its JTL declaration.
+fn describe(element: &JtlElement) -> String {
+    let attrs: Vec<String> = element
+        .attrs
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, value))
+        .collect();
+    format!(">{}>{}>{};", attrs.join(" "), element.key, element.content)
+}
+
+/// Pairs elements of `a` and `b` by key: the n-th element with a given key in
+/// `a` is matched with the n-th element with that key in `b`.
+fn pair_elements<'a>(
+    a: &'a [JtlElement],
+    b: &'a [JtlElement],
+) -> (Vec<(usize, Option<usize>)>, Vec<usize>) {
+    let mut by_key: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, element) in b.iter().enumerate().rev() {
+        by_key.entry(&element.key).or_default().push(index);
+    }
+    let pairs: Vec<(usize, Option<usize>)> = a
+        .iter()
+        .enumerate()
+        .map(|(index, element)| {
+            let matched = by_key.get_mut(element.key.as_str()).and_then(Vec::pop);
+            (index, matched)
+        })
+        .collect();
+    let mut unmatched: Vec<usize> = by_key.into_values().flatten().collect();
+    unmatched.sort_unstable();
+    (pairs, unmatched)
+}
+
+/// Lists the differences between `a` and `b`.
+///
+/// Env variables are compared by name and reported first, sorted by name.
+/// Elements are matched by key, in order of occurrence, so reordering
+/// elements with distinct keys is not a difference. Changed and removed
+/// elements follow the order of `a`, and added elements the order of `b`.
+pub fn diff(a: &JtlDocument, b: &JtlDocument) -> Vec<Difference> {
+    let names: BTreeSet<&String> = a.env.keys().chain(b.env.keys()).collect();
+    let mut differences: Vec<Difference> = names
+        .into_iter()
+        .filter_map(|name| match (a.env.get(name), b.env.get(name)) {
+            (Some(old), Some(new)) if old != new => Some(Difference::EnvChanged {
+                name: name.clone(),
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            (Some(value), None) => Some(Difference::EnvRemoved {
+                name: name.clone(),
+                value: value.clone(),
+            }),
+            (None, Some(value)) => Some(Difference::EnvAdded {
+                name: name.clone(),
+                value: value.clone(),
+            }),
+            _ => None,
+        })
+        .collect();
+
+    let (pairs, added) = pair_elements(&a.elements, &b.elements);
+    for (old, new) in pairs {
+        let old = &a.elements[old];
+        match new.map(|new| &b.elements[new]) {
+            Some(new) if new != old => differences.push(Difference::ElementChanged {
+                old: old.clone(),
+                new: new.clone(),
+            }),
+            Some(_) => {}
+            None => differences.push(Difference::ElementRemoved {
+                element: old.clone(),
+            }),
+        }
+    }
+    differences.extend(added.into_iter().map(|index| Difference::ElementAdded {
+        element: b.elements[index].clone(),
+    }));
+    differences
+}
+
+/// Layers `overlay` on top of `base`.
+///
+/// Variables in `overlay` replace those of the same name in `base`. Elements
+/// are matched by key as in [`diff`]: a matched element of `base` is replaced
+/// in place by its counterpart, and elements only `overlay` has are appended
+/// in order. Nothing is ever removed.
+pub fn merge(base: &JtlDocument, overlay: &JtlDocument) -> JtlDocument {
+    let mut merged = JtlDocument::new();
+    merged.env = base.env.clone();
+    merged.env_origins = base.env_origins.clone();
+    for (name, value) in &overlay.env {
+        merged.env.insert(name.clone(), value.clone());
+        copy_origin(&mut merged, overlay, name);
+    }
+
+    let (pairs, added) = pair_elements(&base.elements, &overlay.elements);
+    merged.elements = pairs
+        .into_iter()
+        .map(|(old, new)| match new {
+            Some(new) => overlay.elements[new].clone(),
+            None => base.elements[old].clone(),
+        })
+        .collect();
+    merged.elements.extend(
+        added
+            .into_iter()
+            .map(|index| overlay.elements[index].clone()),
+    );
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn documents() -> (JtlDocument, JtlDocument) {
+        let a = JtlDocument::parse(
+            r#"DOCTYPE=JTL
+>>>ENV;
+>>>host=localhost;
+>>>debug=true;
+>>>BEGIN;
+>port="80">web>nginx;
+>port="5432">db>postgres;
+>n="1">worker>one;
+>>>END;"#,
+        )
+        .unwrap();
+        let b = JtlDocument::parse(
+            r#"DOCTYPE=JTL
+>>>ENV;
+>>>host=example.com;
+>>>region=eu;
+>>>BEGIN;
+>port="5432">db>postgres;
+>port="443">web>nginx;
+>n="2">cache>redis;
+>>>END;"#,
+        )
+        .unwrap();
+        (a, b)
+    }
+
+    #[test]
+    fn test_diff() {
+        let (a, b) = documents();
+        let lines: Vec<String> = diff(&a, &b).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            lines,
+            [
+                "- env debug=true",
+                "~ env host: localhost -> example.com",
+                "+ env region=eu",
+                "~ >port=\"80\">web>nginx;\n  >port=\"443\">web>nginx;",
+                "- >n=\"1\">worker>one;",
+                "+ >n=\"2\">cache>redis;",
+            ]
+        );
+        assert!(diff(&a, &a).is_empty());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_diff_json() {
+        let (a, b) = documents();
+        assert_eq!(
+            diff(&a, &b)[1].to_json(),
+            json!({ "change": "changed", "target": "env", "name": "host", "old": "localhost", "new": "example.com" })
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let (a, b) = documents();
+        let merged = merge(&a, &b);
+        assert_eq!(merged.env["host"], "example.com");
+        assert_eq!(merged.env["debug"], "true");
+        let keys: Vec<&str> = merged.elements.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["web", "db", "worker", "cache"]);
+        assert_eq!(merged.elements[0].attrs[0].1, "443");
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::encoding::TextFormat;
+use crate::iter::parse_iter_with;
+use crate::limits::Limits;
+use crate::options::{DuplicateEnv, ParseOptions};
+use crate::render::env_references;
+use crate::roundtrip::parse_strict_roundtrip;
+
+/// Which part of [`doctor`] reported a [`Finding`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Category {
+    /// The document does not parse.
+    Syntax,
+    /// The document parses, but something in it is questionable.
+    Lint,
+    /// An element is over a size cap.
+    Limits,
+    /// An env variable is referenced but not declared, or declared but unused.
+    Env,
+    /// Writing the document back out would lose or change something.
+    RoundTrip,
+}
+
+impl Category {
+    /// Returns the category's name, such as `round-trip`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Category::Syntax => "syntax",
+            Category::Lint => "lint",
+            Category::Limits => "limits",
+            Category::Env => "env",
+            Category::RoundTrip => "round-trip",
+        }
+    }
+}
+
+/// How serious a [`Finding`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum Severity {
+    /// Worth a look, but the document is usable.
+    Warning,
+    /// The document is broken or breaks a configured limit.
+    Error,
+}
+
+impl Severity {
+    /// Returns `error` or `warning`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+/// One problem reported by [`doctor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Finding {
+    /// The check that found it.
+    pub category: Category,
+    /// How serious it is.
+    pub severity: Severity,
+    /// The 1-based line it refers to, when there is one.
+    pub line: Option<usize>,
+    /// What was found.
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: ",
+            self.severity.as_str(),
+            self.category.as_str()
+        )?;
+        if let Some(line) = self.line {
+            write!(f, "line {}: ", line)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// The result of [`doctor`]: every finding, in the order the checks ran.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Report {
+    /// What the checks found.
+    pub findings: Vec<Finding>,
+    /// Number of elements read, or `0` if the document does not parse.
+    pub elements: usize,
+    /// Number of env variables declared.
+    pub env: usize,
+}
+
+impl Report {
+    /// Returns `true` if nothing was found at [`Severity::Error`].
+    pub fn is_healthy(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// Returns the findings at [`Severity::Error`].
+    pub fn errors(&self) -> impl Iterator<Item = &Finding> + '_ {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Error)
+    }
+
+    /// Returns the findings at [`Severity::Warning`].
+    pub fn warnings(&self) -> impl Iterator<Item = &Finding> + '_ {
+        self.findings
+            .iter()
+            .filter(|finding| finding.severity == Severity::Warning)
+    }
+
+    fn push(
+        &mut self,
+        category: Category,
+        severity: Severity,
+        line: Option<usize>,
+        message: impl Into<String>,
+    ) {
+        self.findings.push(Finding {
+            category,
+            severity,
+            line,
+            message: message.into(),
+        });
+    }
+}
+
+/// Formats one finding per line, followed by a summary.
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(f, "{}", finding)?;
+        }
+        write!(
+            f,
+            "{} elements, {} env variables: {} errors, {} warnings",
+            self.elements,
+            self.env,
+            self.errors().count(),
+            self.warnings().count()
+        )
+    }
+}
+
+/// Runs every health check on a JTL document and reports all findings at
+/// once, instead of stopping at the first problem.
+///
+/// ```
+/// let report = jtl_rs::doctor("DOCTYPE=JTL\n>>>ENV;\n>>>unused=1;\n>>>BEGIN;\n>n=\"1\">a>$env:nope;\n>>>END;");
+/// assert!(report.is_healthy());
+/// assert_eq!(report.warnings().count(), 2);
+/// ```
+pub fn doctor(text: &str) -> Report {
+    doctor_with(text, &ParseOptions::default())
+}
+
+/// Like [`doctor`], parsing with `options`.
+///
+/// Redefined env variables are reported as warnings whatever the
+/// [`DuplicateEnv`] policy, and every element over the configured
+/// [`Limits`] is reported as an error instead of failing the parse.
+pub fn doctor_with(text: &str, options: &ParseOptions) -> Report {
+    let mut report = Report::default();
+    let limits = options.limits.clone();
+    let options = options
+        .clone()
+        .limits(Limits::default())
+        .duplicate_env(DuplicateEnv::Warn);
+
+    let mut iter = parse_iter_with(text, &options);
+    let elements = match iter.by_ref().collect::<Result<Vec<_>, _>>() {
+        Ok(elements) => elements,
+        Err(err) => {
+            report.env = iter.env().len();
+            report.push(Category::Syntax, Severity::Error, err.line(), err.message());
+            return report;
+        }
+    };
+    for warning in std::mem::take(&mut iter.warnings) {
+        report.push(
+            Category::Lint,
+            Severity::Warning,
+            Some(warning.line),
+            warning.message,
+        );
+    }
+    let document = iter.into_document(elements, TextFormat::detect(text));
+    report.elements = document.elements.len();
+    report.env = document.env.len();
+
+    for element in &document.elements {
+        if let Some(lang) = element.lang() {
+            if let Err(err) = lang.check(&element.content) {
+                report.push(
+                    Category::Lint,
+                    Severity::Warning,
+                    element.line,
+                    format!(
+                        "element `{}`: content is not valid {}: {}",
+                        element.key, lang, err
+                    ),
+                );
+            }
+        }
+        if let Err(err) = limits.check_element(&element.key, element.attrs.len(), &element.content)
+        {
+            report.push(Category::Limits, Severity::Error, element.line, err);
+        }
+    }
+
+    if let Ok(references) = env_references(text) {
+        let mut undeclared: Vec<&String> = references
+            .keys()
+            .filter(|name| !document.env.contains_key(*name))
+            .collect();
+        undeclared.sort();
+        for name in undeclared {
+            report.push(
+                Category::Env,
+                Severity::Warning,
+                None,
+                format!("env variable `{}` is referenced but not declared", name),
+            );
+        }
+        let referenced: HashSet<&str> = references.keys().map(String::as_str).collect();
+        let interpolated = |name: &str| text.contains(&format!("${{env:{}", name));
+        let mut unused: Vec<&String> = document
+            .env
+            .keys()
+            .filter(|name| !referenced.contains(name.as_str()) && !interpolated(name))
+            .collect();
+        unused.sort();
+        for name in unused {
+            let line = document.env_origin(name).and_then(|origin| origin.line());
+            report.push(
+                Category::Env,
+                Severity::Warning,
+                line,
+                format!("env variable `{}` is declared but never used", name),
+            );
+        }
+    }
+
+    if let Err(err) = parse_strict_roundtrip(text) {
+        report.push(
+            Category::RoundTrip,
+            Severity::Warning,
+            None,
+            err.to_string(),
+        );
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_doctor() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>host=a;\n>>>host=b;\n>>>spare=x;\n>>>BEGIN;\n\
+                    >n=\"1\">web>$env:host;\n>n=\"2\" m=\"3\">db>$env:db_host;\n\
+                    >n=\"3\">x>y; stray\n>>>END;";
+        let report = doctor_with(text, &ParseOptions::new().max_attributes(1));
+        let findings: Vec<String> = report.findings.iter().map(Finding::to_string).collect();
+        assert_eq!(
+            findings,
+            [
+                "warning[lint]: line 4: env variable `host` redefined",
+                "error[limits]: line 8: element limit exceeded: `db` has 2 attributes (max 1)",
+                "warning[env]: env variable `db_host` is referenced but not declared",
+                "warning[env]: line 5: env variable `spare` is declared but never used",
+                "warning[round-trip]: round-trip mismatch on line 9: `stray` is not part of any declaration",
+            ]
+        );
+        assert!(!report.is_healthy());
+        assert_eq!((report.elements, report.env), (3, 2));
+        assert!(report
+            .to_string()
+            .ends_with("3 elements, 2 env variables: 1 errors, 4 warnings"));
+
+        let report = doctor("DOCTYPE=JTL\n>>>BEGIN;\n>oops;\n>>>END;");
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].category, Category::Syntax);
+        assert_eq!(report.findings[0].line, Some(3));
+        assert!(doctor("DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">a>b;\n>>>END;")
+            .findings
+            .is_empty());
+    }
+}
@@ -0,0 +1,476 @@
+use crate::annotation::annotation_line;
+use crate::edit::EditState;
+use crate::element::JtlElement;
+use crate::encoding::TextFormat;
+use crate::error::{sendable, FileError};
+use crate::file::write_atomic;
+use crate::options::ParseOptions;
+use crate::provenance::Origin;
+use crate::scan::Defaults;
+use crate::write::JtlWriter;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// A JTL document: its environment and its elements.
+///
+/// Documents are equal when their environments and elements are; the text
+/// format and other metadata of the text they were read from are not
+/// compared.
+#[derive(Debug, Clone, Default)]
+pub struct JtlDocument {
+    /// Variables declared in the `>>>ENV;` block.
+    pub env: HashMap<String, String>,
+    /// Elements of the body, in document order.
+    pub elements: Vec<JtlElement>,
+    pub(crate) edit: EditState,
+    pub(crate) format: TextFormat,
+    pub(crate) version: Option<String>,
+    pub(crate) defaults: Defaults,
+    pub(crate) env_origins: HashMap<String, Origin>,
+}
+
+impl PartialEq for JtlDocument {
+    fn eq(&self, other: &Self) -> bool {
+        self.env == other.env && self.elements == other.elements
+    }
+}
+
+impl Eq for JtlDocument {}
+
+/// Formats the document as JTL text, exactly as [`to_jtl`](JtlDocument::to_jtl)
+/// writes it when the document is valid.
+///
+/// Invalid elements and env variables are formatted as is rather than
+/// rejected, so the text may not parse back; use [`to_jtl`](JtlDocument::to_jtl)
+/// to have them checked.
+impl fmt::Display for JtlDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "DOCTYPE=JTL")?;
+        let mut names: Vec<&String> = self.env.keys().collect();
+        names.sort();
+        if !names.is_empty() {
+            writeln!(f, ">>>ENV;")?;
+        }
+        for name in names {
+            writeln!(f, ">>>{}={};", name, self.env[name])?;
+        }
+        writeln!(f, ">>>BEGIN;")?;
+        for element in &self.elements {
+            if !element.annotations.is_empty() {
+                writeln!(f, "{}", annotation_line(&element.annotations))?;
+            }
+            writeln!(f, "{}", element)?;
+        }
+        writeln!(f, ">>>END;")
+    }
+}
+
+impl JtlDocument {
+    /// Creates a document with no environment and no elements.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a skeleton document: no `DOCTYPE` version, no environment and
+    /// no elements.
+    ///
+    /// Written out, it is an empty `>>>BEGIN;`/`>>>END;` body that parses back
+    /// without errors and that [`append_to_file`](crate::append_to_file) can
+    /// add elements to.
+    pub fn empty() -> Self {
+        Self::new()
+    }
+
+    /// Creates a document with the given environment and no elements.
+    pub fn with_env<K, V>(env: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let mut document = Self::new();
+        document.env = env
+            .into_iter()
+            .map(|(name, value)| (name.into(), value.into()))
+            .collect();
+        document
+    }
+
+    /// Parses JTL text into a document.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        crate::parse_document_with(text, &ParseOptions::default())
+    }
+
+    /// Reads and parses the JTL file at `path`, recording it as the
+    /// [`Origin`] of every element and env variable.
+    ///
+    /// Errors are [`FileError`]s, naming the file and line.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|err| FileError::new(path, err))?;
+        let mut document = Self::parse(&text).map_err(|err| FileError::new(path, sendable(err)))?;
+        document.set_source(path);
+        Ok(document)
+    }
+
+    /// Returns the variables named `prefix.name`, keyed by `name`.
+    ///
+    /// Namespaces nest, so with `db.primary.host` declared, `scoped_env("db")`
+    /// has `primary.host` and `scoped_env("db.primary")` has `host`.
+    pub fn scoped_env(&self, prefix: &str) -> HashMap<String, String> {
+        self.env
+            .iter()
+            .filter_map(|(name, value)| {
+                let rest = name.strip_prefix(prefix)?.strip_prefix('.')?;
+                Some((rest.to_string(), value.clone()))
+            })
+            .collect()
+    }
+
+    /// Sets every variable of `vars` under `prefix`, as `prefix.name`.
+    ///
+    /// [`write_to`](JtlDocument::write_to) sorts variables by name, so each
+    /// namespace is written as one group.
+    pub fn set_scoped_env<'a>(
+        &mut self,
+        prefix: &str,
+        vars: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) {
+        for (name, value) in vars {
+            self.env
+                .insert(format!("{}.{}", prefix, name), value.to_string());
+        }
+    }
+
+    /// Iterates over the keys of the elements, in document order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> + '_ {
+        self.elements.iter().map(|element| element.key.as_str())
+    }
+
+    /// Iterates over the contents of the elements, in document order.
+    pub fn contents(&self) -> impl Iterator<Item = &str> + '_ {
+        self.elements.iter().map(|element| element.content.as_str())
+    }
+
+    /// Iterates over every attribute of every element as `(name, value)`.
+    pub fn attrs(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.elements.iter().flat_map(|element| {
+            element
+                .attrs
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str()))
+        })
+    }
+
+    /// Iterates over the values of attribute `name`, skipping elements that
+    /// don't have it.
+    pub fn attr_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.elements
+            .iter()
+            .filter_map(move |element| element.attr(name))
+    }
+
+    /// Writes the document as JTL text, with env variables sorted by name.
+    pub fn write_to<W: Write>(&self, inner: W) -> io::Result<W> {
+        self.write_with(JtlWriter::new(inner))
+    }
+
+    /// Returns the document as JTL text, as written by [`write_to`](JtlDocument::write_to).
+    pub fn to_jtl(&self) -> io::Result<String> {
+        let bytes = self.write_to(Vec::new())?;
+        Ok(String::from_utf8(bytes).expect("JTL output is UTF-8"))
+    }
+
+    /// Writes the document like [`write_to`](JtlDocument::write_to), but with
+    /// the line endings and byte order mark of the text it was parsed from.
+    pub fn write_preserving<W: Write>(&self, inner: W) -> io::Result<W> {
+        self.write_with(JtlWriter::new(inner).text_format(self.format))
+    }
+
+    /// Returns the text format detected when the document was parsed. New
+    /// documents use `\n` line endings and no BOM.
+    pub fn text_format(&self) -> TextFormat {
+        self.format
+    }
+
+    /// Returns the version given on the `DOCTYPE` line, such as `2` for
+    /// `DOCTYPE=JTL 2`. It is not written back out.
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Returns the attributes declared in `>>>DEFAULTS` blocks, in the order
+    /// first declared.
+    ///
+    /// They are already merged into the parsed elements, so this is only for
+    /// inspecting the source; they are not written back out.
+    pub fn defaults(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.defaults.iter()
+    }
+
+    /// Sets the text format used by [`write_preserving`](JtlDocument::write_preserving).
+    pub fn set_text_format(&mut self, format: TextFormat) {
+        self.format = format;
+    }
+
+    fn write_with<W: Write>(&self, mut writer: JtlWriter<W>) -> io::Result<W> {
+        let mut names: Vec<&String> = self.env.keys().collect();
+        names.sort();
+
+        for name in names {
+            writer = writer.env(name, &self.env[name])?;
+        }
+        writer = writer.begin()?;
+        for element in &self.elements {
+            writer = writer.element(element)?;
+        }
+        writer.finish()
+    }
+
+    /// Saves the document to `path` without ever leaving a partially written file.
+    ///
+    /// The text is written to a temporary file next to `path`, synced to disk, and
+    /// then renamed over the target.
+    pub fn save_atomic(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        write_atomic(path.as_ref(), |file| self.write_to(file).map(drop))
+    }
+
+    /// Writes the document to `path` atomically, like
+    /// [`save_atomic`](JtlDocument::save_atomic), but with the line endings and
+    /// byte order mark it was parsed with (see
+    /// [`write_preserving`](JtlDocument::write_preserving)).
+    ///
+    /// A crash or error mid-write leaves the old file untouched, and a file
+    /// that already exists keeps its permissions.
+    pub fn write_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        write_atomic(path.as_ref(), |file| self.write_preserving(file).map(drop))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::tests::temp_path;
+    use std::fs;
+
+    #[test]
+    fn test_parse_document() {
+        let doc = JtlDocument::parse(
+            r#"DOCTYPE=JTL
+>>>ENV;
+>>>foo=bar;
+>>>BEGIN;
+>b="2" a="1">id>$env:foo;
+>>>END;"#,
+        )
+        .unwrap();
+        assert_eq!(doc.env["foo"], "bar");
+        assert_eq!(
+            doc.elements,
+            vec![JtlElement::new("id", "bar")
+                .with_attr("b", "2")
+                .with_attr("a", "1")]
+        );
+    }
+
+    #[test]
+    fn test_document_metadata() {
+        let text = "DOCTYPE=JTL 2\n>>>DEFAULTS kind=\"user\";\n>>>BEGIN;\n>n=\"1\">id>x;\n>>>END;";
+        let doc = crate::parse_document(text).unwrap();
+        assert_eq!(doc.version(), Some("2"));
+        assert_eq!(doc.defaults().collect::<Vec<_>>(), [("kind", "user")]);
+        assert_eq!(doc.elements[0].attr("kind"), Some("user"));
+        assert_eq!(JtlDocument::parse(&doc.to_jtl().unwrap()).unwrap(), doc);
+        assert_eq!(JtlDocument::new().version(), None);
+    }
+
+    #[test]
+    fn test_annotations() {
+        let text = r#"DOCTYPE=JTL
+>>>BEGIN;
+>//> @deprecated @owner=team-x
+>port="80">web>nginx;
+>//> not an annotation
+>port="5432">db>postgres;
+>>>END;"#;
+        let doc = JtlDocument::parse(text).unwrap();
+        assert_eq!(doc.elements[0].annotation("deprecated"), Some(""));
+        assert_eq!(doc.elements[0].annotation("owner"), Some("team-x"));
+        assert!(doc.elements[1].annotations.is_empty());
+
+        let written = String::from_utf8(doc.write_to(Vec::new()).unwrap()).unwrap();
+        assert!(written.contains(">//> @deprecated @owner=team-x\n>port=\"80\">web>nginx;"));
+        assert_eq!(JtlDocument::parse(&written).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_write_preserving() {
+        let text = "\u{feff}DOCTYPE=JTL\r\n>>>BEGIN;\r\n>a=\"1\">k>v;\r\n>>>END;\r\n";
+        let mut doc = JtlDocument::parse(text).unwrap();
+        assert!(doc.text_format().bom);
+        let written = doc.write_preserving(Vec::new()).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), text);
+
+        doc.set_text_format(TextFormat::default());
+        let written = String::from_utf8(doc.write_preserving(Vec::new()).unwrap()).unwrap();
+        assert_eq!(written, text.replace("\r\n", "\n").replace('\u{feff}', ""));
+        assert_eq!(JtlDocument::parse(&written).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_scoped_env() {
+        let mut doc = JtlDocument::parse(
+            r#"DOCTYPE=JTL
+>>>ENV;
+>>>db.host=localhost;
+>>>db.replica.host=backup;
+>>>dbx=other;
+>>>BEGIN;
+>n="1">k>$env:db.host;
+>>>END;"#,
+        )
+        .unwrap();
+        assert_eq!(doc.elements[0].content, "localhost");
+        let db = doc.scoped_env("db");
+        assert_eq!(db.len(), 2);
+        assert_eq!(db["replica.host"], "backup");
+        assert_eq!(doc.scoped_env("db.replica")["host"], "backup");
+
+        doc.set_scoped_env("cache", [("ttl", "60"), ("size", "10")]);
+        let text = doc.to_jtl().unwrap();
+        assert!(text.contains(">>>cache.size=10;\n>>>cache.ttl=60;\n>>>db.host=localhost;"));
+        assert_eq!(
+            JtlDocument::parse(&text).unwrap().scoped_env("cache").len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_iterators() {
+        let doc = JtlDocument::parse(
+            r#"DOCTYPE=JTL
+>>>BEGIN;
+>type="a" n="1">first>one;
+>n="2">second>two;
+>type="b">third>three;
+>>>END;"#,
+        )
+        .unwrap();
+        assert!(doc.keys().eq(["first", "second", "third"]));
+        assert!(doc.contents().eq(["one", "two", "three"]));
+        assert!(doc.attr_values("type").eq(["a", "b"]));
+        assert_eq!(doc.attrs().nth(1), Some(("n", "1")));
+        assert_eq!(doc.attrs().count(), 4);
+    }
+
+    #[test]
+    fn test_save_atomic() {
+        let path = temp_path("save.jtl");
+        fs::write(&path, "old contents").unwrap();
+
+        let mut doc = JtlDocument::new();
+        doc.env.insert("foo".to_string(), "bar".to_string());
+        doc.elements
+            .push(JtlElement::new("id", "$env:foo").with_attr("key", "value"));
+        doc.save_atomic(&path).unwrap();
+
+        let parsed = JtlDocument::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(parsed.elements.len(), 1);
+        assert_eq!(parsed.elements[0].content, "bar");
+
+        let leftovers = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(".save.jtl")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn test_save_atomic_keeps_target_on_error() {
+        let path = temp_path("save-invalid.jtl");
+        fs::write(&path, "old contents").unwrap();
+
+        let mut doc = JtlDocument::new();
+        doc.elements.push(JtlElement::new("id", "no attributes"));
+        assert!(doc.save_atomic(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old contents");
+    }
+
+    #[test]
+    fn test_empty_skeleton() {
+        let path = temp_path("skeleton.jtl");
+        JtlDocument::with_env([("host", "localhost")])
+            .save_atomic(&path)
+            .unwrap();
+        let skeleton = JtlDocument::load(&path).unwrap();
+        assert_eq!(skeleton.env["host"], "localhost");
+        assert!(skeleton.elements.is_empty());
+
+        let web = JtlElement::new("web", "nginx").with_attr("port", "80");
+        crate::append_to_file(&path, std::slice::from_ref(&web)).unwrap();
+        assert_eq!(JtlDocument::load(&path).unwrap().elements, [web]);
+        assert_eq!(
+            JtlDocument::parse(&JtlDocument::empty().to_jtl().unwrap()).unwrap(),
+            JtlDocument::empty()
+        );
+        assert_eq!(JtlDocument::empty().version(), None);
+    }
+
+    #[test]
+    fn test_write_file() {
+        let path = temp_path("write_file.jtl");
+        fs::write(
+            &path,
+            "DOCTYPE=JTL\r\n>>>BEGIN;\r\n>a=\"1\">old>x;\r\n>>>END;\r\n",
+        )
+        .unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        let mut document = JtlDocument::load(&path).unwrap();
+        document.elements[0].key = "new".to_string();
+        document.write_file(&path).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.contains(">a=\"1\">new>x;\r\n"), "{:?}", text);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let write_error = document.write_file(path.join("not-a-dir"));
+        assert!(write_error.is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+    }
+
+    #[test]
+    fn test_display() {
+        let document = JtlDocument::parse(
+            "DOCTYPE=JTL\n>>>ENV;\n>>>host=db;\n>>>BEGIN;\n>//> @primary\n\
+             >a=\"1\" b=\"2\">web>$env:host;\n>>>END;",
+        )
+        .unwrap();
+        assert_eq!(document.to_string(), document.to_jtl().unwrap());
+        assert_eq!(
+            JtlDocument::empty().to_string(),
+            JtlDocument::empty().to_jtl().unwrap()
+        );
+        assert_eq!(
+            format!("{}", document.elements[0]),
+            ">a=\"1\" b=\"2\">web>db;"
+        );
+    }
+}
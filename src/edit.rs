@@ -0,0 +1,598 @@
+use crate::document::JtlDocument;
+use crate::element::JtlElement;
+use crate::schema::{Schema, Violation};
+use std::ops::Deref;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// A change made through the mutation methods of [`JtlDocument`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ChangeEvent {
+    /// An element was inserted at `index`.
+    ElementAdded { index: usize, element: JtlElement },
+    /// The element at `index` was removed.
+    ElementRemoved { index: usize, element: JtlElement },
+    /// The element at `index` was replaced.
+    ElementReplaced {
+        index: usize,
+        old: JtlElement,
+        new: JtlElement,
+    },
+    /// An env variable was set (`new` is `Some`) or removed (`new` is `None`).
+    EnvChanged {
+        name: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+impl ChangeEvent {
+    /// Returns the event that undoes this one.
+    fn inverse(&self) -> ChangeEvent {
+        match self.clone() {
+            ChangeEvent::ElementAdded { index, element } => {
+                ChangeEvent::ElementRemoved { index, element }
+            }
+            ChangeEvent::ElementRemoved { index, element } => {
+                ChangeEvent::ElementAdded { index, element }
+            }
+            ChangeEvent::ElementReplaced { index, old, new } => ChangeEvent::ElementReplaced {
+                index,
+                old: new,
+                new: old,
+            },
+            ChangeEvent::EnvChanged { name, old, new } => ChangeEvent::EnvChanged {
+                name,
+                old: new,
+                new: old,
+            },
+        }
+    }
+}
+
+/// Editing state of a document: subscriptions, undo history, and attached schema.
+///
+/// None of it is document content, so it never affects equality. Clones keep the
+/// history and schema but start without subscribers.
+#[derive(Debug, Default)]
+pub(crate) struct EditState {
+    subscribers: Vec<Sender<ChangeEvent>>,
+    undo: Vec<Vec<ChangeEvent>>,
+    redo: Vec<Vec<ChangeEvent>>,
+    schema: Option<Schema>,
+}
+
+impl EditState {
+    fn notify(&mut self, event: &ChangeEvent) {
+        // Drop subscribers whose receiver has gone away.
+        self.subscribers
+            .retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
+
+impl Clone for EditState {
+    fn clone(&self) -> Self {
+        EditState {
+            subscribers: Vec::new(),
+            undo: self.undo.clone(),
+            redo: self.redo.clone(),
+            schema: self.schema.clone(),
+        }
+    }
+}
+
+impl PartialEq for EditState {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for EditState {}
+
+impl JtlDocument {
+    /// Returns a channel receiving every change made through the mutation methods,
+    /// including the changes made by [`undo`](JtlDocument::undo) and
+    /// [`redo`](JtlDocument::redo).
+    ///
+    /// Edits made directly to the public fields are not reported.
+    pub fn subscribe(&mut self) -> Receiver<ChangeEvent> {
+        let (sender, receiver) = mpsc::channel();
+        self.edit.subscribers.push(sender);
+        receiver
+    }
+
+    /// Appends an element to the body.
+    ///
+    /// Fails if the element breaks the attached schema.
+    pub fn push_element(&mut self, element: JtlElement) -> Result<(), Vec<Violation>> {
+        self.insert_element(self.elements.len(), element)
+    }
+
+    /// Inserts an element at `index`.
+    ///
+    /// Fails if the element breaks the attached schema.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of elements.
+    pub fn insert_element(
+        &mut self,
+        index: usize,
+        element: JtlElement,
+    ) -> Result<(), Vec<Violation>> {
+        self.guard(index, &element, false)?;
+        self.insert_unchecked(index, element);
+        Ok(())
+    }
+
+    /// Removes and returns the element at `index`, if there is one.
+    pub fn remove_element(&mut self, index: usize) -> Option<JtlElement> {
+        let element = self.elements.get(index)?.clone();
+        self.record(vec![ChangeEvent::ElementRemoved {
+            index,
+            element: element.clone(),
+        }]);
+        Some(element)
+    }
+
+    /// Replaces the element at `index`, returning the old one.
+    ///
+    /// Fails if the new element breaks the attached schema.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn replace_element(
+        &mut self,
+        index: usize,
+        element: JtlElement,
+    ) -> Result<JtlElement, Vec<Violation>> {
+        assert!(
+            index < self.elements.len(),
+            "replacement index out of bounds"
+        );
+        self.guard(index, &element, true)?;
+        Ok(self.replace_unchecked(index, element))
+    }
+
+    /// Sets an env variable, returning its previous value.
+    pub fn set_env(&mut self, name: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        let name = name.into();
+        let old = self.env.get(&name).cloned();
+        self.record(vec![ChangeEvent::EnvChanged {
+            name,
+            old: old.clone(),
+            new: Some(value.into()),
+        }]);
+        old
+    }
+
+    /// Removes an env variable, returning its value if it was set.
+    pub fn remove_env(&mut self, name: &str) -> Option<String> {
+        let old = self.env.get(name)?.clone();
+        self.record(vec![ChangeEvent::EnvChanged {
+            name: name.to_string(),
+            old: Some(old.clone()),
+            new: None,
+        }]);
+        Some(old)
+    }
+
+    /// Reverts the most recent change. Returns `false` if there was nothing to undo.
+    ///
+    /// A committed [`transaction`](JtlDocument::transaction) is undone as one step.
+    pub fn undo(&mut self) -> bool {
+        let Some(step) = self.edit.undo.pop() else {
+            return false;
+        };
+        for event in step.iter().rev() {
+            self.apply(event.inverse());
+        }
+        self.edit.redo.push(step);
+        true
+    }
+
+    /// Re-applies the most recently undone change. Returns `false` if there was
+    /// nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(step) = self.edit.redo.pop() else {
+            return false;
+        };
+        for event in &step {
+            self.apply(event.clone());
+        }
+        self.edit.undo.push(step);
+        true
+    }
+
+    /// Returns whether [`undo`](JtlDocument::undo) would change anything.
+    pub fn can_undo(&self) -> bool {
+        !self.edit.undo.is_empty()
+    }
+
+    /// Returns whether [`redo`](JtlDocument::redo) would change anything.
+    pub fn can_redo(&self) -> bool {
+        !self.edit.redo.is_empty()
+    }
+
+    /// Forgets all undo and redo steps.
+    pub fn clear_history(&mut self) {
+        self.edit.undo.clear();
+        self.edit.redo.clear();
+    }
+
+    /// Attaches a schema, replacing any previous one.
+    ///
+    /// From then on, elements added or replaced through the mutation methods must
+    /// satisfy it. Elements already in the document are not re-checked; use
+    /// [`Schema::validate`] for that.
+    pub fn set_schema(&mut self, schema: Schema) {
+        self.edit.schema = Some(schema);
+    }
+
+    /// Detaches and returns the schema, if one was attached.
+    pub fn take_schema(&mut self) -> Option<Schema> {
+        self.edit.schema.take()
+    }
+
+    /// Returns the attached schema.
+    pub fn schema(&self) -> Option<&Schema> {
+        self.edit.schema.as_ref()
+    }
+
+    /// Runs `edit` against a staged copy of the document and commits its changes
+    /// only if the result passes the attached schema.
+    ///
+    /// On success the changes are applied as a single undo step and reported to
+    /// subscribers; otherwise the document is left untouched and the violations
+    /// are returned.
+    pub fn transaction<R>(
+        &mut self,
+        edit: impl FnOnce(&mut Transaction) -> R,
+    ) -> Result<R, Vec<Violation>> {
+        let mut staged = JtlDocument {
+            env: self.env.clone(),
+            elements: self.elements.clone(),
+            ..JtlDocument::default()
+        };
+        let mut tx = Transaction {
+            staged: &mut staged,
+            changes: Vec::new(),
+        };
+        let result = edit(&mut tx);
+        let changes = tx.changes;
+
+        if let Some(schema) = &self.edit.schema {
+            let violations = schema.validate(&staged);
+            if !violations.is_empty() {
+                return Err(violations);
+            }
+        }
+        if !changes.is_empty() {
+            self.record(changes);
+        }
+        Ok(result)
+    }
+
+    fn guard(
+        &self,
+        index: usize,
+        element: &JtlElement,
+        replaces: bool,
+    ) -> Result<(), Vec<Violation>> {
+        match &self.edit.schema {
+            Some(schema) => {
+                let violations = schema.validate_placed(self, index, element, replaces);
+                if violations.is_empty() {
+                    Ok(())
+                } else {
+                    Err(violations)
+                }
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn insert_unchecked(&mut self, index: usize, element: JtlElement) {
+        assert!(
+            index <= self.elements.len(),
+            "insertion index out of bounds"
+        );
+        self.record(vec![ChangeEvent::ElementAdded { index, element }]);
+    }
+
+    fn replace_unchecked(&mut self, index: usize, element: JtlElement) -> JtlElement {
+        let old = self.elements[index].clone();
+        self.record(vec![ChangeEvent::ElementReplaced {
+            index,
+            old: old.clone(),
+            new: element,
+        }]);
+        old
+    }
+
+    /// Applies a new undo step.
+    fn record(&mut self, step: Vec<ChangeEvent>) {
+        for event in &step {
+            self.apply(event.clone());
+        }
+        self.edit.undo.push(step);
+        self.edit.redo.clear();
+    }
+
+    fn apply(&mut self, event: ChangeEvent) {
+        match &event {
+            ChangeEvent::ElementAdded { index, element } => {
+                self.elements.insert(*index, element.clone())
+            }
+            ChangeEvent::ElementRemoved { index, .. } => {
+                self.elements.remove(*index);
+            }
+            ChangeEvent::ElementReplaced { index, new, .. } => self.elements[*index] = new.clone(),
+            ChangeEvent::EnvChanged { name, new, .. } => match new {
+                Some(value) => {
+                    self.env.insert(name.clone(), value.clone());
+                }
+                None => {
+                    self.env.remove(name);
+                }
+            },
+        }
+        self.edit.notify(&event);
+    }
+}
+
+/// Staged edits inside [`JtlDocument::transaction`].
+///
+/// Edits are not checked against the schema one by one, so intermediate states
+/// may be invalid; the schema is checked once, at commit. Dereferences to the
+/// staged document, so reads see the edits made so far.
+#[derive(Debug)]
+pub struct Transaction<'a> {
+    staged: &'a mut JtlDocument,
+    changes: Vec<ChangeEvent>,
+}
+
+impl Transaction<'_> {
+    /// Appends an element to the body.
+    pub fn push_element(&mut self, element: JtlElement) {
+        self.insert_element(self.staged.elements.len(), element);
+    }
+
+    /// Inserts an element at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the number of elements.
+    pub fn insert_element(&mut self, index: usize, element: JtlElement) {
+        self.staged.insert_unchecked(index, element);
+        self.take_change();
+    }
+
+    /// Removes and returns the element at `index`, if there is one.
+    pub fn remove_element(&mut self, index: usize) -> Option<JtlElement> {
+        let removed = self.staged.remove_element(index)?;
+        self.take_change();
+        Some(removed)
+    }
+
+    /// Replaces the element at `index`, returning the old one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn replace_element(&mut self, index: usize, element: JtlElement) -> JtlElement {
+        let old = self.staged.replace_unchecked(index, element);
+        self.take_change();
+        old
+    }
+
+    /// Sets an env variable, returning its previous value.
+    pub fn set_env(&mut self, name: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        let old = self.staged.set_env(name, value);
+        self.take_change();
+        old
+    }
+
+    /// Removes an env variable, returning its value if it was set.
+    pub fn remove_env(&mut self, name: &str) -> Option<String> {
+        let old = self.staged.remove_env(name)?;
+        self.take_change();
+        Some(old)
+    }
+
+    fn take_change(&mut self) {
+        if let Some(step) = self.staged.edit.undo.pop() {
+            self.changes.extend(step);
+        }
+    }
+}
+
+impl Deref for Transaction<'_> {
+    type Target = JtlDocument;
+
+    fn deref(&self) -> &JtlDocument {
+        self.staged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscribe() {
+        let mut doc = JtlDocument::new();
+        let events = doc.subscribe();
+
+        doc.push_element(JtlElement::new("a", "one").with_attr("n", "1"))
+            .unwrap();
+        doc.set_env("foo", "bar");
+        doc.remove_env("foo");
+        assert!(doc.remove_env("missing").is_none());
+        let removed = doc.remove_element(0).unwrap();
+
+        let received: Vec<ChangeEvent> = events.try_iter().collect();
+        assert_eq!(
+            received,
+            vec![
+                ChangeEvent::ElementAdded {
+                    index: 0,
+                    element: removed.clone()
+                },
+                ChangeEvent::EnvChanged {
+                    name: "foo".to_string(),
+                    old: None,
+                    new: Some("bar".to_string())
+                },
+                ChangeEvent::EnvChanged {
+                    name: "foo".to_string(),
+                    old: Some("bar".to_string()),
+                    new: None
+                },
+                ChangeEvent::ElementRemoved {
+                    index: 0,
+                    element: removed
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dropped_subscriber() {
+        let mut doc = JtlDocument::new();
+        drop(doc.subscribe());
+        let live = doc.subscribe();
+        doc.set_env("foo", "bar");
+        assert_eq!(doc.edit.subscribers.len(), 1);
+        assert_eq!(live.try_iter().count(), 1);
+        assert!(doc.clone().edit.subscribers.is_empty());
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut doc = JtlDocument::new();
+        doc.push_element(JtlElement::new("a", "one").with_attr("n", "1"))
+            .unwrap();
+        doc.push_element(JtlElement::new("b", "two").with_attr("n", "2"))
+            .unwrap();
+        doc.replace_element(0, JtlElement::new("a", "uno").with_attr("n", "1"))
+            .unwrap();
+        doc.set_env("foo", "bar");
+        doc.set_env("foo", "baz");
+        doc.remove_element(1);
+        let edited = doc.clone();
+
+        let events = doc.subscribe();
+        while doc.undo() {}
+        assert!(doc.elements.is_empty());
+        assert!(doc.env.is_empty());
+        assert_eq!(events.try_iter().count(), 6);
+
+        assert!(doc.redo());
+        assert_eq!(doc.elements[0].content, "one");
+        while doc.redo() {}
+        assert_eq!(doc, edited);
+        assert_eq!(doc.env["foo"], "baz");
+
+        doc.undo();
+        doc.set_env("other", "x");
+        assert!(!doc.can_redo());
+        assert_eq!(doc.elements.len(), 2);
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut doc = JtlDocument::new();
+        doc.set_schema(Schema::new().require("type"));
+        let events = doc.subscribe();
+
+        let result = doc.transaction(|tx| {
+            tx.push_element(JtlElement::new("a", "one").with_attr("type", "user"));
+            tx.push_element(JtlElement::new("b", "two").with_attr("type", "user"));
+            tx.set_env("foo", "bar");
+            tx.elements.len()
+        });
+        assert_eq!(result, Ok(2));
+        assert_eq!(doc.elements.len(), 2);
+        assert_eq!(events.try_iter().count(), 3);
+
+        let violations = doc
+            .transaction(|tx| {
+                tx.remove_element(0);
+                tx.push_element(JtlElement::new("c", "three").with_attr("kind", "user"));
+            })
+            .unwrap_err();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].key, "c");
+        assert_eq!(doc.elements[0].key, "a");
+        assert_eq!(doc.elements.len(), 2);
+        assert_eq!(events.try_iter().count(), 0);
+
+        assert!(doc.undo());
+        assert!(doc.elements.is_empty());
+        assert!(doc.env.is_empty());
+    }
+
+    #[test]
+    fn test_schema_guards() {
+        let mut doc = JtlDocument::new();
+        doc.push_element(JtlElement::new("legacy", "x").with_attr("kind", "old"))
+            .unwrap();
+        doc.set_schema(
+            Schema::new()
+                .require("type")
+                .one_of("type", &["user", "group"]),
+        );
+        let events = doc.subscribe();
+
+        let violations = doc
+            .push_element(JtlElement::new("a", "x").with_attr("type", "robot"))
+            .unwrap_err();
+        assert_eq!(violations[0].index, 1);
+        assert!(violations[0]
+            .message
+            .contains("expected one of user, group"));
+        assert!(doc
+            .replace_element(0, JtlElement::new("legacy", "y").with_attr("kind", "new"))
+            .is_err());
+        assert_eq!(doc.elements.len(), 1);
+        assert_eq!(events.try_iter().count(), 0);
+
+        doc.insert_element(0, JtlElement::new("a", "x").with_attr("type", "user"))
+            .unwrap();
+        assert_eq!(doc.elements[0].key, "a");
+        assert_eq!(doc.remove_element(1).unwrap().key, "legacy");
+    }
+
+    #[test]
+    fn test_schema_guards_unique_keys() {
+        let mut doc = JtlDocument::new();
+        doc.set_schema(Schema::new().unique_keys());
+        doc.push_element(JtlElement::new("a", "1").with_attr("n", "1"))
+            .unwrap();
+        doc.push_element(JtlElement::new("b", "2").with_attr("n", "2"))
+            .unwrap();
+
+        let violations = doc
+            .push_element(JtlElement::new("a", "3").with_attr("n", "3"))
+            .unwrap_err();
+        assert_eq!(
+            violations[0].message,
+            "duplicate key, also used by element 0"
+        );
+        let violations = doc
+            .insert_element(0, JtlElement::new("b", "3").with_attr("n", "3"))
+            .unwrap_err();
+        assert_eq!(
+            violations[0].message,
+            "duplicate key, also used by element 2"
+        );
+        assert!(doc
+            .replace_element(1, JtlElement::new("a", "3").with_attr("n", "3"))
+            .is_err());
+        doc.replace_element(1, JtlElement::new("b", "3").with_attr("n", "3"))
+            .unwrap();
+        assert_eq!(doc.elements.len(), 2);
+    }
+}
@@ -0,0 +1,331 @@
+#[cfg(feature = "json")]
+use serde_json::Value;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::digest::{hex, sha256};
+
+/// A single element of a JTL document.
+///
+/// Equality and hashing use the element's canonical form: its key, content
+/// and attributes, with attributes compared regardless of order. Annotations
+/// and [`origin`](JtlElement::origin) are metadata and are not compared.
+#[derive(Debug, Clone, Default)]
+pub struct JtlElement {
+    /// The element's identifier, written between the attributes and the content.
+    pub key: String,
+    /// Attributes in source order.
+    pub attrs: Vec<(String, String)>,
+    /// The element's content.
+    pub content: String,
+    /// Annotations from `>//> @name` and `>//> @name=value` comment lines
+    /// directly above the element, in source order. Flags have an empty value.
+    pub annotations: Vec<(String, String)>,
+    /// The 1-based line the element was declared on, when it was parsed from
+    /// a whole document. Used in error messages.
+    pub line: Option<usize>,
+    /// The file the element was read from, when known.
+    pub source: Option<Arc<Path>>,
+}
+
+impl JtlElement {
+    /// Creates an element with no attributes.
+    pub fn new(key: impl Into<String>, content: impl Into<String>) -> Self {
+        JtlElement {
+            key: key.into(),
+            attrs: Vec::new(),
+            content: content.into(),
+            annotations: Vec::new(),
+            line: None,
+            source: None,
+        }
+    }
+
+    /// Appends an attribute.
+    pub fn with_attr(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attrs.push((name.into(), value.into()));
+        self
+    }
+
+    /// Returns the element's key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the element's content.
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    /// Returns the attributes as `(name, value)` pairs, in source order.
+    pub fn attrs(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        self.attrs
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Returns the value of the attribute named `name`, if any. When the
+    /// name is repeated the last value wins, as in schemas, encoders and
+    /// [`to_value`](JtlElement::to_value).
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .rev()
+            .find(|(attr, _)| attr == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the items of attribute `name` if it is written as a list,
+    /// `[a, b, c]`.
+    pub fn attr_list(&self, name: &str) -> Option<Vec<&str>> {
+        self.attr(name).and_then(crate::list::parse_list)
+    }
+
+    /// Appends an attribute holding a list, written as `[a, b, c]`.
+    ///
+    /// Fails for items that would read back differently: empty ones, ones with
+    /// surrounding whitespace, and ones holding `,`, `[`, `]` or `"`.
+    pub fn with_attr_list<S: AsRef<str>>(
+        self,
+        name: impl Into<String>,
+        items: &[S],
+    ) -> Result<Self, String> {
+        let value = crate::list::format_list(items)?;
+        Ok(self.with_attr(name, value))
+    }
+
+    /// Appends an annotation. Use an empty value for a flag such as `@deprecated`.
+    pub fn with_annotation(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.annotations.push((name.into(), value.into()));
+        self
+    }
+
+    /// Returns the value of annotation `name`, or `""` for a flag, if present.
+    pub fn annotation(&self, name: &str) -> Option<&str> {
+        self.annotations
+            .iter()
+            .find(|(annotation, _)| annotation == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns a content-addressed ID: the SHA-256 of the canonical form, as
+    /// lowercase hex.
+    ///
+    /// Elements that are equal have the same digest, so it can be used to
+    /// deduplicate elements or track them across versions of a document.
+    pub fn digest(&self) -> String {
+        let mut bytes = Vec::new();
+        let mut field = |text: &str| {
+            bytes.extend_from_slice(&(text.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(text.as_bytes());
+        };
+        field(&self.key);
+        field(&self.content);
+        for (name, value) in self.canonical_attrs() {
+            field(name);
+            field(value);
+        }
+        hex(&sha256(&bytes))
+    }
+
+    /// Returns the attributes sorted by name and then value.
+    fn canonical_attrs(&self) -> Vec<(&str, &str)> {
+        let mut attrs: Vec<(&str, &str)> = self
+            .attrs
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        attrs.sort_unstable();
+        attrs
+    }
+
+    /// Reads an element from the JSON object shape produced by [`parse`](crate::parse).
+    ///
+    /// `KEY` and `Content` (or `Contents`) are required; every other field
+    /// becomes an attribute, in the object's order. Numbers and booleans are
+    /// written as text, and arrays as lists. Also available as `TryFrom`.
+    #[cfg(feature = "json")]
+    pub fn from_value(value: &Value) -> Result<Self, String> {
+        let map = value
+            .as_object()
+            .ok_or("invalid element: expected a JSON object")?;
+        let text = |name: &str, value: &Value| match value {
+            Value::String(text) => Ok(text.clone()),
+            Value::Number(number) => Ok(number.to_string()),
+            Value::Bool(flag) => Ok(flag.to_string()),
+            _ => Err(format!("invalid element: field `{}` is not a string", name)),
+        };
+        let field = |name: &str| map.get(name).map(|value| text(name, value)).transpose();
+        let key = field("KEY")?.ok_or("invalid element: missing `KEY`")?;
+        let content = match field("Content")? {
+            Some(content) => content,
+            None => field("Contents")?
+                .ok_or_else(|| format!("invalid element `{}`: missing `Content`", key))?,
+        };
+        let mut element = JtlElement::new(key, content);
+        for (name, value) in map {
+            if matches!(name.as_str(), "KEY" | "Content" | "Contents") {
+                continue;
+            }
+            let value = match value {
+                Value::Array(items) => {
+                    let items = items
+                        .iter()
+                        .map(|item| text(name, item))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    crate::list::format_list(&items).map_err(|err| {
+                        format!(
+                            "invalid element `{}`: attribute `{}`: {}",
+                            element.key, name, err
+                        )
+                    })?
+                }
+                value => text(name, value)?,
+            };
+            element.attrs.push((name.clone(), value));
+        }
+        Ok(element)
+    }
+
+    /// Converts the element to the JSON object shape produced by
+    /// [`parse`](crate::parse), with attribute values as strings. Also
+    /// available as `From`.
+    #[cfg(feature = "json")]
+    pub fn to_value(&self) -> Value {
+        self.to_jtl_value().into()
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<&JtlElement> for Value {
+    fn from(element: &JtlElement) -> Self {
+        element.to_value()
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<JtlElement> for Value {
+    fn from(element: JtlElement) -> Self {
+        element.to_value()
+    }
+}
+
+#[cfg(feature = "json")]
+impl TryFrom<&Value> for JtlElement {
+    type Error = String;
+
+    fn try_from(value: &Value) -> Result<Self, String> {
+        JtlElement::from_value(value)
+    }
+}
+
+#[cfg(feature = "json")]
+impl TryFrom<Value> for JtlElement {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, String> {
+        JtlElement::from_value(&value)
+    }
+}
+
+/// Formats the element as a single JTL line, `>attrs>KEY>content;`, without
+/// annotations.
+///
+/// Elements that are not valid JTL, such as ones without attributes, are
+/// formatted as is and will not parse back; [`JtlWriter`](crate::JtlWriter)
+/// rejects them instead.
+impl fmt::Display for JtlElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(">")?;
+        for (index, (name, value)) in self.attrs.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}=\"{}\"", name, value)?;
+        }
+        write!(f, ">{}>{};", self.key, self.content)
+    }
+}
+
+impl PartialEq for JtlElement {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+            && self.content == other.content
+            && self.attrs.len() == other.attrs.len()
+            && self.canonical_attrs() == other.canonical_attrs()
+    }
+}
+
+impl Eq for JtlElement {}
+
+impl Hash for JtlElement {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+        self.content.hash(state);
+        self.canonical_attrs().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_canonical_equality() {
+        let a = JtlElement::new("web", "nginx")
+            .with_attr("port", "80")
+            .with_attr("host", "a");
+        let b = JtlElement::new("web", "nginx")
+            .with_attr("host", "a")
+            .with_attr("port", "80")
+            .with_annotation("owner", "ops");
+        assert_eq!(a, b);
+        assert_eq!(a.digest(), b.digest());
+        assert_eq!(HashSet::from([a.clone(), b]).len(), 1);
+
+        let c = a.clone().with_attr("port", "81");
+        assert_ne!(a, c);
+        assert_ne!(a.digest(), c.digest());
+        assert_ne!(
+            JtlElement::new("ab", "c").digest(),
+            JtlElement::new("a", "bc").digest()
+        );
+        assert_eq!(a.digest().len(), 64);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_accessors_and_value_conversions() {
+        let parsed =
+            crate::parse("DOCTYPE=JTL\n>>>BEGIN;\n>port=\"80\" tls=\"on\">web>nginx;\n>>>END;")
+                .unwrap();
+        let web = JtlElement::try_from(&parsed[0]).unwrap();
+        assert_eq!(web.key(), "web");
+        assert_eq!(web.content(), "nginx");
+        assert_eq!(
+            web.attrs().collect::<Vec<_>>(),
+            [("port", "80"), ("tls", "on")]
+        );
+        assert_eq!(Value::from(&web), parsed[0]);
+        assert_eq!(JtlElement::try_from(Value::from(web.clone())).unwrap(), web);
+        assert_eq!(
+            JtlElement::try_from(serde_json::json!({ "KEY": "x" })).unwrap_err(),
+            "invalid element `x`: missing `Content`"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_repeated_attr_last_wins() {
+        let parsed = crate::parse_document(
+            "DOCTYPE=JTL\n>>>BEGIN;\n>port=\"80\" port=\"8080\">web>nginx;\n>>>END;",
+        )
+        .unwrap();
+        let web = &parsed.elements[0];
+        assert_eq!(web.attr("port"), Some("8080"));
+        assert_eq!(web.to_value()["port"], "8080");
+    }
+}
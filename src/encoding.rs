@@ -0,0 +1,66 @@
+/// The line ending used between lines of JTL text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Newline {
+    /// `\n`.
+    #[default]
+    Lf,
+    /// `\r\n`.
+    CrLf,
+}
+
+impl Newline {
+    /// Returns the characters ending each line.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        }
+    }
+}
+
+/// How JTL text is laid out on disk, beyond its content.
+///
+/// Documents remember the format they were parsed from, so an edited file can
+/// be written back with [`JtlDocument::write_preserving`](crate::JtlDocument::write_preserving)
+/// without turning every line into a diff.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TextFormat {
+    /// The line ending.
+    pub newline: Newline,
+    /// Whether the text starts with a UTF-8 byte order mark.
+    pub bom: bool,
+}
+
+impl TextFormat {
+    /// Detects the format of `text` from its first line ending and leading BOM.
+    pub fn detect(text: &str) -> TextFormat {
+        let newline = match text.find('\n') {
+            Some(index) if text[..index].ends_with('\r') => Newline::CrLf,
+            _ => Newline::Lf,
+        };
+        TextFormat {
+            newline,
+            bom: text.starts_with(BOM),
+        }
+    }
+}
+
+/// The byte order mark, as a character.
+pub(crate) const BOM: char = '\u{feff}';
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(TextFormat::detect("DOCTYPE=JTL\n"), TextFormat::default());
+        assert_eq!(
+            TextFormat::detect("\u{feff}DOCTYPE=JTL\r\n>>>BEGIN;\n"),
+            TextFormat {
+                newline: Newline::CrLf,
+                bom: true
+            }
+        );
+    }
+}
@@ -0,0 +1,3 @@
+#[cfg(feature = "json")]
+pub use crate::parse_env;
+pub use crate::{env_references, missing_env, redact, render, rewrite_env, Origin, RedactRules};
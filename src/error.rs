@@ -0,0 +1,151 @@
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// An error found while parsing, with the line it was found on when known.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JtlError {
+    message: String,
+    line: Option<usize>,
+}
+
+impl JtlError {
+    pub(crate) fn new(message: impl fmt::Display, line: Option<usize>) -> Self {
+        JtlError {
+            message: message.to_string(),
+            line,
+        }
+    }
+
+    /// Returns the 1-based line the error was found on, if known.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    /// Returns the error message, as displayed.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for JtlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for JtlError {}
+
+/// An error reading or parsing a file, with the file's path and, for parse
+/// errors, the line.
+///
+/// The underlying [`io::Error`](std::io::Error) or [`JtlError`] is its
+/// [`source`](Error::source).
+#[derive(Debug)]
+pub struct FileError {
+    path: PathBuf,
+    line: Option<usize>,
+    source: Box<dyn Error + Send + Sync>,
+}
+
+impl FileError {
+    pub(crate) fn new(path: &Path, source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        let source = source.into();
+        FileError {
+            path: path.to_path_buf(),
+            line: source.downcast_ref::<JtlError>().and_then(JtlError::line),
+            source,
+        }
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the 1-based line the error was found on, if known.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+}
+
+/// Formats the error as `path:line: message`, leaving out the line when it is
+/// not known.
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        write!(f, ": {}", self.source)
+    }
+}
+
+impl Error for FileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// Makes a parse error thread-safe, for keeping in a [`FileError`]. A
+/// [`JtlError`] or [`io::Error`](std::io::Error) is kept as it is; anything
+/// else is kept as its message.
+pub(crate) fn sendable(err: Box<dyn Error>) -> Box<dyn Error + Send + Sync> {
+    match err.downcast::<JtlError>() {
+        Ok(err) => err,
+        Err(err) => match err.downcast::<std::io::Error>() {
+            Ok(err) => err,
+            Err(err) => err.to_string().into(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::tests::temp_path;
+    use crate::JtlDocument;
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_parse_file_errors() {
+        let path = temp_path("parse_file.jtl");
+        std::fs::write(
+            &path,
+            "DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">a>b;\n>ab;\n>>>END;\n",
+        )
+        .unwrap();
+        let err = crate::parse_file(&path).unwrap_err();
+        assert_eq!(err.path(), path);
+        assert_eq!(err.line(), Some(4));
+        assert_eq!(
+            err.to_string(),
+            format!("{}:4: invalid element format: too short", path.display())
+        );
+        assert!(err.source().unwrap().is::<JtlError>());
+
+        std::fs::write(&path, "DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">a>b;\n>>>END;\n").unwrap();
+        assert_eq!(crate::parse_file(&path).unwrap()[0]["KEY"], "a");
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let path = temp_path("load.jtl");
+        std::fs::write(&path, "DOCTYPE=JTL\n>>>BEGIN;\n>ab;\n>>>END;\n").unwrap();
+        let err = JtlDocument::load(&path).unwrap_err();
+        let err = err.downcast_ref::<FileError>().unwrap();
+        assert_eq!(err.line(), Some(3));
+        assert!(err.source().unwrap().is::<JtlError>());
+        fn send_sync<T: Send + Sync + 'static>(_: &T) {}
+        send_sync(err);
+
+        let missing = temp_path("missing.jtl");
+        let err = JtlDocument::load(&missing).unwrap_err();
+        let err = err.downcast_ref::<FileError>().unwrap();
+        assert_eq!(err.line(), None);
+        assert!(err.source().unwrap().is::<std::io::Error>());
+        assert!(err
+            .to_string()
+            .starts_with(&format!("{}: ", missing.display())));
+    }
+}
@@ -0,0 +1,182 @@
+use regex::Regex;
+use std::fmt;
+
+/// A step of the element grammar `>attrs>KEY>content;`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Stage {
+    /// The leading `>`.
+    Prefix,
+    /// The `name="value"` pairs before the first separator.
+    Attributes,
+    /// The `>` between the attributes, key and content.
+    Separator,
+    /// The key and the content.
+    Content,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Prefix => "prefix",
+            Stage::Attributes => "attributes",
+            Stage::Separator => "separator",
+            Stage::Content => "content",
+        })
+    }
+}
+
+/// Why an element declaration does not parse, from [`explain`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// The stage that failed.
+    pub stage: Stage,
+    /// Byte offset into the declaration where the problem is.
+    pub offset: usize,
+    /// What was expected there.
+    pub message: String,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at byte {}: {}",
+            self.stage, self.offset, self.message
+        )
+    }
+}
+
+/// Walks an element declaration through the grammar one stage at a time and
+/// reports the first one that fails, or `None` if the declaration parses.
+///
+/// This accepts and rejects exactly what the parser does; it only says more
+/// about why.
+pub fn explain(decl: &str) -> Option<Explanation> {
+    let fail = |stage, offset, message: &str| {
+        Some(Explanation {
+            stage,
+            offset,
+            message: message.to_string(),
+        })
+    };
+    if !decl.starts_with('>') {
+        return fail(Stage::Prefix, 0, "expected `>` to start an element");
+    }
+    if decl.len() < 5 {
+        return fail(
+            Stage::Content,
+            decl.len(),
+            "element is too short; expected >attrs>KEY>content;",
+        );
+    }
+    let body = &decl[1..];
+    let Some(split) = body.find('>') else {
+        return fail(
+            Stage::Separator,
+            decl.len(),
+            "expected `>` after the attributes",
+        );
+    };
+
+    let attr = Regex::new(r#"(\w+)="([^"]+)""#).expect("valid regex");
+    if !attr.is_match(body) {
+        let section = &body[..split];
+        let (offset, message) = match section.find(|c: char| !c.is_whitespace()) {
+            None => (
+                1,
+                "expected at least one name=\"value\" attribute".to_string(),
+            ),
+            Some(start) => (1 + start, attribute_hint(&section[start..])),
+        };
+        return fail(Stage::Attributes, offset, &message);
+    }
+
+    let start = 1 + split + 1;
+    let rest = decl[start..].strip_suffix(';').unwrap_or(&decl[start..]);
+    let Some(key_len) = rest.find('>') else {
+        return fail(
+            Stage::Separator,
+            start + rest.len(),
+            "expected `>` between the key and the content",
+        );
+    };
+    if key_len == 0 {
+        return fail(Stage::Content, start, "expected a key before `>`");
+    }
+    if key_len + 1 == rest.len() {
+        return fail(
+            Stage::Content,
+            start + rest.len(),
+            "expected content after the key",
+        );
+    }
+    None
+}
+
+/// Describes what is wrong with attribute text the pattern does not match.
+fn attribute_hint(text: &str) -> String {
+    let token: String = text.chars().take_while(|c| !c.is_whitespace()).collect();
+    let hint = if text.starts_with(|c: char| c.is_alphanumeric() || c == '_') {
+        let after = text.trim_start_matches(|c: char| c.is_alphanumeric() || c == '_');
+        if after.starts_with(char::is_whitespace) && after.trim_start().starts_with('=')
+            || after.starts_with("= ")
+        {
+            "spaces around `=` are not allowed"
+        } else if after.starts_with("=\"\"") {
+            "attribute values cannot be empty"
+        } else if after.starts_with("='") {
+            "attribute values need double quotes"
+        } else if after.starts_with("=\"") {
+            "attribute value is missing its closing `\"`"
+        } else {
+            "expected name=\"value\""
+        }
+    } else {
+        "attribute names are letters, digits and `_`"
+    };
+    format!("`{}`: {}", token, hint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain() {
+        let cases = [
+            (">a=\"1\">key>content;", None),
+            ("a=\"1\">key>content;", Some((Stage::Prefix, 0))),
+            (">a>", Some((Stage::Content, 3))),
+            (">a=\"1\" key content", Some((Stage::Separator, 18))),
+            (">>key>content;", Some((Stage::Attributes, 1))),
+            ("> a = \"1\">key>content;", Some((Stage::Attributes, 2))),
+            (">a=\"1\">keycontent;", Some((Stage::Separator, 17))),
+            (">a=\"1\">>content;", Some((Stage::Content, 7))),
+            (">a=\"1\">key>;", Some((Stage::Content, 11))),
+        ];
+        for (decl, expected) in cases {
+            let explanation = explain(decl);
+            assert_eq!(
+                explanation.as_ref().map(|e| (e.stage, e.offset)),
+                expected,
+                "{}",
+                decl
+            );
+            assert_eq!(
+                explanation.is_none(),
+                crate::parse_raw_element(decl).is_ok(),
+                "{}",
+                decl
+            );
+        }
+        assert_eq!(
+            explain("> a = \"1\">key>content;").unwrap().to_string(),
+            "attributes at byte 2: `a`: spaces around `=` are not allowed"
+        );
+        assert_eq!(
+            explain(">a=\"\">key>content;").unwrap().message,
+            "`a=\"\"`: attribute values cannot be empty"
+        );
+    }
+}
@@ -0,0 +1,388 @@
+use crate::cst::CstDocument;
+use crate::document::JtlDocument;
+use crate::element::JtlElement;
+use crate::scan::Scanner;
+use crate::write::element_line;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Appends elements to the body of an existing JTL file.
+///
+/// The new lines are inserted in front of the `>>>END;` line that closes the
+/// body, the first one a parser stops at; the terminator
+/// and anything after it, such as trailing comments, are written back unchanged.
+/// The file's newline style is kept. To append repeatedly, use an [`Appender`].
+pub fn append_to_file(path: impl AsRef<Path>, elements: &[JtlElement]) -> io::Result<()> {
+    Appender::open(path)?.append_all(elements)
+}
+
+/// Appends elements to a JTL file that is kept open, for files used as logs.
+///
+/// The file is read once when opened, to find the last `>>>END;` line. Each
+/// append then writes only the new lines plus the terminator and whatever
+/// follows it, so its cost does not grow with the file. Other writers must not
+/// touch the file while the appender is open.
+#[derive(Debug)]
+pub struct Appender {
+    file: File,
+    offset: u64,
+    tail: Vec<u8>,
+    newline: &'static str,
+}
+
+impl Appender {
+    /// Opens the JTL file at `path` for appending.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+
+        if !text
+            .lines()
+            .next()
+            .is_some_and(|line| line.contains("DOCTYPE=JTL"))
+        {
+            return Err(invalid_data("invalid JTL document: missing DOCTYPE"));
+        }
+        let offset = terminator_offset(&text)
+            .ok_or_else(|| invalid_data("invalid JTL document: missing >>>END;"))?;
+        Ok(Appender {
+            file,
+            offset: offset as u64,
+            tail: text.as_bytes()[offset..].to_vec(),
+            newline: if text.contains("\r\n") { "\r\n" } else { "\n" },
+        })
+    }
+
+    /// Appends one element.
+    pub fn append(&mut self, element: &JtlElement) -> io::Result<()> {
+        self.append_all(std::slice::from_ref(element))
+    }
+
+    /// Appends elements in one write. If any element cannot be written, none
+    /// are.
+    pub fn append_all(&mut self, elements: &[JtlElement]) -> io::Result<()> {
+        let mut inserted = String::new();
+        for element in elements {
+            let line = element_line(element)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            inserted.push_str(&line);
+            inserted.push_str(self.newline);
+        }
+
+        let mut bytes = inserted.into_bytes();
+        let len = bytes.len() as u64;
+        bytes.extend_from_slice(&self.tail);
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        self.file.write_all(&bytes)?;
+        self.file.flush()?;
+        self.offset += len;
+        Ok(())
+    }
+
+    /// Flushes appended elements through to the disk.
+    pub fn sync(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+}
+
+/// Loads, edits, and saves the JTL file at `path` while holding an exclusive lock.
+///
+/// The lock is an advisory lock on a `<path>.lock` file next to the document, so
+/// every process touching the document must go through this helper for it to
+/// protect anything. A missing document starts out empty.
+///
+/// The file is only written if `edit` changed the document. Changes are then
+/// made to the file's text in place, so comments, formatting and env
+/// references outside the edited declarations are kept; if they can't be, the
+/// whole document is written with [`JtlDocument::write_file`].
+pub fn with_locked_document<R>(
+    path: impl AsRef<Path>,
+    edit: impl FnOnce(&mut JtlDocument) -> R,
+) -> Result<R, Box<dyn Error>> {
+    let path = path.as_ref();
+    let mut lock_path = OsString::from(path.as_os_str());
+    lock_path.push(".lock");
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)?;
+    lock.lock()?;
+
+    let text = match fs::read_to_string(path) {
+        Ok(text) => Some(text),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => return Err(err.into()),
+    };
+    let mut document = match &text {
+        Some(text) => JtlDocument::parse(text)?,
+        None => JtlDocument::new(),
+    };
+    let before = document.clone();
+    let result = edit(&mut document);
+    if !same_document(&before, &document) {
+        match text.and_then(|text| edit_source(&text, &before, &document)) {
+            Some(source) => write_atomic(path, |file| file.write_all(source.as_bytes()))?,
+            None => document.write_file(path)?,
+        }
+    }
+
+    lock.unlock()?;
+    Ok(result)
+}
+
+/// Returns whether two documents have the same env, elements and annotations.
+fn same_document(a: &JtlDocument, b: &JtlDocument) -> bool {
+    a == b
+        && a.elements
+            .iter()
+            .zip(&b.elements)
+            .all(|(a, b)| a.annotations == b.annotations)
+}
+
+/// Edits `text`, the source of `before`, into the source of `after`, touching
+/// only the env variables and the run of elements that differ. Returns `None`
+/// if the result would not parse back to `after`, for instance because a kept
+/// element refers to an env variable whose value changed.
+fn edit_source(text: &str, before: &JtlDocument, after: &JtlDocument) -> Option<String> {
+    let mut cst = CstDocument::parse(text).ok()?;
+    let mut names: Vec<&String> = before.env.keys().chain(after.env.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        match after.env.get(name) {
+            Some(value) if before.env.get(name) != Some(value) => cst.set_env(name, value).ok()?,
+            Some(_) => {}
+            None => {
+                cst.remove_env(name);
+            }
+        }
+    }
+
+    let (old, new) = (&before.elements, &after.elements);
+    if cst.element_count() != old.len() {
+        return None;
+    }
+    let same = |(a, b): &(&JtlElement, &JtlElement)| a == b && a.annotations == b.annotations;
+    let prefix = old.iter().zip(new).take_while(same).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(same)
+        .count();
+    for _ in prefix..old.len() - suffix {
+        cst.remove_element(prefix);
+    }
+    for (index, element) in new[prefix..new.len() - suffix].iter().enumerate() {
+        cst.insert_element(prefix + index, element).ok()?;
+    }
+
+    let edited = cst.to_document().ok()?;
+    same_document(&edited, after).then(|| cst.to_string())
+}
+
+/// Replaces `path` with whatever `write` produces, via a synced temporary file and a rename.
+///
+/// An existing target keeps its permissions.
+pub(crate) fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut File) -> io::Result<()>,
+) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let temp = dir.join(format!(
+        ".{}.tmp{}-{}",
+        file_name.to_string_lossy(),
+        std::process::id(),
+        TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = File::create(&temp).and_then(|mut file| {
+        write(&mut file)?;
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+        return result;
+    }
+
+    // Persist the rename itself.
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+/// Byte offset of the start of the `>>>END;` line that ends the document,
+/// found the same way the parser finds it.
+fn terminator_offset(text: &str) -> Option<usize> {
+    let mut scanner = Scanner::default();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let _ = scanner.scan_line(line);
+        if scanner.ended() {
+            return Some(offset);
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Returns a fresh path under the system temp directory.
+    pub(crate) fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jtl-rs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_append_to_file() {
+        let path = temp_path("append.jtl");
+        fs::write(
+            &path,
+            "DOCTYPE=JTL\r\n>>>BEGIN;\r\n>a=\"1\">first>one;\r\n>>>END;\r\n>//> trailing comment\r\n",
+        )
+        .unwrap();
+
+        append_to_file(
+            &path,
+            &[JtlElement::new("second", "two").with_attr("a", "2")],
+        )
+        .unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            text,
+            "DOCTYPE=JTL\r\n>>>BEGIN;\r\n>a=\"1\">first>one;\r\n>a=\"2\">second>two;\r\n>>>END;\r\n>//> trailing comment\r\n"
+        );
+        assert_eq!(JtlDocument::parse(&text).unwrap().elements.len(), 2);
+    }
+
+    #[test]
+    fn test_appender() {
+        let path = temp_path("appender.jtl");
+        fs::write(&path, "DOCTYPE=JTL\n>>>BEGIN;\n>>>END;\n/* end of log */\n").unwrap();
+        let mut appender = Appender::open(&path).unwrap();
+        for n in 0..3 {
+            let event = JtlElement::new(format!("event{}", n), "ok").with_attr("n", n.to_string());
+            appender.append(&event).unwrap();
+        }
+        assert!(appender
+            .append(&JtlElement::new("bad", "no attrs"))
+            .is_err());
+        appender.sync().unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.ends_with(">n=\"2\">event2>ok;\n>>>END;\n/* end of log */\n"));
+        assert_eq!(JtlDocument::parse(&text).unwrap().elements.len(), 3);
+    }
+
+    #[test]
+    fn test_with_locked_document() {
+        let path = temp_path("locked.jtl");
+        let workers: Vec<_> = (0..4)
+            .map(|worker| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for i in 0..5 {
+                        with_locked_document(&path, |doc| {
+                            let key = format!("w{}-{}", worker, i);
+                            doc.elements.push(
+                                JtlElement::new(key, "entry")
+                                    .with_attr("worker", worker.to_string()),
+                            );
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let count = with_locked_document(&path, |doc| doc.elements.len()).unwrap();
+        assert_eq!(count, 20);
+    }
+
+    #[test]
+    fn test_with_locked_document_keeps_source() {
+        let path = temp_path("locked-source.jtl");
+        let source = "DOCTYPE=JTL\r\n>>>ENV;\r\n>>>host=db;\r\n>>>BEGIN;\r\n/* keep me */\r\n\
+                      >a=\"1\">web>$env:host;\r\n>>>END;\r\n";
+        fs::write(&path, source).unwrap();
+        let count = with_locked_document(&path, |doc| doc.elements.len()).unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), source);
+
+        with_locked_document(&path, |doc| {
+            doc.elements
+                .push(JtlElement::new("cache", "redis").with_attr("b", "2"));
+        })
+        .unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(
+            text.contains("/* keep me */\r\n>a=\"1\">web>$env:host;\r\n>b=\"2\">cache>redis;\r\n")
+        );
+
+        with_locked_document(&path, |doc| doc.env.insert("host".into(), "primary".into())).unwrap();
+        let document = JtlDocument::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(document.env["host"], "primary");
+        assert_eq!(document.elements[0].content, "db");
+    }
+
+    #[test]
+    fn test_append_to_file_before_first_terminator() {
+        let path = temp_path("append-first-terminator.jtl");
+        let text =
+            "DOCTYPE=JTL\n>>>BEGIN;\n>x=\"1\">a>one;\n>>>END;\n>//> example: >>>END;\n>>>END;\n";
+        fs::write(&path, text).unwrap();
+        append_to_file(&path, &[JtlElement::new("b", "two").with_attr("x", "2")]).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "DOCTYPE=JTL\n>>>BEGIN;\n>x=\"1\">a>one;\n>x=\"2\">b>two;\n>>>END;\n>//> example: >>>END;\n>>>END;\n"
+        );
+        let document = JtlDocument::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(document.elements.len(), 2);
+    }
+
+    #[test]
+    fn test_append_to_file_without_terminator() {
+        let path = temp_path("append-unterminated.jtl");
+        fs::write(&path, "DOCTYPE=JTL\n>>>BEGIN;\n").unwrap();
+        let err =
+            append_to_file(&path, &[JtlElement::new("id", "x").with_attr("a", "1")]).unwrap_err();
+        assert_eq!(err.to_string(), "invalid JTL document: missing >>>END;");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "DOCTYPE=JTL\n>>>BEGIN;\n"
+        );
+    }
+}
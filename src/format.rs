@@ -0,0 +1,131 @@
+use regex::Regex;
+use std::error::Error;
+
+use crate::scan;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Head,
+    Env,
+    Body,
+    Tail,
+}
+
+/// Rewrites JTL source in a canonical style, without changing what it parses
+/// to.
+///
+/// Each declaration goes on its own line, indented by `indent` spaces inside
+/// the `>>>ENV;` and `>>>BEGIN;` blocks, with directives at the margin. Env
+/// declarations lose the spaces around `=`, and attributes are separated by
+/// single spaces. Comments are kept in place, runs of blank lines become one,
+/// and anything the parser ignores is kept as written. Attribute sections the
+/// parser would read differently once respaced are left untouched.
+pub fn format_source(text: &str, indent: usize) -> Result<String, Box<dyn Error>> {
+    let mut lines = text.lines();
+    let doctype = lines.next();
+    scan::check_doctype(doctype)?;
+
+    let attrs_only = Regex::new(r#"^\s*(?:\w+="[^"]+"\s*)+$"#)?;
+    let attr = Regex::new(r#"\w+="[^"]+""#)?;
+    let pad = " ".repeat(indent);
+    let mut out = vec![doctype.unwrap_or_default().trim().to_string()];
+    let mut section = Section::Head;
+    for line in lines {
+        let line = line.trim();
+        let margin = if matches!(section, Section::Env | Section::Body) {
+            pad.as_str()
+        } else {
+            ""
+        };
+        if line.is_empty() {
+            if out.last().is_some_and(|last| !last.is_empty()) {
+                out.push(String::new());
+            }
+            continue;
+        }
+        let directive = match line {
+            ">>>ENV;" => Some(Section::Env),
+            ">>>BEGIN;" => Some(Section::Body),
+            ">>>END;" => Some(Section::Tail),
+            _ => None,
+        };
+        if let Some(next) = directive {
+            section = next;
+            out.push(line.to_string());
+            continue;
+        }
+        if scan::is_skipped(line) {
+            out.push(format!("{}{}", margin, line));
+            continue;
+        }
+
+        let segments: Vec<&str> = line.split(';').collect();
+        for (index, segment) in segments.iter().enumerate() {
+            let decl = segment.trim();
+            if decl.is_empty() {
+                continue;
+            }
+            let terminated = index + 1 < segments.len();
+            let formatted = match section {
+                Section::Env if decl.starts_with(">>>") && decl.contains('=') => {
+                    let (name, value) = decl[3..].split_once('=').unwrap_or_default();
+                    format!(">>>{}={};", name.trim(), value.trim())
+                }
+                Section::Body if decl.starts_with('>') && !decl.starts_with(">//>") => {
+                    format!("{};", format_element(decl, &attrs_only, &attr))
+                }
+                _ if terminated => format!("{};", decl),
+                _ => decl.to_string(),
+            };
+            out.push(format!("{}{}", margin, formatted));
+        }
+    }
+    while out.last().is_some_and(String::is_empty) {
+        out.pop();
+    }
+    let mut formatted = out.join("\n");
+    formatted.push('\n');
+    Ok(formatted)
+}
+
+/// Respaces the attribute section of an element declaration, if that cannot
+/// change how it parses.
+fn format_element(decl: &str, attrs_only: &Regex, attr: &Regex) -> String {
+    let body = &decl[1..];
+    let Some(end) = body.find('>') else {
+        return decl.to_string();
+    };
+    let section = &body[..end];
+    if !attrs_only.is_match(section) {
+        return decl.to_string();
+    }
+    let attrs: Vec<&str> = attr
+        .find_iter(section)
+        .map(|found| found.as_str())
+        .collect();
+    format!(">{}{}", attrs.join(" "), &body[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_source() {
+        let text = "DOCTYPE=JTL  \n>>>ENV;\n  >>> host = localhost ;>>>port=80;\n\n\n/* servers */\n\
+                    >>>BEGIN;\n>a=\"1\"   b=\"2\">web>$env:host; >//> inline\n>weird =\"x\" n=\"1\">k>v;\n\
+                    >>>END;\n\n";
+        let formatted = format_source(text, 2).unwrap();
+        assert_eq!(
+            formatted,
+            "DOCTYPE=JTL\n>>>ENV;\n  >>>host=localhost;\n  >>>port=80;\n\n  /* servers */\n>>>BEGIN;\n  \
+             >a=\"1\" b=\"2\">web>$env:host;\n  >//> inline\n  >weird =\"x\" n=\"1\">k>v;\n>>>END;\n"
+        );
+        assert_eq!(format_source(&formatted, 2).unwrap(), formatted);
+        assert_eq!(
+            crate::JtlDocument::parse(&formatted).unwrap(),
+            crate::JtlDocument::parse(text).unwrap()
+        );
+        assert!(format_source("no doctype", 0).is_err());
+    }
+}
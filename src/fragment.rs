@@ -0,0 +1,167 @@
+use std::collections::BTreeSet;
+
+use crate::combine::copy_origin;
+use crate::{JtlDocument, JtlElement};
+
+/// Where [`inject`] places a fragment's elements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Position {
+    /// Before the first element.
+    Start,
+    /// After the last element.
+    End,
+    /// Before the first element with this key.
+    Before(String),
+    /// After the last element with this key.
+    After(String),
+}
+
+/// Copies the elements whose key is one of `keys` into a new document, in
+/// document order, along with the env variables they reference.
+///
+/// Referenced variables that `document` does not declare are left out; use
+/// [`missing_env`] on the result to list them.
+pub fn extract(document: &JtlDocument, keys: &[&str]) -> JtlDocument {
+    let elements: Vec<JtlElement> = document
+        .elements
+        .iter()
+        .filter(|element| keys.contains(&element.key.as_str()))
+        .cloned()
+        .collect();
+    let mut fragment = JtlDocument::new();
+    for name in references(&elements) {
+        if let Some(value) = document.env.get(name) {
+            fragment.env.insert(name.to_string(), value.clone());
+            copy_origin(&mut fragment, document, name);
+        }
+    }
+    fragment.elements = elements;
+    fragment
+}
+
+/// Inserts the elements of `fragment` into `document` at `position`, and adds
+/// the fragment's env variables.
+///
+/// Fails, leaving `document` untouched, if the position names a key that is
+/// not in the document or if a variable is declared in both with different
+/// values.
+pub fn inject(
+    document: &mut JtlDocument,
+    fragment: &JtlDocument,
+    position: Position,
+) -> Result<(), String> {
+    let index = match &position {
+        Position::Start => Some(0),
+        Position::End => Some(document.elements.len()),
+        Position::Before(key) => document.elements.iter().position(|e| &e.key == key),
+        Position::After(key) => document
+            .elements
+            .iter()
+            .rposition(|e| &e.key == key)
+            .map(|index| index + 1),
+    }
+    .ok_or_else(|| match &position {
+        Position::Before(key) | Position::After(key) => format!("element `{}` not found", key),
+        _ => unreachable!("start and end always exist"),
+    })?;
+    for (name, value) in &fragment.env {
+        match document.env.get(name) {
+            Some(existing) if existing != value => {
+                return Err(format!(
+                    "env variable `{}` conflicts: `{}` and `{}`",
+                    name, existing, value
+                ))
+            }
+            _ => {}
+        }
+    }
+    for (name, value) in &fragment.env {
+        if !document.env.contains_key(name) {
+            document.env.insert(name.clone(), value.clone());
+            copy_origin(document, fragment, name);
+        }
+    }
+    document
+        .elements
+        .splice(index..index, fragment.elements.iter().cloned());
+    Ok(())
+}
+
+/// Returns the env variables referenced by the document's elements, through
+/// `$env:NAME` or `${env:NAME}`, that it does not declare, sorted by name.
+///
+/// Parsed documents have their references resolved already, so this matters
+/// for documents built or moved around with [`extract`] and [`inject`].
+pub fn missing_env(document: &JtlDocument) -> Vec<String> {
+    references(&document.elements)
+        .into_iter()
+        .filter(|name| !document.env.contains_key(*name))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Collects the names of the env variables referenced by `elements`.
+fn references(elements: &[JtlElement]) -> BTreeSet<&str> {
+    let mut names = BTreeSet::new();
+    for element in elements {
+        if let Some(name) = crate::env_reference(&element.content) {
+            names.insert(name);
+            continue;
+        }
+        names.extend(crate::interp::references(&element.content));
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> JtlDocument {
+        let mut document = JtlDocument::with_env([("host", "a"), ("port", "80"), ("unused", "x")]);
+        document.elements = vec![
+            JtlElement::new("web", "$env:host").with_attr("n", "1"),
+            JtlElement::new("db", "pg").with_attr("n", "2"),
+            JtlElement::new("url", "http://${env:host}:${env:port|trim}/${env:path}")
+                .with_attr("n", "3"),
+        ];
+        document
+    }
+
+    #[test]
+    fn test_extract() {
+        let fragment = extract(&source(), &["url", "web"]);
+        assert_eq!(fragment.keys().collect::<Vec<_>>(), ["web", "url"]);
+        assert_eq!(fragment.env.len(), 2);
+        assert_eq!(fragment.env["port"], "80");
+        assert_eq!(missing_env(&fragment), ["path"]);
+        assert!(extract(&source(), &["db"]).env.is_empty());
+    }
+
+    #[test]
+    fn test_inject() {
+        let fragment = extract(&source(), &["web"]);
+        let mut target = JtlDocument::new();
+        target.elements = vec![
+            JtlElement::new("a", "1").with_attr("n", "1"),
+            JtlElement::new("b", "2").with_attr("n", "2"),
+        ];
+        inject(&mut target, &fragment, Position::After("a".into())).unwrap();
+        inject(&mut target, &fragment, Position::Start).unwrap();
+        assert_eq!(target.keys().collect::<Vec<_>>(), ["web", "a", "web", "b"]);
+        assert_eq!(target.env["host"], "a");
+        assert!(missing_env(&target).is_empty());
+
+        assert_eq!(
+            inject(&mut target, &fragment, Position::Before("zzz".into())).unwrap_err(),
+            "element `zzz` not found"
+        );
+        target.env.insert("host".into(), "b".into());
+        assert_eq!(
+            inject(&mut target, &fragment, Position::End).unwrap_err(),
+            "env variable `host` conflicts: `b` and `a`"
+        );
+        assert_eq!(target.elements.len(), 4);
+    }
+}
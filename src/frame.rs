@@ -0,0 +1,214 @@
+use std::error::Error;
+use std::io::Read;
+
+use crate::encoding::BOM;
+use crate::scan::is_skipped;
+use crate::JtlDocument;
+
+/// Splits a byte stream of back-to-back documents into whole documents.
+///
+/// The decoder is sans-IO: feed it a buffer that received bytes are appended
+/// to, and it removes each complete document from the front. A document ends
+/// at its `>>>END;` line or, if it has none, where the next `DOCTYPE` line
+/// starts. Blank lines and comments between documents are dropped; anything
+/// else there is an error. Errors leave the buffer in an unspecified state,
+/// so the stream should be dropped after one.
+///
+/// The methods mirror `tokio_util::codec::Decoder`, so a codec is a thin
+/// wrapper that calls them on the contents of its `BytesMut`. For blocking
+/// readers, use [`read_frames`].
+#[derive(Debug, Clone, Default)]
+pub struct JtlFrameDecoder {
+    scanned: usize,
+    in_document: bool,
+    max_bytes: Option<usize>,
+}
+
+impl JtlFrameDecoder {
+    /// Creates a decoder with no size cap.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails once a single document, or the unfinished line in front of its
+    /// `DOCTYPE`, grows past `max` bytes, instead of buffering it without
+    /// bound.
+    pub fn max_document_bytes(mut self, max: usize) -> Self {
+        self.max_bytes = Some(max);
+        self
+    }
+
+    /// Removes and parses the next complete document at the front of `buf`,
+    /// or returns `None` if more bytes are needed.
+    pub fn decode(&mut self, buf: &mut Vec<u8>) -> Result<Option<JtlDocument>, Box<dyn Error>> {
+        while let Some(len) = buf[self.scanned..].iter().position(|&b| b == b'\n') {
+            let (start, end) = (self.scanned, self.scanned + len + 1);
+            let line = std::str::from_utf8(&buf[start..end])
+                .map_err(|err| format!("invalid JTL stream: {}", err))?;
+            let line = line.trim().trim_start_matches(BOM);
+            let doctype = line.starts_with("DOCTYPE=JTL");
+
+            if !self.in_document {
+                if doctype {
+                    self.in_document = true;
+                    buf.drain(..start);
+                    self.scanned = end - start;
+                } else if is_skipped(line) {
+                    buf.drain(..end);
+                    self.scanned = 0;
+                } else {
+                    return Err(format!("invalid JTL stream: `{}` outside a document", line).into());
+                }
+            } else if doctype && start > 0 {
+                self.scanned = end - start;
+                return parse(buf.drain(..start).collect()).map(Some);
+            } else if line == ">>>END;" {
+                self.in_document = false;
+                self.scanned = 0;
+                return parse(buf.drain(..end).collect()).map(Some);
+            } else {
+                self.scanned = end;
+            }
+        }
+        if let Some(max) = self.max_bytes {
+            if buf.len() > max {
+                return Err(format!("invalid JTL stream: document exceeds {} bytes", max).into());
+            }
+        }
+        Ok(None)
+    }
+
+    /// Like [`decode`](JtlFrameDecoder::decode), once the stream has ended:
+    /// a document still being received is parsed as it stands.
+    pub fn decode_eof(&mut self, buf: &mut Vec<u8>) -> Result<Option<JtlDocument>, Box<dyn Error>> {
+        if let Some(document) = self.decode(buf)? {
+            return Ok(Some(document));
+        }
+        if !self.in_document {
+            let rest = String::from_utf8_lossy(buf);
+            if let Some(line) = rest.lines().map(str::trim).find(|line| !is_skipped(line)) {
+                return Err(format!("invalid JTL stream: `{}` outside a document", line).into());
+            }
+            buf.clear();
+            return Ok(None);
+        }
+        self.in_document = false;
+        self.scanned = 0;
+        parse(std::mem::take(buf)).map(Some)
+    }
+}
+
+fn parse(bytes: Vec<u8>) -> Result<JtlDocument, Box<dyn Error>> {
+    JtlDocument::parse(&String::from_utf8(bytes)?)
+}
+
+/// Reads back-to-back documents from `reader`, such as a TCP stream, with a
+/// [`JtlFrameDecoder`].
+pub fn read_frames<R: Read>(reader: R, decoder: JtlFrameDecoder) -> Frames<R> {
+    Frames {
+        reader,
+        decoder,
+        buf: Vec::new(),
+        done: false,
+    }
+}
+
+/// Iterator returned by [`read_frames`].
+#[derive(Debug)]
+pub struct Frames<R> {
+    reader: R,
+    decoder: JtlFrameDecoder,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> Iterator for Frames<R> {
+    type Item = Result<JtlDocument, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = [0; 8192];
+        while !self.done {
+            match self.decoder.decode(&mut self.buf) {
+                Ok(Some(document)) => return Some(Ok(document)),
+                Ok(None) => {}
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+            match self.reader.read(&mut chunk) {
+                Ok(0) => {
+                    self.done = true;
+                    return self.decoder.decode_eof(&mut self.buf).transpose();
+                }
+                Ok(n) => self.buf.extend_from_slice(&chunk[..n]),
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err.into()));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STREAM: &str = "DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">first>a;\n>>>END;\n\n/* next */\n\
+                          DOCTYPE=JTL\n>>>BEGIN;\n>n=\"2\">second>b;\n\
+                          DOCTYPE=JTL\n>>>BEGIN;\n>n=\"3\">third>c;\n";
+
+    #[test]
+    fn test_frame_decoder() {
+        // Feed the stream a few bytes at a time.
+        let mut decoder = JtlFrameDecoder::new();
+        let mut buf = Vec::new();
+        let mut keys = Vec::new();
+        for chunk in STREAM.as_bytes().chunks(7) {
+            buf.extend_from_slice(chunk);
+            while let Some(document) = decoder.decode(&mut buf).unwrap() {
+                keys.push(document.elements[0].key.clone());
+            }
+        }
+        assert_eq!(keys, ["first", "second"]);
+        let last = decoder.decode_eof(&mut buf).unwrap().unwrap();
+        assert_eq!(last.elements[0].key, "third");
+        assert_eq!(decoder.decode_eof(&mut buf).unwrap(), None);
+
+        let frames: Vec<_> = read_frames(STREAM.as_bytes(), JtlFrameDecoder::new())
+            .map(|document| document.unwrap().elements[0].key.clone())
+            .collect();
+        assert_eq!(frames, ["first", "second", "third"]);
+    }
+
+    #[test]
+    fn test_frame_decoder_errors() {
+        let mut buf = b"garbage\nDOCTYPE=JTL\n".to_vec();
+        let err = JtlFrameDecoder::new().decode(&mut buf).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid JTL stream: `garbage` outside a document"
+        );
+
+        let mut decoder = JtlFrameDecoder::new().max_document_bytes(32);
+        let mut buf = b"DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">first>a;\n".to_vec();
+        let err = decoder.decode(&mut buf).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid JTL stream: document exceeds 32 bytes"
+        );
+
+        let mut decoder = JtlFrameDecoder::new().max_document_bytes(32);
+        let mut buf = b"/* a comment that never ends".to_vec();
+        assert!(decoder.decode(&mut buf).unwrap().is_none());
+        buf.extend_from_slice(&[b'x'; 8]);
+        let err = decoder.decode(&mut buf).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid JTL stream: document exceeds 32 bytes"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+use crate::sample::SplitMix64;
+use crate::{JtlDocument, JtlElement};
+
+const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Shape of the documents made by [`generate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct GenOptions {
+    /// Number of elements.
+    pub elements: usize,
+    /// Attributes on each element; at least one is always written, since
+    /// elements need one.
+    pub attrs_per_element: usize,
+    /// Length of each content in bytes, at least 1.
+    pub content_len: usize,
+    /// Seed for the random attribute values and contents.
+    pub seed: u64,
+}
+
+impl Default for GenOptions {
+    fn default() -> Self {
+        GenOptions {
+            elements: 100,
+            attrs_per_element: 3,
+            content_len: 32,
+            seed: 0,
+        }
+    }
+}
+
+/// Builds a synthetic document for benchmarks and load tests.
+///
+/// Elements are keyed `e0`, `e1`, … with attributes `a0`, `a1`, … holding
+/// short random values, and random alphanumeric contents. The result depends
+/// only on `options`, so the same options always give the same document.
+pub fn generate(options: &GenOptions) -> JtlDocument {
+    let mut rng = SplitMix64(options.seed);
+    let mut text = |len: usize| -> String {
+        (0..len)
+            .map(|_| ALPHABET[rng.below(ALPHABET.len())] as char)
+            .collect()
+    };
+    let mut document = JtlDocument::new();
+    for index in 0..options.elements {
+        let mut element = JtlElement::new(format!("e{}", index), text(options.content_len.max(1)));
+        for attr in 0..options.attrs_per_element.max(1) {
+            element = element.with_attr(format!("a{}", attr), text(8));
+        }
+        document.elements.push(element);
+    }
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let options = GenOptions {
+            elements: 50,
+            attrs_per_element: 2,
+            content_len: 20,
+            seed: 7,
+        };
+        let document = generate(&options);
+        assert_eq!(document.elements.len(), 50);
+        assert_eq!(document.elements[49].key, "e49");
+        assert_eq!(document.elements[0].attrs.len(), 2);
+        assert_eq!(document.elements[0].content.len(), 20);
+        assert_eq!(generate(&options), document);
+        assert_ne!(generate(&GenOptions { seed: 8, ..options }), document);
+        assert_eq!(
+            JtlDocument::parse(&document.to_jtl().unwrap()).unwrap(),
+            document
+        );
+    }
+}
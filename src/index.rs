@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::element::JtlElement;
+use crate::encoding;
+use crate::error::{sendable, FileError, JtlError};
+use crate::options::ParseOptions;
+use crate::scan::{self, Decl, Defaults, Scanner};
+
+/// A JTL file indexed by the byte offset of each element, for random access
+/// into files too large to read into memory.
+///
+/// Opening the file reads it once, keeping only the environment and the
+/// position of every element. [`get`](IndexedFile::get) then reads and
+/// decodes a single element from disk, so memory use does not grow with the
+/// size of the contents. Elements are not cached.
+///
+/// The file is not memory-mapped, although that was the original request:
+/// std has no mmap, and the crate avoids `unsafe` mapping code or a `memmap2`
+/// dependency. Each `get` seeks and reads instead, under a `Mutex` around the
+/// file, so threads sharing an `IndexedFile` take turns reading.
+#[derive(Debug)]
+pub struct IndexedFile {
+    file: Mutex<File>,
+    source: Arc<Path>,
+    env: HashMap<String, String>,
+    defaults: Defaults,
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    offset: u64,
+    len: usize,
+    line: usize,
+}
+
+impl IndexedFile {
+    /// Opens and indexes the JTL file at `path`.
+    ///
+    /// Errors are [`FileError`]s, naming the file and line. Malformed
+    /// elements are only reported when they are accessed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| FileError::new(path, err))?;
+        let mut index = IndexedFile {
+            file: Mutex::new(file.try_clone().map_err(|err| FileError::new(path, err))?),
+            source: Arc::from(path),
+            env: HashMap::new(),
+            defaults: Defaults::default(),
+            entries: Vec::new(),
+        };
+        index
+            .build(BufReader::new(file))
+            .map_err(|err| FileError::new(path, sendable(err)))?;
+        Ok(index)
+    }
+
+    fn build(&mut self, mut reader: impl BufRead) -> Result<(), Box<dyn Error>> {
+        let mut scanner = Scanner::default();
+        let mut buffer = Vec::new();
+        let mut offset = 0;
+        let mut number = 0;
+        loop {
+            buffer.clear();
+            let read = reader.read_until(b'\n', &mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            number += 1;
+            let line = std::str::from_utf8(&buffer)
+                .map_err(|_| format!("invalid JTL document: line {} is not UTF-8", number))?;
+            if number == 1 {
+                scan::check_doctype(Some(line.trim_start_matches(encoding::BOM)))?;
+            }
+            for decl in scanner.scan_line(line) {
+                match decl {
+                    Decl::Env(name, value) => {
+                        self.env.insert(name.to_string(), value.to_string());
+                    }
+                    Decl::Defaults(attrs) => self
+                        .defaults
+                        .extend(attrs)
+                        .map_err(|err| format!("{} on line {}", err, number))?,
+                    Decl::Element(decl) => self.entries.push(Entry {
+                        offset: offset + (decl.as_ptr() as usize - line.as_ptr() as usize) as u64,
+                        len: decl.len(),
+                        line: number,
+                    }),
+                }
+            }
+            offset += read as u64;
+        }
+        if number == 0 {
+            scan::check_doctype(None)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the file has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the variables declared in the `>>>ENV;` block.
+    pub fn env(&self) -> &HashMap<String, String> {
+        &self.env
+    }
+
+    /// Returns the byte range of the element at `index` in the file.
+    pub fn span(&self, index: usize) -> Option<Range<u64>> {
+        let entry = self.entries.get(index)?;
+        Some(entry.offset..entry.offset + entry.len as u64)
+    }
+
+    /// Reads and decodes the element at `index`.
+    ///
+    /// Env substitution uses the file's final environment, like
+    /// [`LazyDocument::get`](crate::LazyDocument::get). Fails if the file
+    /// cannot be read or the element is malformed.
+    pub fn get(&self, index: usize) -> Option<Result<JtlElement, Box<dyn Error>>> {
+        let entry = *self.entries.get(index)?;
+        Some(self.decode(entry).map_err(|err| {
+            FileError::new(&self.source, JtlError::new(err, Some(entry.line))).into()
+        }))
+    }
+
+    fn decode(&self, entry: Entry) -> Result<JtlElement, Box<dyn Error>> {
+        let mut bytes = vec![0; entry.len];
+        {
+            let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+            file.seek(SeekFrom::Start(entry.offset))?;
+            file.read_exact(&mut bytes)?;
+        }
+        let decl = String::from_utf8(bytes)?;
+        let mut element =
+            crate::parse_element(&decl, &self.env, &self.defaults, &ParseOptions::default())?
+                .expect("no filter is set");
+        element.line = Some(entry.line);
+        element.source = Some(Arc::clone(&self.source));
+        Ok(element)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::tests::temp_path;
+
+    #[test]
+    fn test_indexed_file() {
+        let path = temp_path("indexed.jtl");
+        let text = "\u{feff}DOCTYPE=JTL\r\n>>>ENV;\r\n>>>foo=bar;\r\n>>>BEGIN;\r\n\
+                    >a=\"1\">first>$env:foo; >a=\"2\">second>two;\r\n>ab;\r\n>>>END;\r\n";
+        std::fs::write(&path, text).unwrap();
+        let index = IndexedFile::open(&path).unwrap();
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.env()["foo"], "bar");
+
+        let span = index.span(1).unwrap();
+        assert_eq!(
+            &text.as_bytes()[span.start as usize..span.end as usize],
+            b">a=\"2\">second>two"
+        );
+        let second = index.get(1).unwrap().unwrap();
+        assert_eq!(second, JtlElement::new("second", "two").with_attr("a", "2"));
+        assert_eq!(second.origin().line(), Some(5));
+        assert_eq!(index.get(0).unwrap().unwrap().content, "bar");
+
+        let err = index.get(2).unwrap().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("{}:6: invalid element format: too short", path.display())
+        );
+        assert!(index.get(3).is_none());
+
+        std::fs::write(&path, "not jtl\n").unwrap();
+        assert!(IndexedFile::open(&path).is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use crate::options::ParseOptions;
+
+/// A function usable in `${env:NAME|function:argument}`, called with the value
+/// so far and the argument, if one was given.
+pub(crate) type ContentFunction =
+    dyn Fn(&str, Option<&str>) -> Result<String, String> + Send + Sync;
+
+/// Expands every `${env:NAME|function|...}` in `content`.
+///
+/// Functions run left to right. An expression naming a variable that is not
+/// declared is left as written, like an unresolved `$env:NAME`, unless the
+/// pipeline uses `default`, in which case the value starts out empty. Text in
+/// `${...}` that does not start with `env:`, or has no closing `}`, is left
+/// alone too.
+pub(crate) fn interpolate<'a>(
+    content: &'a str,
+    env: &HashMap<String, String>,
+    options: &ParseOptions,
+) -> Result<Cow<'a, str>, String> {
+    if !content.contains("${env:") {
+        return Ok(Cow::Borrowed(content));
+    }
+
+    let mut out = String::with_capacity(content.len());
+    let mut rest = content;
+    while let Some(start) = rest.find("${env:") {
+        out.push_str(&rest[..start]);
+        let expression = &rest[start + 6..];
+        let Some(end) = expression.find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        match evaluate(&expression[..end], env, options)? {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..start + 6 + end + 1]),
+        }
+        rest = &expression[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(Cow::Owned(out))
+}
+
+/// Returns the names of the variables in every `${env:NAME|...}` in `content`.
+pub(crate) fn references(content: &str) -> impl Iterator<Item = &str> {
+    content.split("${env:").skip(1).filter_map(|expression| {
+        let end = expression.find('}')?;
+        let name = expression[..end].split('|').next().unwrap_or_default();
+        Some(name.trim())
+    })
+}
+
+/// Evaluates one expression, or returns `None` if its variable is undefined.
+fn evaluate(
+    expression: &str,
+    env: &HashMap<String, String>,
+    options: &ParseOptions,
+) -> Result<Option<String>, String> {
+    let mut parts = expression.split('|');
+    let name = parts.next().unwrap_or_default().trim();
+    let calls: Vec<(&str, Option<&str>)> = parts
+        .map(|call| match call.split_once(':') {
+            Some((function, argument)) => (function.trim(), Some(argument)),
+            None => (call.trim(), None),
+        })
+        .collect();
+
+    let mut value = match env.get(name) {
+        Some(value) => value.clone(),
+        None if calls.iter().any(|(function, _)| *function == "default") => String::new(),
+        None => return Ok(None),
+    };
+    for (function, argument) in calls {
+        value = match options.functions.get(function) {
+            Some(custom) => (custom.0)(&value, argument)?,
+            None => builtin(function, &value, argument)?,
+        };
+    }
+    Ok(Some(value))
+}
+
+fn builtin(function: &str, value: &str, argument: Option<&str>) -> Result<String, String> {
+    Ok(match function {
+        "upper" => value.to_uppercase(),
+        "lower" => value.to_lowercase(),
+        "trim" => value.trim().to_string(),
+        "default" if value.is_empty() => argument.unwrap_or_default().to_string(),
+        "default" => value.to_string(),
+        _ => return Err(format!("unknown function `{}`", function)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(content: &str, options: &ParseOptions) -> Result<String, String> {
+        let env = HashMap::from([
+            ("name".to_string(), "  World ".to_string()),
+            ("empty".to_string(), String::new()),
+        ]);
+        interpolate(content, &env, options).map(Cow::into_owned)
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let options = ParseOptions::new();
+        assert_eq!(
+            expand("Hello ${env:name|trim|upper}!", &options).unwrap(),
+            "Hello WORLD!"
+        );
+        assert_eq!(
+            expand("${env:empty|default:none}", &options).unwrap(),
+            "none"
+        );
+        assert_eq!(
+            expand("${env:missing|default:a:b}", &options).unwrap(),
+            "a:b"
+        );
+        assert_eq!(
+            expand("${other} stays", &options).unwrap(),
+            "${other} stays"
+        );
+        assert_eq!(
+            expand("echo ${env:HOME} ${env:name|trim}", &options).unwrap(),
+            "echo ${env:HOME} World"
+        );
+        assert_eq!(
+            expand("${env:name|shout}", &options).unwrap_err(),
+            "unknown function `shout`"
+        );
+        assert_eq!(expand("a ${env:name", &options).unwrap(), "a ${env:name");
+        assert_eq!(
+            references("${env:a|upper} ${env: b} ${env:c").collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+    }
+
+    #[test]
+    fn test_custom_function() {
+        let options = ParseOptions::new()
+            .function("repeat", |value, argument| {
+                let times = argument
+                    .unwrap_or("2")
+                    .parse()
+                    .map_err(|_| "bad count".to_string())?;
+                Ok(value.trim().repeat(times))
+            })
+            .function("upper", |value, _| Ok(format!("<{}>", value)));
+        assert_eq!(
+            expand("${env:name|repeat:3}", &options).unwrap(),
+            "WorldWorldWorld"
+        );
+        assert_eq!(
+            expand("${env:name|trim|upper}", &options).unwrap(),
+            "<World>"
+        );
+        assert_eq!(
+            expand("${env:name|repeat:x}", &options).unwrap_err(),
+            "bad count"
+        );
+    }
+}
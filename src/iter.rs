@@ -0,0 +1,262 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::iter::Enumerate;
+use std::str::Lines;
+
+use crate::annotation;
+use crate::encoding::{self, TextFormat};
+use crate::error::JtlError;
+use crate::options::{DuplicateEnv, ParseOptions};
+use crate::scan::{self, Decl, Defaults, Scanner};
+use crate::visit::JtlVisitor;
+use crate::warning::Warning;
+use crate::{JtlDocument, JtlElement};
+
+/// Parses JTL text one element at a time, instead of collecting them all.
+///
+/// Only the environment and the current line are held besides the element
+/// being returned, so memory does not grow with the number of elements. The
+/// iterator stops after the first error.
+pub fn parse_iter(text: &str) -> ParseIter<'_> {
+    ParseIter::new(text, Cow::Owned(ParseOptions::default()))
+}
+
+/// Like [`parse_iter`], applying `options`.
+pub fn parse_iter_with<'a>(text: &'a str, options: &'a ParseOptions) -> ParseIter<'a> {
+    ParseIter::new(text, Cow::Borrowed(options))
+}
+
+/// Iterator returned by [`parse_iter`].
+#[derive(Debug)]
+pub struct ParseIter<'a> {
+    text: &'a str,
+    lines: Enumerate<Lines<'a>>,
+    options: Cow<'a, ParseOptions>,
+    scanner: Scanner,
+    pending: VecDeque<Decl<'a>>,
+    line: usize,
+    started: bool,
+    done: bool,
+    env: HashMap<String, String>,
+    env_lines: HashMap<&'a str, usize>,
+    defaults: Defaults,
+    pub(crate) warnings: Vec<Warning>,
+    anchors: HashMap<String, String>,
+    annotations: Vec<(String, String)>,
+}
+
+impl<'a> ParseIter<'a> {
+    fn new(text: &'a str, options: Cow<'a, ParseOptions>) -> Self {
+        let text = text.strip_prefix(encoding::BOM).unwrap_or(text);
+        ParseIter {
+            text,
+            lines: text.lines().enumerate(),
+            options,
+            scanner: Scanner::default(),
+            pending: VecDeque::new(),
+            line: 0,
+            started: false,
+            done: false,
+            env: HashMap::new(),
+            env_lines: HashMap::new(),
+            defaults: Defaults::default(),
+            warnings: Vec::new(),
+            anchors: HashMap::new(),
+            annotations: Vec::new(),
+        }
+    }
+
+    /// Returns the variables declared so far.
+    pub fn env(&self) -> &HashMap<String, String> {
+        &self.env
+    }
+
+    /// Returns the line of the next element declaration, without parsing it,
+    /// or `None` if no more are left.
+    pub(crate) fn next_element_line(&self) -> Option<usize> {
+        if self.done {
+            return None;
+        }
+        let is_element = |decl: &Decl| matches!(decl, Decl::Element(_));
+        if self.pending.iter().any(is_element) {
+            return Some(self.line);
+        }
+        let mut scanner = self.scanner;
+        self.lines.clone().find_map(|(index, text)| {
+            scanner
+                .scan_line(text)
+                .any(|decl| is_element(&decl))
+                .then_some(index + 1)
+        })
+    }
+
+    /// Builds the document holding `elements` and everything declared so far.
+    pub(crate) fn into_document(
+        self,
+        elements: Vec<JtlElement>,
+        format: TextFormat,
+    ) -> JtlDocument {
+        let mut document = JtlDocument {
+            env: self.env,
+            elements,
+            format,
+            version: self
+                .text
+                .lines()
+                .next()
+                .and_then(scan::doctype_version)
+                .map(String::from),
+            defaults: self.defaults,
+            ..JtlDocument::default()
+        };
+        for (name, line) in self.env_lines {
+            document.set_env_line(name, line);
+        }
+        document
+    }
+
+    /// Returns the next element, reporting env variables and comments read on
+    /// the way to `visitor`.
+    pub(crate) fn next_visiting(
+        &mut self,
+        visitor: &mut dyn JtlVisitor,
+    ) -> Option<Result<JtlElement, JtlError>> {
+        if self.done {
+            return None;
+        }
+        let result = self.step(visitor).transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+
+    fn step(&mut self, visitor: &mut dyn JtlVisitor) -> Result<Option<JtlElement>, JtlError> {
+        let options = &*self.options;
+        if !self.started {
+            self.started = true;
+            scan::check_doctype_with(
+                self.text.lines().next(),
+                options.doctype,
+                options.doctype_versions.as_deref(),
+            )
+            .map_err(|err| JtlError::new(err, None))?;
+        }
+        loop {
+            let line = self.line;
+            let error = |message: String| JtlError::new(message, Some(line));
+            while let Some(decl) = self.pending.pop_front() {
+                match decl {
+                    Decl::Env(name, value) => {
+                        if self.env.contains_key(name) {
+                            let message = format!("env variable `{}` redefined", name);
+                            match options.duplicate_env {
+                                DuplicateEnv::Error => {
+                                    return Err(error(format!(
+                                        "invalid JTL document: {} on line {}",
+                                        message, line
+                                    )))
+                                }
+                                DuplicateEnv::FirstWins => continue,
+                                DuplicateEnv::LastWins => {}
+                                DuplicateEnv::Warn => self.warnings.push(Warning { line, message }),
+                            }
+                        }
+                        self.env.insert(name.to_string(), value.to_string());
+                        self.env_lines.insert(name, line);
+                        visitor.visit_env(name, value, line);
+                    }
+                    Decl::Defaults(attrs) => self
+                        .defaults
+                        .extend(attrs)
+                        .map_err(|err| error(format!("{} on line {}", err, line)))?,
+                    Decl::Element(decl) => {
+                        let anchors = options.anchors.then_some(&mut self.anchors);
+                        let annotations = std::mem::take(&mut self.annotations);
+                        let element = crate::parse_element_with(
+                            decl,
+                            &self.env,
+                            &self.defaults,
+                            anchors,
+                            options,
+                        )
+                        .map_err(|err| error(err.to_string()))?;
+                        let Some(mut element) = element else {
+                            continue;
+                        };
+                        element.annotations = annotations;
+                        element.line = Some(line);
+                        if let Some(validate) = &options.validator {
+                            (validate.0)(&element).map_err(|err| {
+                                error(format!(
+                                    "invalid element `{}` on line {}: {}",
+                                    element.key, line, err
+                                ))
+                            })?;
+                        }
+                        return Ok(Some(element));
+                    }
+                }
+            }
+
+            let Some((index, text)) = self.lines.next() else {
+                return Ok(None);
+            };
+            self.line = index + 1;
+            let trimmed = text.trim();
+            if options.reject_trailing && self.scanner.ended() && !scan::is_skipped(trimmed) {
+                return Err(JtlError::new(
+                    format!(
+                        "invalid JTL document: content after >>>END; on line {}: `{}`",
+                        self.line, trimmed
+                    ),
+                    Some(self.line),
+                ));
+            }
+            if !trimmed.is_empty() && scan::is_skipped(trimmed) {
+                visitor.visit_comment(trimmed, self.line);
+            }
+            if let Some(comment) = trimmed.strip_prefix(">//>") {
+                if self.scanner.in_body() {
+                    self.annotations
+                        .extend(annotation::parse_annotations(comment));
+                }
+            }
+            self.pending.extend(self.scanner.scan_line(text));
+        }
+    }
+}
+
+impl Iterator for ParseIter<'_> {
+    type Item = Result<JtlElement, JtlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        struct Ignore;
+        impl JtlVisitor for Ignore {}
+        self.next_visiting(&mut Ignore)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_iter() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>host=a;\n>>>BEGIN;\n>n=\"1\">first>$env:host;\n\
+                    >n=\"2\">second>x; >bad;\n>n=\"3\">third>y;\n>>>END;";
+        let mut elements = parse_iter(text);
+        let first = elements.next().unwrap().unwrap();
+        assert_eq!((first.key.as_str(), first.content.as_str()), ("first", "a"));
+        assert_eq!(elements.env()["host"], "a");
+        assert_eq!(elements.next().unwrap().unwrap().key, "second");
+        let err = elements.next().unwrap().unwrap_err();
+        assert_eq!(err.line(), Some(6));
+        assert_eq!(err.message(), "invalid element format: too short");
+        assert!(elements.next().is_none());
+
+        let err = parse_iter("no doctype").next().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "invalid JTL document: missing DOCTYPE");
+        assert_eq!(err.line(), None);
+    }
+}
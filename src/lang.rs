@@ -0,0 +1,109 @@
+use std::fmt;
+
+use crate::JtlElement;
+
+/// The reserved attribute naming the language of an element's content.
+pub(crate) const LANG_ATTR: &str = "lang";
+
+/// The language of an element's content, from its `lang` attribute.
+///
+/// Names are matched case-insensitively; unknown ones are kept as
+/// [`Other`](Lang::Other), lowercased.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Lang {
+    /// `json`.
+    Json,
+    /// `sql`.
+    Sql,
+    /// `markdown` or `md`.
+    Markdown,
+    /// `html`.
+    Html,
+    /// `toml`.
+    Toml,
+    /// `yaml` or `yml`.
+    Yaml,
+    /// Any other language.
+    Other(String),
+}
+
+impl Lang {
+    /// Returns the canonical name of the language.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Lang::Json => "json",
+            Lang::Sql => "sql",
+            Lang::Markdown => "markdown",
+            Lang::Html => "html",
+            Lang::Toml => "toml",
+            Lang::Yaml => "yaml",
+            Lang::Other(name) => name,
+        }
+    }
+
+    /// Checks that `content` is well-formed in this language.
+    ///
+    /// Only JSON is checked, and only with the `json` feature; content in any
+    /// other language is accepted.
+    pub fn check(&self, content: &str) -> Result<(), String> {
+        match self {
+            #[cfg(feature = "json")]
+            Lang::Json => serde_json::from_str::<serde_json::Value>(content)
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            _ => {
+                let _ = content;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<&str> for Lang {
+    fn from(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "json" => Lang::Json,
+            "sql" => Lang::Sql,
+            "markdown" | "md" => Lang::Markdown,
+            "html" => Lang::Html,
+            "toml" => Lang::Toml,
+            "yaml" | "yml" => Lang::Yaml,
+            other => Lang::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl JtlElement {
+    /// Returns the language of the content, from the reserved `lang`
+    /// attribute, such as `lang="sql"`.
+    pub fn lang(&self) -> Option<Lang> {
+        self.attr(LANG_ATTR).map(Lang::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lang() {
+        let element = JtlElement::new("q", "select 1").with_attr("lang", "SQL");
+        assert_eq!(element.lang(), Some(Lang::Sql));
+        assert_eq!(JtlElement::new("q", "x").lang(), None);
+        assert_eq!(Lang::from("yml"), Lang::Yaml);
+        assert_eq!(Lang::from("Lua").to_string(), "lua");
+        assert!(Lang::Sql.check("not { checked").is_ok());
+        #[cfg(feature = "json")]
+        {
+            assert!(Lang::Json.check("{\"a\": [1, 2]}").is_ok());
+            assert!(Lang::Json.check("{\"a\": ").is_err());
+        }
+    }
+}
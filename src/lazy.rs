@@ -0,0 +1,149 @@
+use crate::element::JtlElement;
+use crate::options::ParseOptions;
+use crate::scan::{self, Decl, Defaults, Scanner};
+use std::collections::HashMap;
+use std::error::Error;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+/// A document whose elements are decoded on first access.
+///
+/// Parsing only finds where each element is; attributes, key and content are
+/// decoded (and allocated) the first time an element is requested, and cached
+/// from then on. Errors in an element are reported when it is accessed.
+#[derive(Debug)]
+pub struct LazyDocument<'a> {
+    text: &'a str,
+    /// Variables declared in the `>>>ENV;` block.
+    pub env: HashMap<String, String>,
+    defaults: Defaults,
+    elements: Vec<LazyElement>,
+}
+
+#[derive(Debug)]
+struct LazyElement {
+    span: Range<usize>,
+    decoded: OnceLock<Result<JtlElement, String>>,
+}
+
+/// Scans JTL text, leaving each element to be decoded on first access.
+pub fn parse_lazy(text: &str) -> Result<LazyDocument<'_>, Box<dyn Error>> {
+    scan::check_doctype(text.lines().next())?;
+
+    let mut env = HashMap::new();
+    let mut defaults = Defaults::default();
+    let mut elements = Vec::new();
+    let mut scanner = Scanner::default();
+    for line in text.lines() {
+        for decl in scanner.scan_line(line) {
+            match decl {
+                Decl::Env(name, value) => {
+                    env.insert(name.to_string(), value.to_string());
+                }
+                Decl::Defaults(attrs) => defaults.extend(attrs)?,
+                Decl::Element(decl) => {
+                    let start = decl.as_ptr() as usize - text.as_ptr() as usize;
+                    elements.push(LazyElement {
+                        span: start..start + decl.len(),
+                        decoded: OnceLock::new(),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(LazyDocument {
+        text,
+        env,
+        defaults,
+        elements,
+    })
+}
+
+impl<'a> LazyDocument<'a> {
+    /// Returns the number of elements.
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Returns whether the document has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Returns the undecoded source text of the element at `index`.
+    pub fn source(&self, index: usize) -> Option<&'a str> {
+        let text: &'a str = self.text;
+        self.elements
+            .get(index)
+            .map(|element| &text[element.span.clone()])
+    }
+
+    /// Returns the byte range of the element at `index` in the original text.
+    pub fn span(&self, index: usize) -> Option<Range<usize>> {
+        self.elements.get(index).map(|element| element.span.clone())
+    }
+
+    /// Decodes (on first access) and returns the element at `index`.
+    ///
+    /// Env substitution uses the document's final environment, like
+    /// [`parse`](crate::parse) does for elements after the `>>>ENV;` block.
+    pub fn get(&self, index: usize) -> Option<Result<&JtlElement, Box<dyn Error>>> {
+        let element = self.elements.get(index)?;
+        let decoded = element.decoded.get_or_init(|| {
+            let source = &self.text[element.span.clone()];
+            match crate::parse_element(source, &self.env, &self.defaults, &ParseOptions::default())
+            {
+                Ok(element) => Ok(element.expect("no filter is set")),
+                Err(err) => Err(err.to_string()),
+            }
+        });
+        Some(decoded.as_ref().map_err(|err| err.as_str().into()))
+    }
+
+    /// Returns whether the element at `index` has already been decoded.
+    pub fn is_decoded(&self, index: usize) -> bool {
+        self.elements
+            .get(index)
+            .is_some_and(|element| element.decoded.get().is_some())
+    }
+
+    /// Decodes every element, failing on the first malformed one.
+    pub fn decode_all(&self) -> Result<Vec<&JtlElement>, Box<dyn Error>> {
+        (0..self.len())
+            .map(|index| self.get(index).expect("index in bounds"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = r#"DOCTYPE=JTL
+>>>ENV;
+>>>foo=bar;
+>>>BEGIN;
+>a="1">first>$env:foo; >a="2">second>two;
+>ab;
+>>>END;"#;
+
+    #[test]
+    fn test_parse_lazy() {
+        let doc = parse_lazy(TEXT).unwrap();
+        assert_eq!(doc.len(), 3);
+        assert_eq!(doc.source(1), Some(r#">a="2">second>two"#));
+        assert_eq!(&TEXT[doc.span(1).unwrap()], r#">a="2">second>two"#);
+        assert!(!doc.is_decoded(0));
+
+        let first = doc.get(0).unwrap().unwrap();
+        assert_eq!(first.content, "bar");
+        assert!(doc.is_decoded(0));
+        assert!(!doc.is_decoded(1));
+
+        let err = doc.get(2).unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "invalid element format: too short");
+        assert!(doc.decode_all().is_err());
+        assert!(doc.get(3).is_none());
+    }
+}
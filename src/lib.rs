@@ -1,80 +1,471 @@
-use regex::Regex;
-use serde_json::{self, Value};
+#[cfg(feature = "json")]
+use serde_json::Value;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::error::Error;
 
+use scan::Defaults;
+
+mod access;
+mod anchor;
+mod annotation;
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "async")]
+mod async_read;
+#[cfg(feature = "avro")]
+mod avro;
+/// Individual parser stages, exposed for benchmarks with the `bench` feature.
+///
+/// These are not a stable API: they follow the parser's internals and change
+/// whenever it does.
+#[cfg(feature = "bench")]
+pub mod bench;
+mod borrowed;
+mod builder;
+mod cache;
+mod canonical;
+#[cfg(feature = "cbor")]
+mod cbor;
+mod combine;
+/// Conversions between documents, JSON, serde types and other formats, and
+/// operations that build one document from others.
+pub mod convert;
+mod cst;
+mod de;
+mod deadline;
+mod diff;
+mod digest;
+mod doctor;
+mod document;
+mod edit;
+mod element;
+mod encoding;
+/// Env variables: finding, rendering, rewriting and redacting references.
+pub mod env;
+mod error;
+mod explain;
+mod file;
+mod format;
+mod fragment;
+mod frame;
+mod generate;
+mod index;
+mod interp;
+mod iter;
+mod lang;
+mod lazy;
+mod limits;
+mod list;
+mod log;
+mod macros;
+mod mapping;
+#[cfg(feature = "markdown")]
+mod markdown;
+mod memory;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+mod options;
+mod order;
+/// Every way to parse JTL, with its options and errors.
+pub mod parser;
+/// The types and functions most programs need, for a glob import.
+///
+/// ```
+/// use jtl_rs::prelude::*;
+///
+/// let document = parse_document("DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">a>x;\n>>>END;")?;
+/// assert_eq!(document.elements[0], JtlElement::new("a", "x").with_attr("n", "1"));
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub mod prelude;
+#[cfg(feature = "json")]
+mod pretty;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+mod provenance;
+/// Inspecting documents: search, schemas, diffs, statistics and health checks.
+pub mod query;
+mod reader;
+mod redact;
+mod render;
+mod roundtrip;
+mod sample;
+mod scan;
+mod schema;
+mod search;
+mod ser;
+mod store;
+mod stream;
+#[cfg(feature = "tail")]
+mod tail;
+mod value;
+mod visit;
+mod warning;
+mod write;
+/// Writing JTL text and files.
+pub mod writer;
+
+pub use access::AttrError;
+#[cfg(feature = "archive")]
+pub use archive::{to_archive, ArchivedDocument, ArchivedElement};
+#[cfg(feature = "async")]
+pub use async_read::{parse_async, AsyncBufRead, AsyncElements};
+#[cfg(feature = "avro")]
+pub use avro::AvroSchema;
+pub use borrowed::{parse_borrowed, BorrowedElement};
+pub use builder::DocumentBuilder;
+pub use canonical::canonicalize;
+#[cfg(feature = "cbor")]
+pub use cbor::to_cbor;
+pub use combine::{concat, shard, split_by, EnvConflict};
+pub use cst::{CstDocument, CstNode, NodeKind};
+pub use de::{
+    from_document, from_element, from_elements, from_str, DeError, ElementsDeserializer,
+    JtlDeserializer,
+};
+pub use deadline::{parse_with_deadline, parse_with_deadline_options, PartialParse, TimedOut};
+pub use diff::{diff, merge, Difference};
+pub use doctor::{doctor, doctor_with, Category, Finding, Report, Severity};
+pub use document::JtlDocument;
+pub use edit::{ChangeEvent, Transaction};
+pub use element::JtlElement;
+pub use encoding::{Newline, TextFormat};
+pub use error::{FileError, JtlError};
+pub use explain::{explain, Explanation, Stage};
+pub use file::{append_to_file, with_locked_document, Appender};
+pub use format::format_source;
+pub use fragment::{extract, inject, missing_env, Position};
+pub use frame::{read_frames, Frames, JtlFrameDecoder};
+pub use generate::{generate, GenOptions};
+pub use index::IndexedFile;
+pub use iter::{parse_iter, parse_iter_with, ParseIter};
+#[cfg(feature = "derive")]
+pub use jtl_derive::JtlElement;
+pub use lang::Lang;
+pub use lazy::{parse_lazy, LazyDocument};
+#[cfg(feature = "json")]
+pub use limits::largest_elements;
+pub use limits::{ElementSize, Limits};
+pub use log::{first_out_of_order, sort_records, LogRecord};
+#[doc(hidden)]
+pub use macros::__check_jtl;
+pub use mapping::ElementMapping;
+#[doc(hidden)]
+pub use mapping::{__parse_field, __required_attr};
+#[cfg(feature = "markdown")]
+pub use markdown::markdown_to_html;
+pub use memory::MemoryUsage;
+#[cfg(feature = "msgpack")]
+pub use msgpack::to_msgpack;
+pub use options::{AttrCase, DoctypeCheck, DuplicateEnv, ParseOptions};
+#[cfg(feature = "json")]
+pub use order::stringify_ordered;
+pub use order::AttrOrder;
+#[cfg(feature = "json")]
+pub use pretty::{stringify_pretty, stringify_with, StringifyOptions};
+#[cfg(feature = "protobuf")]
+pub use protobuf::{FieldType, MessageDescriptor};
+pub use provenance::Origin;
+pub use reader::{Event, Reader};
+pub use redact::{redact, RedactRules};
+pub use render::{env_references, render, rewrite_env};
+pub use roundtrip::parse_strict_roundtrip;
+pub use schema::{Schema, Violation};
+pub use search::{Matcher, SearchHit};
+pub use ser::{to_element, to_elements, to_string, JtlSerializer, SerError};
+pub use store::JtlStore;
+#[cfg(feature = "json")]
+pub use stream::{convert_stream, Format};
+pub use stream::{
+    parse_chunks, parse_from, parse_reader, Checkpoint, Chunks, ReaderElements, Resumed,
+};
+#[cfg(feature = "tail")]
+pub use tail::{tail, Tail};
+pub use value::JtlValue;
+pub use visit::{parse_with_visitor, parse_with_visitor_options, JtlVisitor};
+pub use warning::Warning;
+pub use write::JtlWriter;
+
 /// Parses JTL content into a structured vector.
+///
+/// Attributes declared before `>>>BEGIN;` with `>>>DEFAULTS type="user";` are
+/// added to every element that does not set them itself. Elements still need
+/// one attribute of their own.
+#[cfg(feature = "json")]
 pub fn parse(text: &str) -> Result<Vec<Value>, Box<dyn Error>> {
-    let mut result: Vec<Value> = Vec::new();
-    let lines: Vec<&str> = text.lines().collect();
+    parse_with_options(text, &ParseOptions::default())
+}
 
-    if lines.is_empty() || !lines[0].contains("DOCTYPE=JTL") {
-        return Err("invalid JTL document: missing DOCTYPE".into());
-    }
+/// Reads and parses the JTL file at `path` like [`parse`].
+///
+/// Errors name the file and, for parse errors, the line, as in
+/// `config.jtl:4: invalid element format: too short`.
+#[cfg(feature = "json")]
+pub fn parse_file(path: impl AsRef<std::path::Path>) -> Result<Vec<Value>, FileError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|err| FileError::new(path, err))?;
+    parse(&text).map_err(|err| FileError::new(path, error::sendable(err)))
+}
 
-    let mut in_body = false;
-    let mut in_env = false;
-    let mut current_env: HashMap<String, String> = HashMap::new();
+/// Parses JTL content into a [`JtlDocument`], keeping the environment and
+/// document metadata that [`parse`] drops. The same as [`JtlDocument::parse`].
+pub fn parse_document(text: &str) -> Result<JtlDocument, Box<dyn Error>> {
+    JtlDocument::parse(text)
+}
 
-    for line in lines {
-        let line = line.trim();
-        if line.is_empty()
-            || line.starts_with("/*")
-            || line.starts_with("*/")
-            || line.starts_with(">//>")
-        {
-            continue;
-        }
+/// Parses JTL content into a structured vector, applying `options`.
+#[cfg(feature = "json")]
+pub fn parse_with_options(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<Vec<Value>, Box<dyn Error>> {
+    let values = parse_values_with(text, options)?;
+    Ok(values.into_iter().map(Value::from).collect())
+}
 
-        if line == ">>>ENV;" {
-            in_env = true;
-            continue;
-        }
-        if line == ">>>BEGIN;" {
-            in_env = false;
-            in_body = true;
-            continue;
-        }
-        if line == ">>>END;" {
-            in_body = false;
-            continue;
-        }
+/// Parses JTL content into [`JtlValue`]s in the shape of [`parse`], without
+/// going through `serde_json`.
+pub fn parse_values(text: &str) -> Result<Vec<JtlValue>, Box<dyn Error>> {
+    parse_values_with(text, &ParseOptions::default())
+}
 
-        // Handle multiple declarations per line.
-        let declarations: Vec<&str> = line.split(';').collect();
-        for decl in declarations {
-            let decl = decl.trim();
-            if decl.is_empty() || decl.starts_with(">//>") {
-                continue;
-            }
+/// Like [`parse_values`], applying `options`.
+pub fn parse_values_with(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<Vec<JtlValue>, Box<dyn Error>> {
+    let mut contents = options.cache_env.then(value::Interner::default);
+    parse_iter_with(text, options)
+        .map(|element| Ok(value::element_value(&element?, options, contents.as_mut())))
+        .collect()
+}
 
-            if in_env && decl.starts_with(">>>") {
-                let content = &decl[3..];
-                if let Some(eq_index) = content.find('=') {
-                    let var_name = content[..eq_index].trim();
-                    let var_value = content[eq_index + 1..].trim();
-                    current_env.insert(var_name.to_string(), var_value.to_string());
-                }
-            } else if in_body && decl.starts_with('>') {
-                if decl.len() < 5 {
-                    return Err("invalid element format: too short".into());
+/// Parses JTL content like [`parse_with_options`], also returning warnings
+/// about questionable input that was accepted.
+#[cfg(feature = "json")]
+pub fn parse_with_warnings(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<(Vec<Value>, Vec<Warning>), Box<dyn Error>> {
+    let mut warnings = Vec::new();
+    let document = parse_document_warn(text, options, &mut warnings)?;
+    let values = document
+        .elements
+        .iter()
+        .map(|element| Value::from(value::element_value(element, options, None)))
+        .collect();
+    Ok((values, warnings))
+}
+
+/// Parses JTL content into a document, applying `options`.
+pub(crate) fn parse_document_with(
+    text: &str,
+    options: &ParseOptions,
+) -> Result<JtlDocument, Box<dyn Error>> {
+    parse_document_warn(text, options, &mut Vec::new())
+}
+
+fn parse_document_warn(
+    text: &str,
+    options: &ParseOptions,
+    warnings: &mut Vec<Warning>,
+) -> Result<JtlDocument, Box<dyn Error>> {
+    let mut iter = parse_iter_with(text, options);
+    let elements = iter.by_ref().collect::<Result<Vec<_>, _>>()?;
+    warnings.append(&mut iter.warnings);
+    Ok(iter.into_document(elements, TextFormat::detect(text)))
+}
+
+/// Converts a vector to a compact JSON string; see [`stringify_pretty`] for
+/// indented output.
+#[cfg(feature = "json")]
+pub fn stringify(data: &Vec<Value>) -> Result<String, serde_json::Error> {
+    serde_json::to_string(data)
+}
+
+/// Builds a document from a JSON array of elements in the shape produced by
+/// [`parse`], the reverse of parsing a document and calling [`stringify`].
+///
+/// Each object needs a `KEY` and a `Content` (or `Contents`) field; its other
+/// fields become attributes. Write the result with [`JtlDocument::to_jtl`].
+#[cfg(feature = "json")]
+pub fn from_json(json: &Value) -> Result<JtlDocument, Box<dyn Error>> {
+    let items = json
+        .as_array()
+        .ok_or("invalid JSON: expected an array of elements")?;
+    document_from_values(items, &HashMap::new())
+}
+
+#[cfg(feature = "json")]
+fn document_from_values(
+    data: &[Value],
+    env: &HashMap<String, String>,
+) -> Result<JtlDocument, Box<dyn Error>> {
+    Ok(JtlDocument {
+        env: env.clone(),
+        elements: data
+            .iter()
+            .map(JtlElement::from_value)
+            .collect::<Result<_, _>>()?,
+        ..JtlDocument::default()
+    })
+}
+
+/// Converts elements in the shape produced by [`parse`] back to JTL text that
+/// [`parse`] accepts, with the `DOCTYPE` line and `>>>BEGIN;`/`>>>END;` block.
+///
+/// Parsed values no longer know which variables their content came from, so
+/// the output has no `>>>ENV;` block; use [`to_jtl_with_env`] or
+/// [`JtlDocument::to_jtl`] to keep one.
+#[cfg(feature = "json")]
+pub fn to_jtl(data: &[Value]) -> Result<String, Box<dyn Error>> {
+    to_jtl_with_env(data, &HashMap::new())
+}
+
+/// Like [`to_jtl`], declaring `env` in an `>>>ENV;` block sorted by name.
+#[cfg(feature = "json")]
+pub fn to_jtl_with_env(
+    data: &[Value],
+    env: &HashMap<String, String>,
+) -> Result<String, Box<dyn Error>> {
+    Ok(document_from_values(data, env)?.to_jtl()?)
+}
+
+/// Parses a single JTL element, or returns `None` if the filter skips it.
+pub(crate) fn parse_element(
+    line: &str,
+    env: &HashMap<String, String>,
+    defaults: &Defaults,
+    options: &ParseOptions,
+) -> Result<Option<JtlElement>, Box<dyn Error>> {
+    parse_element_with(line, env, defaults, None, options)
+}
+
+/// Parses a single JTL element, resolving anchors against `anchors` if given.
+pub(crate) fn parse_element_with(
+    line: &str,
+    env: &HashMap<String, String>,
+    defaults: &Defaults,
+    anchors: Option<&mut HashMap<String, String>>,
+    options: &ParseOptions,
+) -> Result<Option<JtlElement>, Box<dyn Error>> {
+    let RawElement {
+        key: id,
+        attrs: mut attributes,
+        content,
+    } = parse_raw_element(line)?;
+    defaults.apply(&mut attributes);
+    if options.reject_case_duplicates {
+        let mut seen: HashMap<String, &str> = HashMap::new();
+        for (name, _) in &attributes {
+            match seen.insert(name.to_lowercase(), name) {
+                Some(other) if other != *name => {
+                    return Err(format!(
+                        "invalid element `{}`: attributes `{}` and `{}` differ only in case",
+                        id, other, name
+                    )
+                    .into())
                 }
-                let element_map = parse_element(decl, &current_env)?;
-                result.push(Value::Object(element_map));
+                _ => {}
             }
         }
     }
+    let folded: Vec<(Cow<str>, &str)> = attributes
+        .into_iter()
+        .map(|(name, value)| {
+            let name = match options.attr_case {
+                AttrCase::Lowercase if name.chars().any(char::is_uppercase) => {
+                    Cow::Owned(name.to_lowercase())
+                }
+                _ => Cow::Borrowed(name),
+            };
+            let value = if options.trim_values {
+                value.trim()
+            } else {
+                value
+            };
+            (name, value)
+        })
+        .collect();
+    let attributes: Vec<(&str, &str)> = folded
+        .iter()
+        .map(|(name, value)| (options.renamed(name), *value))
+        .collect();
+
+    // Anchors are recorded even for elements the filter skips.
+    let content = match anchors {
+        Some(anchors) => anchor::resolve(content, anchors)
+            .map_err(|err| format!("invalid element `{}`: {}", id, err))?,
+        None => Cow::Borrowed(content),
+    };
+
+    if let Some(filter) = &options.filter {
+        if !(filter.0)(id, &attributes) {
+            return Ok(None);
+        }
+    }
 
-    Ok(result)
+    // Replace environment variable if needed.
+    let content = match env_reference(&content).and_then(|name| env.get(name)) {
+        Some(val) => Cow::Borrowed(val.as_str()),
+        None => interp::interpolate(&content, env, options)
+            .map_err(|err| format!("invalid element `{}`: {}", id, err))?,
+    };
+    let processor = options.processors.iter().find(|(attr, value, _)| {
+        attributes
+            .iter()
+            .any(|(name, found)| name == attr && found == value)
+    });
+    let content = match processor {
+        Some((_, _, process)) => Cow::Owned(
+            (process.0)(&content).map_err(|err| format!("invalid element `{}`: {}", id, err))?,
+        ),
+        None => content,
+    };
+    options
+        .limits
+        .check_element(id, attributes.len(), &content)?;
+
+    let field = |name: &str, value: &str| {
+        if options.keeps(name) {
+            value.to_string()
+        } else {
+            String::new()
+        }
+    };
+    let attrs = attributes
+        .into_iter()
+        .filter(|(name, _)| options.keeps(name))
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    Ok(Some(JtlElement {
+        key: field("KEY", id),
+        attrs,
+        content: field("Content", &content),
+        annotations: Vec::new(),
+        line: None,
+        source: None,
+    }))
 }
 
-/// Converts a vector to a JSON string.
-pub fn stringify(data: &Vec<Value>) -> Result<String, serde_json::Error> {
-    serde_json::to_string(data)
+/// An element declaration split into slices of the source line, before env
+/// substitution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawElement<'a> {
+    /// The element's key.
+    pub key: &'a str,
+    /// Attributes in source order.
+    pub attrs: Vec<(&'a str, &'a str)>,
+    /// The content as written.
+    pub content: &'a str,
 }
 
-/// Parses a single JTL element.
-fn parse_element(line: &str, env: &HashMap<String, String>) -> Result<serde_json::Map<String, Value>, Box<dyn Error>> {
+/// Splits an element declaration without substituting the environment.
+pub(crate) fn parse_raw_element(line: &str) -> Result<RawElement<'_>, Box<dyn Error>> {
+    if line.len() < 5 {
+        return Err("invalid element format: too short".into());
+    }
     let line = line
         .strip_prefix('>')
         .ok_or("invalid element format: missing '>' prefix")?;
@@ -83,19 +474,14 @@ fn parse_element(line: &str, env: &HashMap<String, String>) -> Result<serde_json
         return Err("invalid element format: missing separator".into());
     }
 
-    let attr_regex = Regex::new(r#"(\w+)="([^"]+)""#)?;
-    let captures: Vec<_> = attr_regex.captures_iter(line).collect();
-    if captures.is_empty() {
+    let attrs: Vec<(&str, &str)> = scan::ATTR_REGEX
+        .captures_iter(line)
+        .map(|cap| (cap.get(1).unwrap().as_str(), cap.get(2).unwrap().as_str()))
+        .collect();
+    if attrs.is_empty() {
         return Err("invalid element format: no attributes found".into());
     }
 
-    let mut element_map = serde_json::Map::new();
-    for cap in captures {
-        let key = cap.get(1).unwrap().as_str();
-        let value = cap.get(2).unwrap().as_str();
-        element_map.insert(key.to_string(), Value::String(value.to_string()));
-    }
-
     // Find the first occurrence of '>' to separate attributes from content.
     let content_start = line
         .find('>')
@@ -111,24 +497,24 @@ fn parse_element(line: &str, env: &HashMap<String, String>) -> Result<serde_json
     if parts.len() != 2 || parts[0].is_empty() || parts[1].is_empty() {
         return Err("invalid element format: malformed content".into());
     }
-    let id = parts[0].to_string();
-    let mut content = parts[1].to_string();
+    Ok(RawElement {
+        key: parts[0],
+        attrs,
+        content: parts[1],
+    })
+}
 
-    // Replace environment variable if needed.
+/// Returns the variable named by content of the form `$env:NAME`.
+pub(crate) fn env_reference(content: &str) -> Option<&str> {
     if content.starts_with("$env:") {
-        let env_var = content.trim_start_matches("$env:");
-        if let Some(val) = env.get(env_var) {
-            content = val.clone();
-        }
+        Some(content.trim_start_matches("$env:"))
+    } else {
+        None
     }
-    element_map.insert("KEY".to_string(), Value::String(id));
-    element_map.insert("Content".to_string(), Value::String(content.clone()));
-    element_map.insert("Contents".to_string(), Value::String(content));
-
-    Ok(element_map)
 }
 
 /// Extracts environment variables from JTL text.
+#[cfg(feature = "json")]
 pub fn parse_env(text: &str) -> Result<HashMap<String, Value>, Box<dyn Error>> {
     let mut env_map: HashMap<String, Value> = HashMap::new();
     let lines: Vec<&str> = text.lines().collect();
@@ -160,8 +546,7 @@ pub fn parse_env(text: &str) -> Result<HashMap<String, Value>, Box<dyn Error>> {
             let declarations: Vec<&str> = line.split(';').collect();
             for decl in declarations {
                 let decl = decl.trim();
-                if decl.starts_with(">>>") {
-                    let content = &decl[3..];
+                if let Some(content) = decl.strip_prefix(">>>") {
                     if let Some(eq_index) = content.find('=') {
                         let var_name = content[..eq_index].trim();
                         let var_value = content[eq_index + 1..].trim();
@@ -178,8 +563,12 @@ pub fn parse_env(text: &str) -> Result<HashMap<String, Value>, Box<dyn Error>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
 
+    fn elements(text: &str, options: &ParseOptions) -> Result<Vec<JtlElement>, JtlError> {
+        parse_iter_with(text, options).collect()
+    }
+
+    #[cfg(feature = "json")]
     const SAMPLE_JTL: &str = r#"DOCTYPE=JTL
 >>>ENV;
 >>>foo=bar;
@@ -187,19 +576,21 @@ mod tests {
 >element_id key="value">element_id>$env:foo;
 >>>END;"#;
 
+    #[cfg(feature = "json")]
     #[test]
     fn test_parse() {
         let parsed = parse(SAMPLE_JTL).expect("Parsing should succeed");
         assert!(!parsed.is_empty());
 
         // Check that the parsed element contains the expected fields.
-        let element = parsed.get(0).unwrap();
+        let element = parsed.first().unwrap();
         let obj = element.as_object().expect("Element should be an object");
         assert_eq!(obj.get("key").unwrap(), "value");
         assert_eq!(obj.get("Content").unwrap(), "bar");
         assert_eq!(obj.get("Contents").unwrap(), "bar");
     }
 
+    #[cfg(feature = "json")]
     #[test]
     fn test_parse_env() {
         let env_vars = parse_env(SAMPLE_JTL).expect("Parsing env should succeed");
@@ -207,6 +598,7 @@ mod tests {
         assert_eq!(env_vars.get("foo").unwrap(), "bar");
     }
 
+    #[cfg(feature = "json")]
     #[test]
     fn test_stringify() {
         // Create a sample vector.
@@ -221,6 +613,7 @@ mod tests {
         assert!(parsed_json.get(0).is_some());
     }
 
+    #[cfg(feature = "json")]
     #[test]
     fn test_missing_doctype() {
         let invalid_jtl = r#"No DOCTYPE here
@@ -234,6 +627,7 @@ mod tests {
         assert_eq!(err.to_string(), "invalid JTL document: missing DOCTYPE");
     }
 
+    #[cfg(feature = "json")]
     #[test]
     fn test_element_too_short() {
         // An element line that is too short.
@@ -244,4 +638,360 @@ mod tests {
         let err = parse(jtl).unwrap_err();
         assert_eq!(err.to_string(), "invalid element format: too short");
     }
+
+    #[test]
+    fn test_element_limits() {
+        let jtl = r#"DOCTYPE=JTL
+>>>BEGIN;
+>a="1" b="2" c="3">wide>short;
+>a="1">long>this content is too long;
+>>>END;"#;
+        let err = elements(jtl, &ParseOptions::new().max_attributes(2)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "element limit exceeded: `wide` has 3 attributes (max 2)"
+        );
+
+        let err = elements(jtl, &ParseOptions::new().max_content_bytes(10)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "element limit exceeded: `long` has 24 content bytes (max 10)"
+        );
+
+        let parsed = elements(jtl, &ParseOptions::new().max_attributes(3)).unwrap();
+        assert_eq!(parsed.len(), 2);
+    }
+
+    #[test]
+    fn test_filter() {
+        let jtl = r#"DOCTYPE=JTL
+>>>BEGIN;
+>type="user">alice>admin;
+>type="group">staff>everyone;
+>type="user">bob>member;
+>>>END;"#;
+        let options = ParseOptions::new()
+            .filter(|key, attrs| key != "bob" && attrs.contains(&("type", "user")));
+        let parsed = elements(jtl, &options).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].key, "alice");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_projection() {
+        let jtl = r#"DOCTYPE=JTL
+>>>BEGIN;
+>id="1" status="up" region="eu" owner="ops">web>frontend;
+>id="2" region="us">db>database;
+>>>END;"#;
+        let parsed =
+            parse_with_options(jtl, &ParseOptions::new().project(&["id", "status"])).unwrap();
+        assert_eq!(parsed[0], serde_json::json!({ "id": "1", "status": "up" }));
+        assert_eq!(parsed[1], serde_json::json!({ "id": "2" }));
+
+        let parsed =
+            parse_with_options(jtl, &ParseOptions::new().project(&["KEY", "Content"])).unwrap();
+        assert_eq!(
+            parsed[1],
+            serde_json::json!({ "KEY": "db", "Content": "database", "Contents": "database" })
+        );
+    }
+
+    #[test]
+    fn test_doctype_check() {
+        let body = "\n>>>BEGIN;\n>a=\"1\">k>v;\n>>>END;";
+        let check = |first: &str, options: ParseOptions| {
+            elements(&format!("{}{}", first, body), &options)
+                .map(|_| ())
+                .map_err(|err| err.to_string())
+        };
+        let exact = || ParseOptions::new().doctype(DoctypeCheck::Exact);
+
+        assert_eq!(check("xDOCTYPE=JTLx", ParseOptions::new()), Ok(()));
+        assert_eq!(
+            check(
+                "xDOCTYPE=JTL",
+                ParseOptions::new().doctype(DoctypeCheck::Prefix)
+            ),
+            Err("invalid JTL document: missing DOCTYPE".to_string())
+        );
+        assert_eq!(
+            check(
+                "DOCTYPE=JTLx",
+                ParseOptions::new().doctype(DoctypeCheck::Prefix)
+            ),
+            Err("invalid JTL document: unexpected text after DOCTYPE".to_string())
+        );
+        assert_eq!(
+            check(
+                "DOCTYPE=JTL 2 extra",
+                ParseOptions::new().doctype(DoctypeCheck::Prefix)
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check("DOCTYPE=JTL 2 extra", exact()),
+            Err("invalid JTL document: unexpected text after DOCTYPE".to_string())
+        );
+        assert_eq!(check("DOCTYPE=JTL  ", exact()), Ok(()));
+        assert_eq!(
+            check("DOCTYPE=JTL 1", exact().doctype_versions(&["1"])),
+            Ok(())
+        );
+        assert_eq!(
+            check("DOCTYPE=JTL", exact().doctype_versions(&["1"])),
+            Ok(())
+        );
+        assert_eq!(
+            check("DOCTYPE=JTL 3", exact().doctype_versions(&["1", "2"])),
+            Err("invalid JTL document: unsupported DOCTYPE version `3`".to_string())
+        );
+    }
+
+    #[test]
+    fn test_trailing_content() {
+        let jtl =
+            "DOCTYPE=JTL\n>>>BEGIN;\n>a=\"1\">k>v;\n>>>END;\n\n/* done */\n>a=\"2\">pasted>twice;";
+        assert_eq!(elements(jtl, &ParseOptions::new()).unwrap().len(), 1);
+
+        let strict = ParseOptions::new().reject_trailing_content(true);
+        let err = elements(jtl, &strict).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid JTL document: content after >>>END; on line 7: `>a=\"2\">pasted>twice;`"
+        );
+        let complete = "DOCTYPE=JTL\n>>>BEGIN;\n>a=\"1\">k>v;\n>>>END;\n/* done */\n";
+        assert_eq!(elements(complete, &strict).unwrap().len(), 1);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_duplicate_env() {
+        let jtl = "DOCTYPE=JTL\n>>>ENV;\n>>>foo=one;\n>>>foo=two;\n>>>BEGIN;\n>a=\"1\">k>$env:foo;\n>>>END;";
+        let with = |policy| {
+            parse_with_warnings(jtl, &ParseOptions::new().duplicate_env(policy))
+                .map(|(values, warnings)| (values[0]["Content"].clone(), warnings))
+                .map_err(|err| err.to_string())
+        };
+
+        assert_eq!(with(DuplicateEnv::LastWins), Ok(("two".into(), vec![])));
+        assert_eq!(with(DuplicateEnv::FirstWins), Ok(("one".into(), vec![])));
+        let (content, warnings) = with(DuplicateEnv::Warn).unwrap();
+        assert_eq!(content, "two");
+        assert_eq!(
+            warnings[0].to_string(),
+            "line 4: env variable `foo` redefined"
+        );
+        assert_eq!(
+            with(DuplicateEnv::Error),
+            Err("invalid JTL document: env variable `foo` redefined on line 4".to_string())
+        );
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let jtl = "DOCTYPE=JTL\n>>>ENV;\n>>>user=alice;\n>>>BEGIN;\n\
+                   >a=\"1\">greeting>Hi ${env:user|upper}, from ${env:team|default:ops};\n\
+                   >a=\"2\">broken>${env:nobody};\n>>>END;";
+        let parsed = elements(jtl, &ParseOptions::new()).unwrap();
+        assert_eq!(parsed[1].content, "${env:nobody}");
+        let err = elements(&jtl.replace("nobody", "user|shout"), &ParseOptions::new()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid element `broken`: unknown function `shout`"
+        );
+
+        let jtl = jtl.replace("nobody", "user|lower|wrap");
+        let options = ParseOptions::new().function("wrap", |value, _| Ok(format!("[{}]", value)));
+        let parsed = elements(&jtl, &options).unwrap();
+        assert_eq!(parsed[0].content, "Hi ALICE, from ops");
+        assert_eq!(parsed[1].content, "[alice]");
+    }
+
+    #[test]
+    fn test_anchors() {
+        let jtl = r#"DOCTYPE=JTL
+>>>BEGIN;
+>env="prod">tls>&cert -----BEGIN CERTIFICATE-----;
+>env="staging">tls>*cert;
+>>>END;"#;
+        let parsed = elements(jtl, &ParseOptions::new()).unwrap();
+        assert_eq!(parsed[1].content, "*cert");
+
+        let options = ParseOptions::new().anchors(true);
+        let parsed = elements(jtl, &options).unwrap();
+        assert_eq!(parsed[0].content, "-----BEGIN CERTIFICATE-----");
+        assert_eq!(parsed[1].content, "-----BEGIN CERTIFICATE-----");
+
+        let options = options.filter(|_, attrs| attrs.contains(&("env", "staging")));
+        let parsed = elements(jtl, &options).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].content, "-----BEGIN CERTIFICATE-----");
+    }
+
+    #[test]
+    fn test_validate_element() {
+        let jtl = r#"DOCTYPE=JTL
+>>>BEGIN;
+>port="443">https>secure;
+>port="99999">broken>oops;
+>>>END;"#;
+        let options = ParseOptions::new().validate_element(|element| {
+            let port = element.attrs.iter().find(|(name, _)| name == "port");
+            match port.map(|(_, value)| value.parse::<u16>()) {
+                Some(Ok(port)) if port > 0 => Ok(()),
+                _ => Err("port must be 1-65535".to_string()),
+            }
+        });
+        let err = elements(jtl, &options).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid element `broken` on line 4: port must be 1-65535"
+        );
+        let options = options.filter(|key, _| key != "broken");
+        assert_eq!(elements(jtl, &options).unwrap().len(), 1);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_to_jtl() {
+        let jtl = r#"DOCTYPE=JTL
+>>>BEGIN;
+>b="2" a="1">first>hello;
+>n="3">second>world;
+>>>END;"#;
+        let parsed = parse(jtl).unwrap();
+        let text = to_jtl(&parsed).unwrap();
+        assert!(text.starts_with("DOCTYPE=JTL\n>>>BEGIN;\n>a=\"1\" b=\"2\">first>hello;"));
+        assert_eq!(parse(&text).unwrap(), parsed);
+
+        let env = HashMap::from([("who".to_string(), "world".to_string())]);
+        let text = to_jtl_with_env(&parsed, &env).unwrap();
+        assert!(text.contains(">>>ENV;\n>>>who=world;\n"));
+
+        let err = to_jtl(&[serde_json::json!({ "KEY": "k", "a": "1" })]).unwrap_err();
+        assert_eq!(err.to_string(), "invalid element `k`: missing `Content`");
+        assert!(to_jtl(&[serde_json::json!({ "KEY": "k", "Content": "v" })]).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_list_attrs() {
+        let jtl = "DOCTYPE=JTL\n>>>BEGIN;\n>tags=\"[a, b ,c]\" empty=\"[]\" n=\"1\">k>v;\n>>>END;";
+        let options = ParseOptions::new().list_attrs(true);
+        let parsed = parse_with_options(jtl, &options).unwrap();
+        assert_eq!(parsed[0]["tags"], serde_json::json!(["a", "b", "c"]));
+        assert_eq!(parsed[0]["empty"], serde_json::json!([]));
+        assert_eq!(parsed[0]["n"], "1");
+        assert_eq!(parse(jtl).unwrap()[0]["tags"], "[a, b ,c]");
+
+        let text = to_jtl(&parsed).unwrap();
+        assert!(text.contains("tags=\"[a, b, c]\""));
+        assert_eq!(parse_with_options(&text, &options).unwrap(), parsed);
+    }
+
+    #[test]
+    fn test_attr_case() {
+        let jtl = "DOCTYPE=JTL\n>>>BEGIN;\n>Port=\" 80 \" TLS=\"on\">web>x;\n>>>END;";
+        let options = ParseOptions::new()
+            .attr_case(AttrCase::Lowercase)
+            .trim_attr_values(true)
+            .rename_attrs(&[("tls", "secure")]);
+        let parsed = elements(jtl, &options).unwrap();
+        assert_eq!(parsed[0].attr("port"), Some("80"));
+        assert_eq!(parsed[0].attr("secure"), Some("on"));
+        let parsed = elements(jtl, &ParseOptions::new()).unwrap();
+        assert_eq!(parsed[0].attr("Port"), Some(" 80 "));
+
+        let mixed = "DOCTYPE=JTL\n>>>BEGIN;\n>Port=\"80\" port=\"81\">web>x;\n>>>END;";
+        let strict = ParseOptions::new().reject_case_duplicates(true);
+        assert_eq!(
+            elements(mixed, &strict).unwrap_err().to_string(),
+            "invalid element `web`: attributes `Port` and `port` differ only in case"
+        );
+        assert!(elements(jtl, &strict).is_ok());
+    }
+
+    #[test]
+    fn test_defaults() {
+        let jtl = "DOCTYPE=JTL\n>>>DEFAULTS type=\"user\" active=\"true\";\n>>>BEGIN;\n\
+                   >name=\"ann\">u1>a;\n>name=\"bob\" active=\"false\">u2>b;\n>>>END;";
+        let document = JtlDocument::parse(jtl).unwrap();
+        let attrs: Vec<_> = document.elements[0]
+            .attrs
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        assert_eq!(attrs, ["name=ann", "type=user", "active=true"]);
+        assert_eq!(document.elements[1].attr("active"), Some("false"));
+        assert_eq!(document.elements[1].attr("type"), Some("user"));
+        assert_eq!(
+            parse_lazy(jtl).unwrap().get(1).unwrap().unwrap(),
+            &document.elements[1]
+        );
+
+        let empty = "DOCTYPE=JTL\n>>>DEFAULTS ;\n>>>BEGIN;\n>>>END;";
+        assert_eq!(
+            parse_document(empty).unwrap_err().to_string(),
+            "invalid JTL document: >>>DEFAULTS without attributes on line 2"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_from_json() {
+        let json = serde_json::json!([
+            { "KEY": "web", "Content": "nginx", "port": 80 },
+            { "KEY": "list", "Contents": "a b", "n": "2" },
+        ]);
+        let document = from_json(&json).unwrap();
+        assert_eq!(document.elements[0].attr("port"), Some("80"));
+        let text = document.to_jtl().unwrap();
+        assert_eq!(
+            from_json(&serde_json::to_value(parse(&text).unwrap()).unwrap()).unwrap(),
+            document
+        );
+        assert_eq!(
+            from_json(&serde_json::json!({ "KEY": "web" }))
+                .unwrap_err()
+                .to_string(),
+            "invalid JSON: expected an array of elements"
+        );
+    }
+
+    #[test]
+    fn test_process_content() {
+        let jtl = r#"DOCTYPE=JTL
+>>>ENV;
+>>>name=world;
+>>>BEGIN;
+>case="upper">greeting>$env:name;
+>case="fail">broken>x;
+>>>END;"#;
+        let options = ParseOptions::new()
+            .process_content("case", "upper", |content| Ok(content.to_uppercase()))
+            .process_content("case", "fail", |_| Err("no".to_string()));
+        let err = elements(jtl, &options).unwrap_err();
+        assert_eq!(err.to_string(), "invalid element `broken`: no");
+        let options = options.filter(|key, _| key != "broken");
+        let parsed = elements(jtl, &options).unwrap();
+        assert_eq!(parsed[0].content, "WORLD");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_rename_attrs() {
+        let jtl = r#"DOCTYPE=JTL
+>>>BEGIN;
+>colour="blue" size="2">sky>clear;
+>>>END;"#;
+        let options = ParseOptions::new()
+            .rename_attrs(&[("colour", "color")])
+            .project(&["color", "KEY"]);
+        let parsed = parse_with_options(jtl, &options).unwrap();
+        assert_eq!(parsed[0]["color"], "blue");
+        assert!(parsed[0].get("colour").is_none());
+        assert!(parsed[0].get("size").is_none());
+    }
 }
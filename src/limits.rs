@@ -0,0 +1,125 @@
+#[cfg(feature = "json")]
+use serde_json::Value;
+#[cfg(feature = "json")]
+use std::cmp::Reverse;
+
+/// Size caps enforced on each element while parsing.
+///
+/// A `None` cap is unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Limits {
+    /// Maximum number of attributes on a single element.
+    pub max_attributes: Option<usize>,
+    /// Maximum length of a single element's content, in bytes.
+    pub max_content_bytes: Option<usize>,
+}
+
+impl Limits {
+    /// Checks one element against the caps.
+    pub(crate) fn check_element(
+        &self,
+        key: &str,
+        attributes: usize,
+        content: &str,
+    ) -> Result<(), String> {
+        if let Some(max) = self.max_attributes {
+            if attributes > max {
+                return Err(format!(
+                    "element limit exceeded: `{}` has {} attributes (max {})",
+                    key, attributes, max
+                ));
+            }
+        }
+        if let Some(max) = self.max_content_bytes {
+            if content.len() > max {
+                return Err(format!(
+                    "element limit exceeded: `{}` has {} content bytes (max {})",
+                    key,
+                    content.len(),
+                    max
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Size summary of a single parsed element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ElementSize {
+    /// Position of the element in the parsed vector.
+    pub index: usize,
+    /// The element's `KEY`.
+    pub key: String,
+    /// Number of attributes, not counting `KEY` and the content fields.
+    pub attributes: usize,
+    /// Length of the element's content, in bytes.
+    pub content_bytes: usize,
+    /// Total bytes of attribute names, attribute values, key and content.
+    pub bytes: usize,
+}
+
+/// Reports the `n` largest elements, biggest first.
+#[cfg(feature = "json")]
+pub fn largest_elements(elements: &[Value], n: usize) -> Vec<ElementSize> {
+    let mut sizes: Vec<ElementSize> = elements
+        .iter()
+        .enumerate()
+        .filter_map(|(index, element)| element_size(index, element))
+        .collect();
+    sizes.sort_by_key(|size| Reverse(size.bytes));
+    sizes.truncate(n);
+    sizes
+}
+
+#[cfg(feature = "json")]
+fn element_size(index: usize, element: &Value) -> Option<ElementSize> {
+    let obj = element.as_object()?;
+    let key = obj.get("KEY").and_then(Value::as_str).unwrap_or_default();
+    let content = obj
+        .get("Content")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+
+    let mut attributes = 0;
+    let mut bytes = key.len() + content.len();
+    for (name, value) in obj {
+        if matches!(name.as_str(), "KEY" | "Content" | "Contents") {
+            continue;
+        }
+        attributes += 1;
+        bytes += name.len() + value.as_str().map_or(0, str::len);
+    }
+
+    Some(ElementSize {
+        index,
+        key: key.to_string(),
+        attributes,
+        content_bytes: content.len(),
+        bytes,
+    })
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_largest_elements() {
+        let jtl = r#"DOCTYPE=JTL
+>>>BEGIN;
+>a="1">small>x;
+>a="1" b="22">big>a much longer content string;
+>a="1">medium>medium content;
+>>>END;"#;
+        let parsed = crate::parse(jtl).unwrap();
+        let largest = largest_elements(&parsed, 2);
+        assert_eq!(largest.len(), 2);
+        assert_eq!(largest[0].key, "big");
+        assert_eq!(largest[0].index, 1);
+        assert_eq!(largest[0].attributes, 2);
+        assert_eq!(largest[1].key, "medium");
+    }
+}
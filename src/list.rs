@@ -0,0 +1,47 @@
+/// Reads an attribute value written as a list, `[a, b, c]`, into its items.
+///
+/// Items are separated by commas and trimmed; `[]` is the empty list. Returns
+/// `None` for values that are not in brackets.
+pub(crate) fn parse_list(value: &str) -> Option<Vec<&str>> {
+    let inner = value.strip_prefix('[')?.strip_suffix(']')?;
+    if inner.trim().is_empty() {
+        return Some(Vec::new());
+    }
+    Some(inner.split(',').map(str::trim).collect())
+}
+
+/// Writes items as a list attribute value, the inverse of [`parse_list`].
+///
+/// Fails for items that would read back differently: empty ones, ones with
+/// surrounding whitespace, and ones holding `,`, `[`, `]` or `"`.
+pub(crate) fn format_list<S: AsRef<str>>(items: &[S]) -> Result<String, String> {
+    let mut out = String::from("[");
+    for (index, item) in items.iter().enumerate() {
+        let item = item.as_ref();
+        if item.is_empty() || item.trim() != item || item.contains([',', '[', ']', '"']) {
+            return Err(format!("list item `{}` cannot be written", item));
+        }
+        if index > 0 {
+            out.push_str(", ");
+        }
+        out.push_str(item);
+    }
+    out.push(']');
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lists() {
+        assert_eq!(parse_list("[a, b ,c]"), Some(vec!["a", "b", "c"]));
+        assert_eq!(parse_list("[ ]"), Some(vec![]));
+        assert_eq!(parse_list("a, b"), None);
+        assert_eq!(format_list(&["a", "b c"]).unwrap(), "[a, b c]");
+        assert_eq!(format_list::<&str>(&[]).unwrap(), "[]");
+        assert!(format_list(&["a,b"]).is_err());
+        assert!(format_list(&[" a"]).is_err());
+    }
+}
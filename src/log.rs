@@ -0,0 +1,285 @@
+use std::error::Error;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::JtlElement;
+
+/// An element read as an event in a log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    /// When the event happened, parsed from the timestamp attribute.
+    pub timestamp: SystemTime,
+    /// The element's key.
+    pub key: String,
+    /// The remaining attributes in source order, followed by `Content`.
+    pub fields: Vec<(String, String)>,
+}
+
+impl LogRecord {
+    /// Reads `element` as a log record, taking its time from the
+    /// `timestamp_attr` attribute.
+    ///
+    /// The timestamp may be seconds since the Unix epoch, with an optional
+    /// fraction (`1700000000.25`), or an RFC 3339 date-time
+    /// (`2023-11-14T22:13:20Z`, `2023-11-14T23:13:20.5+01:00`).
+    pub fn from_element(
+        element: &JtlElement,
+        timestamp_attr: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let raw = element.attr(timestamp_attr).ok_or_else(|| {
+            format!(
+                "invalid log record: `{}` has no `{}` attribute",
+                element.key, timestamp_attr
+            )
+        })?;
+        let timestamp = parse_timestamp(raw).ok_or_else(|| {
+            format!(
+                "invalid log record: `{}` has malformed timestamp `{}`",
+                element.key, raw
+            )
+        })?;
+
+        let mut fields: Vec<(String, String)> = element
+            .attrs
+            .iter()
+            .filter(|(name, _)| name != timestamp_attr)
+            .cloned()
+            .collect();
+        fields.push(("Content".to_string(), element.content.clone()));
+        Ok(LogRecord {
+            timestamp,
+            key: element.key.clone(),
+            fields,
+        })
+    }
+
+    /// Reads every element as a log record, failing on the first bad timestamp.
+    pub fn from_elements(
+        elements: &[JtlElement],
+        timestamp_attr: &str,
+    ) -> Result<Vec<Self>, Box<dyn Error>> {
+        elements
+            .iter()
+            .map(|element| LogRecord::from_element(element, timestamp_attr))
+            .collect()
+    }
+
+    /// Returns the value of field `name`, if present.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Sorts records by timestamp, keeping the file order of simultaneous events.
+pub fn sort_records(records: &mut [LogRecord]) {
+    records.sort_by_key(|record| record.timestamp);
+}
+
+/// Returns the index of the first record that is older than the one before it.
+pub fn first_out_of_order(records: &[LogRecord]) -> Option<usize> {
+    records
+        .windows(2)
+        .position(|pair| pair[1].timestamp < pair[0].timestamp)
+        .map(|index| index + 1)
+}
+
+fn parse_timestamp(raw: &str) -> Option<SystemTime> {
+    let (secs, nanos) = if raw.contains('-') {
+        parse_rfc3339(raw)?
+    } else {
+        let (whole, fraction) = raw.split_once('.').unwrap_or((raw, ""));
+        if whole.is_empty() || !whole.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        (whole.parse().ok()?, parse_fraction(fraction)?)
+    };
+    if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos))
+    } else {
+        UNIX_EPOCH
+            .checked_sub(Duration::from_secs(secs.unsigned_abs()))?
+            .checked_add(Duration::from_nanos(nanos.into()))
+    }
+}
+
+/// Parses `YYYY-MM-DDTHH:MM:SS[.frac](Z|±HH:MM)` into epoch seconds and nanoseconds.
+fn parse_rfc3339(raw: &str) -> Option<(i64, u32)> {
+    let bytes = raw.as_bytes();
+    if bytes.len() < 20
+        || bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = raw.get(range)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    };
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    let mut rest = &raw[19..];
+    let mut nanos = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let end = fraction
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(fraction.len());
+        if end == 0 {
+            return None;
+        }
+        nanos = parse_fraction(&fraction[..end])?;
+        rest = &fraction[end..];
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.as_bytes().first()? {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let offset = rest.get(1..)?;
+            let (hours, minutes) = offset.split_once(':')?;
+            if hours.len() != 2 || minutes.len() != 2 {
+                return None;
+            }
+            let (hours, minutes): (i64, i64) = (hours.parse().ok()?, minutes.parse().ok()?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            sign * (hours * 3600 + minutes * 60)
+        }
+    };
+
+    let days = days_from_civil(year, month, day);
+    Some((
+        days * 86_400 + hour * 3600 + minute * 60 + second - offset,
+        nanos,
+    ))
+}
+
+/// Reads up to nine fractional digits as nanoseconds; later digits are dropped.
+fn parse_fraction(digits: &str) -> Option<u32> {
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let mut nanos = 0;
+    for position in 0..9 {
+        let digit = digits.as_bytes().get(position).map_or(0, |b| b - b'0');
+        nanos = nanos * 10 + u32::from(digit);
+    }
+    Some(nanos)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days between 1970-01-01 and the given date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let shifted_month = (month + 9) % 12;
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(secs: u64, nanos: u32) -> SystemTime {
+        UNIX_EPOCH + Duration::new(secs, nanos)
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1700000000"), Some(at(1_700_000_000, 0)));
+        assert_eq!(
+            parse_timestamp("1700000000.25"),
+            Some(at(1_700_000_000, 250_000_000))
+        );
+        assert_eq!(
+            parse_timestamp("2023-11-14T22:13:20Z"),
+            Some(at(1_700_000_000, 0))
+        );
+        assert_eq!(
+            parse_timestamp("2023-11-14T23:13:20.5+01:00"),
+            Some(at(1_700_000_000, 500_000_000))
+        );
+        assert_eq!(
+            parse_timestamp("2024-02-29T00:00:00Z"),
+            Some(at(1_709_164_800, 0))
+        );
+        assert_eq!(
+            parse_timestamp("1969-12-31T23:59:59Z"),
+            UNIX_EPOCH.checked_sub(Duration::from_secs(1))
+        );
+        for bad in [
+            "",
+            "soon",
+            "17e9",
+            "2023-02-29T00:00:00Z",
+            "2023-11-14T22:13:20",
+            "2023-11-14T25:00:00Z",
+        ] {
+            assert_eq!(parse_timestamp(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_log_records() {
+        let elements = [
+            JtlElement::new("login", "alice")
+                .with_attr("ts", "2023-11-14T22:13:25Z")
+                .with_attr("level", "info"),
+            JtlElement::new("logout", "alice").with_attr("ts", "1700000001"),
+        ];
+        let mut records = LogRecord::from_elements(&elements, "ts").unwrap();
+        assert_eq!(records[0].field("level"), Some("info"));
+        assert_eq!(records[0].field("Content"), Some("alice"));
+        assert_eq!(records[0].field("ts"), None);
+
+        assert_eq!(first_out_of_order(&records), Some(1));
+        sort_records(&mut records);
+        assert_eq!(records[0].key, "logout");
+        assert_eq!(first_out_of_order(&records), None);
+
+        let err =
+            LogRecord::from_element(&JtlElement::new("boot", "x").with_attr("ts", "later"), "ts")
+                .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid log record: `boot` has malformed timestamp `later`"
+        );
+        let err = LogRecord::from_element(&JtlElement::new("boot", "x"), "ts").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid log record: `boot` has no `ts` attribute"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+/// Embeds a JTL document that is checked at compile time.
+///
+/// The argument must be a constant string expression, such as a literal or
+/// `include_str!("config.jtl")`. Syntax errors fail the build; the document
+/// is then parsed when the expression runs, giving a [`JtlDocument`](crate::JtlDocument).
+///
+/// ```
+/// let doc = jtl_rs::jtl!(r#"DOCTYPE=JTL
+/// >>>ENV;
+/// >>>host=localhost;
+/// >>>BEGIN;
+/// >port="80">web>$env:host;
+/// >>>END;"#);
+/// assert_eq!(doc.elements[0].content, "localhost");
+/// ```
+///
+/// A typo is a compile error:
+///
+/// ```compile_fail
+/// let doc = jtl_rs::jtl!("DOCTYPE=JTL\n>>>BEGIN;\n>port=80>web>nginx;\n>>>END;");
+/// ```
+///
+/// The compile-time check covers the grammar: the `DOCTYPE` line and the
+/// shape of every element. Problems that depend on values, such as calling
+/// an unknown function in `${env:NAME|function}`, still panic when the
+/// document is parsed.
+#[macro_export]
+macro_rules! jtl {
+    ($text:expr) => {{
+        const TEXT: &str = $text;
+        const _: () = if let ::core::result::Result::Err(err) = $crate::__check_jtl(TEXT) {
+            ::core::panic!("{}", err)
+        };
+        $crate::JtlDocument::parse(TEXT).expect("jtl! document was checked at compile time")
+    }};
+}
+
+/// Checks the grammar of a JTL document in a `const` context, for [`jtl!`].
+///
+/// Mirrors the parser's line scanner and element splitting. Word characters
+/// in attribute names are approximated as ASCII letters, digits, `_` and any
+/// non-ASCII byte.
+#[doc(hidden)]
+pub const fn __check_jtl(text: &str) -> Result<(), &'static str> {
+    let bytes = text.as_bytes();
+    let (mut in_env, mut in_body) = (false, false);
+    let mut start = 0;
+    let mut first = true;
+    while start <= bytes.len() {
+        let mut end = start;
+        while end < bytes.len() && bytes[end] != b'\n' {
+            end += 1;
+        }
+        let (from, to) = trim(bytes, start, end);
+        if first {
+            if !contains(bytes, start, end, b"DOCTYPE=JTL") {
+                return Err("jtl!: invalid JTL document: missing DOCTYPE");
+            }
+            first = false;
+        } else if equals(bytes, from, to, b">>>ENV;") {
+            in_env = true;
+        } else if equals(bytes, from, to, b">>>BEGIN;") {
+            in_env = false;
+            in_body = true;
+        } else if equals(bytes, from, to, b">>>END;") {
+            in_body = false;
+        } else if !(starts_with(bytes, from, to, b"/*")
+            || starts_with(bytes, from, to, b"*/")
+            || starts_with(bytes, from, to, b">//>"))
+        {
+            // Declarations are separated by `;`.
+            let mut decl_start = from;
+            while decl_start <= to {
+                let mut decl_end = decl_start;
+                while decl_end < to && bytes[decl_end] != b';' {
+                    decl_end += 1;
+                }
+                let (from, to) = trim(bytes, decl_start, decl_end);
+                let is_env = in_env && starts_with(bytes, from, to, b">>>");
+                if from < to
+                    && !starts_with(bytes, from, to, b">//>")
+                    && in_body
+                    && !is_env
+                    && bytes[from] == b'>'
+                {
+                    if let Err(err) = check_element(bytes, from, to) {
+                        return Err(err);
+                    }
+                }
+                decl_start = decl_end + 1;
+            }
+        }
+        start = end + 1;
+    }
+    Ok(())
+}
+
+/// Checks one element declaration, without its `;`, like `parse_raw_element`.
+const fn check_element(bytes: &[u8], from: usize, to: usize) -> Result<(), &'static str> {
+    if to - from < 5 {
+        return Err("jtl!: invalid element format: too short");
+    }
+    let body = from + 1;
+    let Some(split) = find(bytes, body, to, b'>') else {
+        return Err("jtl!: invalid element format: missing separator");
+    };
+    if !has_attribute(bytes, body, to) {
+        return Err("jtl!: invalid element format: no attributes found");
+    }
+    let Some(key_end) = find(bytes, split + 1, to, b'>') else {
+        return Err("jtl!: invalid element format: malformed content");
+    };
+    if key_end == split + 1 || key_end + 1 == to {
+        return Err("jtl!: invalid element format: malformed content");
+    }
+    Ok(())
+}
+
+/// Returns whether `name="value"` occurs in `bytes[from..to]`.
+const fn has_attribute(bytes: &[u8], from: usize, to: usize) -> bool {
+    let mut i = from + 1;
+    while i + 1 < to {
+        let before = bytes[i - 1];
+        let word = before.is_ascii_alphanumeric() || before == b'_' || before >= 0x80;
+        if bytes[i] == b'=' && bytes[i + 1] == b'"' && word {
+            if let Some(close) = find(bytes, i + 2, to, b'"') {
+                if close > i + 2 {
+                    return true;
+                }
+            }
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn find(bytes: &[u8], from: usize, to: usize, byte: u8) -> Option<usize> {
+    let mut i = from;
+    while i < to {
+        if bytes[i] == byte {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+const fn trim(bytes: &[u8], mut from: usize, mut to: usize) -> (usize, usize) {
+    while from < to && bytes[from].is_ascii_whitespace() {
+        from += 1;
+    }
+    while to > from && bytes[to - 1].is_ascii_whitespace() {
+        to -= 1;
+    }
+    (from, to)
+}
+
+const fn starts_with(bytes: &[u8], from: usize, to: usize, prefix: &[u8]) -> bool {
+    if to - from < prefix.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < prefix.len() {
+        if bytes[from + i] != prefix[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn equals(bytes: &[u8], from: usize, to: usize, other: &[u8]) -> bool {
+    to - from == other.len() && starts_with(bytes, from, to, other)
+}
+
+const fn contains(bytes: &[u8], from: usize, to: usize, needle: &[u8]) -> bool {
+    let mut i = from;
+    while i + needle.len() <= to {
+        if starts_with(bytes, i, to, needle) {
+            return true;
+        }
+        i += 1;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_jtl() {
+        let good = [
+            "DOCTYPE=JTL\n>>>BEGIN;\n>a=\"1\">k>v;\n>>>END;",
+            "DOCTYPE=JTL\r\n>>>ENV;\r\n>>>x=1;\r\n>>>BEGIN;\r\n  >a=\"1\">k>v; >b=\"2\">l>w; >//> note\r\n>>>END;\r\n",
+            "DOCTYPE=JTL\n>>>BEGIN;\n/* >broken */\n>>>END;\n>not checked",
+        ];
+        for text in good {
+            assert_eq!(__check_jtl(text), Ok(()), "{}", text);
+            assert!(crate::JtlDocument::parse(text).is_ok());
+        }
+        let bad = [
+            "no doctype",
+            "DOCTYPE=JTL\n>>>BEGIN;\n>a=1>k>v;\n>>>END;",
+            "DOCTYPE=JTL\n>>>BEGIN;\n>a=\"1\">k;\n>>>END;",
+            "DOCTYPE=JTL\n>>>BEGIN;\n>a=\"\">k>v;\n>>>END;",
+            "DOCTYPE=JTL\n>>>BEGIN;\n>a=\"1\">k>;\n>>>END;",
+        ];
+        for text in bad {
+            assert!(__check_jtl(text).is_err(), "{}", text);
+            assert!(crate::JtlDocument::parse(text).is_err(), "{}", text);
+        }
+        let doc = crate::jtl!("DOCTYPE=JTL\n>>>BEGIN;\n>a=\"1\">k>v;\n>>>END;");
+        assert_eq!(doc.elements.len(), 1);
+    }
+}
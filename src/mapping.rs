@@ -0,0 +1,74 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::JtlElement;
+
+/// A type that maps to and from a single element.
+///
+/// With the `derive` feature, `#[derive(JtlElement)]` implements it for
+/// structs with named fields. Each field is an attribute of the same name,
+/// read with [`FromStr`] and written with [`Display`]; `Option` fields are
+/// optional attributes. `#[jtl(content)]` marks the content field,
+/// `#[jtl(key)]` the key field, and `#[jtl(rename = "name")]` changes an
+/// attribute's name. Without a key field, elements are written with the
+/// struct's name as their key, and any key is accepted when reading.
+///
+/// ```
+/// # #[cfg(feature = "derive")] {
+/// use jtl_rs::{ElementMapping, JtlDocument, JtlElement};
+///
+/// #[derive(JtlElement, Debug, PartialEq)]
+/// struct Service {
+///     #[jtl(key)]
+///     name: String,
+///     port: u16,
+///     #[jtl(rename = "tls")]
+///     secure: Option<bool>,
+///     #[jtl(content)]
+///     image: String,
+/// }
+///
+/// let doc = JtlDocument::parse("DOCTYPE=JTL\n>>>BEGIN;\n>port=\"80\">web>nginx;\n>>>END;").unwrap();
+/// let service = Service::from_element(&doc.elements[0]).unwrap();
+/// assert_eq!(service.port, 80);
+/// assert_eq!(service.secure, None);
+/// assert_eq!(service.to_element(), doc.elements[0]);
+///
+/// let bad = JtlElement::new("web", "nginx").with_attr("port", "http");
+/// assert_eq!(
+///     Service::from_element(&bad).unwrap_err(),
+///     "invalid element `web`: field `port`: `http`: invalid digit found in string"
+/// );
+/// # }
+/// ```
+pub trait ElementMapping: Sized {
+    /// Reads the value from an element.
+    fn from_element(element: &JtlElement) -> Result<Self, String>;
+
+    /// Writes the value as an element.
+    fn to_element(&self) -> JtlElement;
+}
+
+#[doc(hidden)]
+pub fn __parse_field<T>(element: &JtlElement, name: &str, value: &str) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value.parse().map_err(|err| {
+        format!(
+            "invalid element `{}`: field `{}`: `{}`: {}",
+            element.key, name, value, err
+        )
+    })
+}
+
+#[doc(hidden)]
+pub fn __required_attr<'a>(element: &'a JtlElement, name: &str) -> Result<&'a str, String> {
+    element.attr(name).ok_or_else(|| {
+        format!(
+            "invalid element `{}`: missing attribute `{}`",
+            element.key, name
+        )
+    })
+}
@@ -0,0 +1,124 @@
+use crate::options::ParseOptions;
+
+/// Renders inline Markdown as HTML.
+///
+/// JTL content is a single line, so only inline syntax applies: `` `code` ``,
+/// `**strong**`, `*emphasis*`, `[links](url)` and backslash escapes. Other
+/// text is HTML-escaped, and markers without a closing partner are literal.
+pub fn markdown_to_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let (html, used) = match c {
+            '\\' => match rest[1..].chars().next() {
+                Some(next) if next.is_ascii_punctuation() => (escape(&next.to_string()), 2),
+                _ => (escape("\\"), 1),
+            },
+            '`' => match rest[1..].find('`') {
+                Some(end) => (format!("<code>{}</code>", escape(&rest[1..=end])), end + 2),
+                None => (escape("`"), 1),
+            },
+            '*' if rest.starts_with("**") => match closing(&rest[2..], "**") {
+                Some(end) if end > 0 => (
+                    format!("<strong>{}</strong>", markdown_to_html(&rest[2..end + 2])),
+                    end + 4,
+                ),
+                _ => (escape("**"), 2),
+            },
+            '*' => match closing(&rest[1..], "*") {
+                Some(end) if end > 0 => (
+                    format!("<em>{}</em>", markdown_to_html(&rest[1..=end])),
+                    end + 2,
+                ),
+                _ => (escape("*"), 1),
+            },
+            '[' => match link(rest) {
+                Some((label, url, used)) => (
+                    format!(
+                        "<a href=\"{}\">{}</a>",
+                        escape(url),
+                        markdown_to_html(label)
+                    ),
+                    used,
+                ),
+                None => (escape("["), 1),
+            },
+            _ => (escape(&c.to_string()), c.len_utf8()),
+        };
+        out.push_str(&html);
+        rest = &rest[used..];
+    }
+    out
+}
+
+/// Finds the marker closing an emphasis span that starts `text`. As in
+/// CommonMark, the span cannot start or end with whitespace.
+fn closing(text: &str, marker: &str) -> Option<usize> {
+    if text.starts_with(char::is_whitespace) {
+        return None;
+    }
+    text.match_indices(marker)
+        .map(|(index, _)| index)
+        .find(|&index| {
+            index > 0 && !text[..index].ends_with(|c: char| c.is_whitespace() || c == '\\')
+        })
+}
+
+/// Splits `[label](url)` at the start of `text`, returning the label, the
+/// URL, and the number of bytes used.
+fn link(text: &str) -> Option<(&str, &str, usize)> {
+    let close = text.find("](")?;
+    let end = text[close + 2..].find(')')? + close + 2;
+    Some((&text[1..close], &text[close + 2..end], end + 1))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl ParseOptions {
+    /// Renders the content of elements with `format="markdown"` as HTML, using
+    /// [`markdown_to_html`].
+    pub fn markdown(self) -> Self {
+        self.process_content("format", "markdown", |content| {
+            Ok(markdown_to_html(content))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_iter_with;
+
+    #[test]
+    fn test_markdown_to_html() {
+        assert_eq!(
+            markdown_to_html("**Bold** and *em*, `a<b`, [docs](https://x.io/?a=1&b=2)"),
+            "<strong>Bold</strong> and <em>em</em>, <code>a&lt;b</code>, \
+             <a href=\"https://x.io/?a=1&amp;b=2\">docs</a>"
+        );
+        assert_eq!(
+            markdown_to_html(r"2 * 3 \*not em\* [x]"),
+            "2 * 3 *not em* [x]"
+        );
+    }
+
+    #[test]
+    fn test_markdown_option() {
+        let jtl = r#"DOCTYPE=JTL
+>>>BEGIN;
+>format="markdown">intro>Hello **world**;
+>format="text">plain>Hello **world**;
+>>>END;"#;
+        let options = ParseOptions::new().markdown();
+        let parsed: Vec<_> = parse_iter_with(jtl, &options)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(parsed[0].content, "Hello <strong>world</strong>");
+        assert_eq!(parsed[1].content, "Hello **world**");
+    }
+}
@@ -0,0 +1,78 @@
+use std::mem::size_of;
+
+use crate::{JtlDocument, JtlElement};
+
+/// Approximate heap memory held by a [`JtlDocument`], from
+/// [`JtlDocument::memory_usage`].
+///
+/// Sizes are computed from capacities and type sizes, not measured from the
+/// allocator, so they leave out allocator overhead and the map's exact
+/// bucket layout. They are meant for capacity planning, not accounting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Bytes of string data: keys, contents, attributes, annotations and env
+    /// names and values.
+    pub strings: usize,
+    /// Bytes of the element, attribute and annotation vectors themselves.
+    pub elements: usize,
+    /// Bytes of the env map's table, without the strings it holds.
+    pub env: usize,
+}
+
+impl MemoryUsage {
+    /// Returns the total, in bytes.
+    pub fn total(&self) -> usize {
+        self.strings + self.elements + self.env
+    }
+}
+
+impl JtlDocument {
+    /// Estimates how much heap memory the document holds.
+    ///
+    /// The size of the `JtlDocument` value itself is not included, so a
+    /// service holding many documents adds `size_of::<JtlDocument>()` for
+    /// each.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage {
+            elements: self.elements.capacity() * size_of::<JtlElement>(),
+            // One control byte per bucket besides the entry.
+            env: self.env.capacity() * (size_of::<(String, String)>() + 1),
+            ..MemoryUsage::default()
+        };
+        for (name, value) in &self.env {
+            usage.strings += name.capacity() + value.capacity();
+        }
+        for element in &self.elements {
+            usage.strings += element.key.capacity() + element.content.capacity();
+            for pairs in [&element.attrs, &element.annotations] {
+                usage.elements += pairs.capacity() * size_of::<(String, String)>();
+                for (name, value) in pairs {
+                    usage.strings += name.capacity() + value.capacity();
+                }
+            }
+        }
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_usage() {
+        assert_eq!(JtlDocument::new().memory_usage().total(), 0);
+        let small = crate::generate(&crate::GenOptions {
+            elements: 10,
+            ..Default::default()
+        });
+        let large = crate::generate(&crate::GenOptions {
+            elements: 100,
+            ..Default::default()
+        });
+        let (small, large) = (small.memory_usage(), large.memory_usage());
+        assert!(small.strings >= 10 * (32 + 3 * 10));
+        assert!(large.total() > 9 * small.total());
+        assert_eq!(small.env, 0);
+    }
+}
@@ -0,0 +1,160 @@
+use serde_json::Value;
+
+/// Encodes parsed elements as MessagePack.
+///
+/// The output is an array of maps with exactly the shape [`stringify`](crate::stringify)
+/// produces as JSON.
+pub fn to_msgpack(parsed: &[Value]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_array_len(&mut out, parsed.len());
+    for value in parsed {
+        write_value(&mut out, value);
+    }
+    out
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(false) => out.push(0xc2),
+        Value::Bool(true) => out.push(0xc3),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                write_uint(out, u);
+            } else if let Some(i) = n.as_i64() {
+                write_int(out, i);
+            } else {
+                out.push(0xcb);
+                out.extend_from_slice(&n.as_f64().unwrap_or(f64::NAN).to_be_bytes());
+            }
+        }
+        Value::String(s) => write_str(out, s),
+        Value::Array(items) => {
+            write_array_len(out, items.len());
+            for item in items {
+                write_value(out, item);
+            }
+        }
+        Value::Object(map) => {
+            write_header(out, map.len(), 0x80, 16, 0xde, 0xdf);
+            for (key, item) in map {
+                write_str(out, key);
+                write_value(out, item);
+            }
+        }
+    }
+}
+
+fn write_uint(out: &mut Vec<u8>, u: u64) {
+    if u < 0x80 {
+        out.push(u as u8);
+    } else if u <= u8::MAX as u64 {
+        out.extend_from_slice(&[0xcc, u as u8]);
+    } else if u <= u16::MAX as u64 {
+        out.push(0xcd);
+        out.extend_from_slice(&(u as u16).to_be_bytes());
+    } else if u <= u32::MAX as u64 {
+        out.push(0xce);
+        out.extend_from_slice(&(u as u32).to_be_bytes());
+    } else {
+        out.push(0xcf);
+        out.extend_from_slice(&u.to_be_bytes());
+    }
+}
+
+fn write_int(out: &mut Vec<u8>, i: i64) {
+    if i >= -32 {
+        out.push(i as i8 as u8);
+    } else if i >= i8::MIN as i64 {
+        out.extend_from_slice(&[0xd0, i as i8 as u8]);
+    } else if i >= i16::MIN as i64 {
+        out.push(0xd1);
+        out.extend_from_slice(&(i as i16).to_be_bytes());
+    } else if i >= i32::MIN as i64 {
+        out.push(0xd2);
+        out.extend_from_slice(&(i as i32).to_be_bytes());
+    } else {
+        out.push(0xd3);
+        out.extend_from_slice(&i.to_be_bytes());
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    let len = s.len();
+    if len < 32 {
+        out.push(0xa0 | len as u8);
+    } else if len <= u8::MAX as usize {
+        out.extend_from_slice(&[0xd9, len as u8]);
+    } else if len <= u16::MAX as usize {
+        out.push(0xda);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(0xdb);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_array_len(out: &mut Vec<u8>, len: usize) {
+    write_header(out, len, 0x90, 16, 0xdc, 0xdd);
+}
+
+fn write_header(
+    out: &mut Vec<u8>,
+    len: usize,
+    fix: u8,
+    fix_max: usize,
+    marker16: u8,
+    marker32: u8,
+) {
+    if len < fix_max {
+        out.push(fix | len as u8);
+    } else if len <= u16::MAX as usize {
+        out.push(marker16);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(marker32);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+    use serde_json::json;
+
+    #[test]
+    fn test_to_msgpack() {
+        let parsed = parse("DOCTYPE=JTL\n>>>BEGIN;\n>a=\"1\">id>hi;\n>>>END;").unwrap();
+        let bytes = to_msgpack(&parsed);
+
+        let mut expected = vec![0x91, 0x84];
+        for (key, value) in [
+            ("Content", "hi"),
+            ("Contents", "hi"),
+            ("KEY", "id"),
+            ("a", "1"),
+        ] {
+            expected.push(0xa0 | key.len() as u8);
+            expected.extend_from_slice(key.as_bytes());
+            expected.push(0xa0 | value.len() as u8);
+            expected.extend_from_slice(value.as_bytes());
+        }
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn test_msgpack_scalars() {
+        let mut out = Vec::new();
+        write_value(
+            &mut out,
+            &json!([null, true, 300, -5, -200, 1.5, "x".repeat(40)]),
+        );
+        assert_eq!(
+            &out[..12],
+            &[0x97, 0xc0, 0xc3, 0xcd, 0x01, 0x2c, 0xfb, 0xd1, 0xff, 0x38, 0xcb, 0x3f]
+        );
+        assert_eq!(&out[out.len() - 42..out.len() - 40], &[0xd9, 40]);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::element::JtlElement;
+use crate::interp::ContentFunction;
+use crate::limits::Limits;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Predicate deciding from an element's key and attributes whether to keep it.
+pub(crate) type ElementFilter = dyn Fn(&str, &[(&str, &str)]) -> bool + Send + Sync;
+
+/// Domain check run on every element as it is parsed.
+pub(crate) type ElementValidator = dyn Fn(&JtlElement) -> Result<(), String> + Send + Sync;
+
+/// Transformation applied to the content of matching elements.
+pub(crate) type ContentProcessor = dyn Fn(&str) -> Result<String, String> + Send + Sync;
+
+/// A shared callback stored in [`ParseOptions`].
+pub(crate) struct Callback<F: ?Sized>(pub(crate) Arc<F>);
+
+impl<F: ?Sized> Clone for Callback<F> {
+    fn clone(&self) -> Self {
+        Callback(Arc::clone(&self.0))
+    }
+}
+
+impl<F: ?Sized> fmt::Debug for Callback<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Callback(..)")
+    }
+}
+
+/// How strictly the first line is checked for the `DOCTYPE=JTL` marker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DoctypeCheck {
+    /// The marker may appear anywhere on the first line.
+    #[default]
+    Contains,
+    /// The first line must start with the marker, optionally followed by a
+    /// space and a version.
+    Prefix,
+    /// The first line must be exactly the marker, or the marker, a space and a
+    /// version, with nothing else but trailing whitespace.
+    Exact,
+}
+
+/// What to do when an env variable is declared more than once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DuplicateEnv {
+    /// Fail with an error naming the variable and line.
+    Error,
+    /// Keep the first value and ignore later declarations.
+    FirstWins,
+    /// Keep the last value.
+    #[default]
+    LastWins,
+    /// Keep the last value and report a [`Warning`](crate::Warning).
+    Warn,
+}
+
+/// How attribute names are cased when parsed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AttrCase {
+    /// Keep names as written.
+    #[default]
+    Preserve,
+    /// Lowercase every name, so `Port` and `port` are the same attribute.
+    Lowercase,
+}
+
+/// Options controlling how a JTL document is parsed.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub(crate) limits: Limits,
+    pub(crate) projection: Option<Vec<String>>,
+    pub(crate) filter: Option<Callback<ElementFilter>>,
+    pub(crate) doctype: DoctypeCheck,
+    pub(crate) doctype_versions: Option<Vec<String>>,
+    pub(crate) reject_trailing: bool,
+    pub(crate) duplicate_env: DuplicateEnv,
+    pub(crate) functions: HashMap<String, Callback<ContentFunction>>,
+    pub(crate) anchors: bool,
+    pub(crate) validator: Option<Callback<ElementValidator>>,
+    pub(crate) renames: HashMap<String, String>,
+    pub(crate) processors: Vec<(String, String, Callback<ContentProcessor>)>,
+    pub(crate) lists: bool,
+    pub(crate) attr_case: AttrCase,
+    pub(crate) reject_case_duplicates: bool,
+    pub(crate) trim_values: bool,
+    pub(crate) cache_env: bool,
+}
+
+impl ParseOptions {
+    /// Creates options matching the behavior of [`parse`](crate::parse).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces all size caps at once.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Rejects elements with more than `max` attributes.
+    pub fn max_attributes(mut self, max: usize) -> Self {
+        self.limits.max_attributes = Some(max);
+        self
+    }
+
+    /// Rejects elements whose content is longer than `max` bytes.
+    pub fn max_content_bytes(mut self, max: usize) -> Self {
+        self.limits.max_content_bytes = Some(max);
+        self
+    }
+
+    /// Keeps only the listed fields of each element.
+    ///
+    /// Fields are named as in the JSON output: attribute names, plus `KEY` and
+    /// `Content` for the element's key and content. Everything else is skipped
+    /// before it is allocated. Limits still apply to the full element.
+    pub fn project(mut self, fields: &[&str]) -> Self {
+        self.projection = Some(fields.iter().map(|field| field.to_string()).collect());
+        self
+    }
+
+    /// Skips elements for which `filter(key, attrs)` returns `false`.
+    ///
+    /// The predicate sees the element's key and attributes, borrowed from the
+    /// input, before anything is allocated for the element. Skipped elements must
+    /// still be well-formed, but limits are not applied to them.
+    pub fn filter(
+        mut self,
+        filter: impl Fn(&str, &[(&str, &str)]) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.filter = Some(Callback(Arc::new(filter)));
+        self
+    }
+
+    /// Sets how strictly the `DOCTYPE` line is checked. Defaults to
+    /// [`DoctypeCheck::Contains`].
+    pub fn doctype(mut self, check: DoctypeCheck) -> Self {
+        self.doctype = check;
+        self
+    }
+
+    /// Accepts only the listed versions in a versioned `DOCTYPE=JTL <version>`
+    /// line. Documents without a version are still accepted.
+    pub fn doctype_versions(mut self, versions: &[&str]) -> Self {
+        self.doctype_versions = Some(versions.iter().map(|version| version.to_string()).collect());
+        self
+    }
+
+    /// Fails on anything but blank lines and comments after `>>>END;`, instead
+    /// of ignoring it.
+    pub fn reject_trailing_content(mut self, reject: bool) -> Self {
+        self.reject_trailing = reject;
+        self
+    }
+
+    /// Sets how redefined env variables are handled. Defaults to
+    /// [`DuplicateEnv::LastWins`].
+    pub fn duplicate_env(mut self, policy: DuplicateEnv) -> Self {
+        self.duplicate_env = policy;
+        self
+    }
+
+    /// Registers a function for `${env:NAME|name}` interpolation in content.
+    ///
+    /// The function receives the value so far and the text after `name:`, if
+    /// any. `upper`, `lower`, `trim` and `default:fallback` are built in; a
+    /// function registered under one of those names replaces it.
+    pub fn function(
+        mut self,
+        name: &str,
+        function: impl Fn(&str, Option<&str>) -> Result<String, String> + Send + Sync + 'static,
+    ) -> Self {
+        self.functions
+            .insert(name.to_string(), Callback(Arc::new(function)));
+        self
+    }
+
+    /// Enables YAML-style anchors in element content.
+    ///
+    /// Content written as `&name payload` defines the anchor `name` and reads
+    /// as `payload`; later elements whose content is exactly `*name` read as the
+    /// same payload. Aliasing an undefined anchor is an error. Off by default,
+    /// since content starting with `&` or `*` is otherwise taken literally.
+    /// Anchors only apply when parsing a whole document, not to lazily decoded
+    /// or streamed elements.
+    pub fn anchors(mut self, enabled: bool) -> Self {
+        self.anchors = enabled;
+        self
+    }
+
+    /// Runs `validate` on each element as soon as it is built, failing the parse
+    /// with the element's key and line on the first `Err`.
+    ///
+    /// The element is validated as returned, after env substitution and
+    /// projection. Elements skipped by a filter are not validated.
+    pub fn validate_element(
+        mut self,
+        validate: impl Fn(&JtlElement) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.validator = Some(Callback(Arc::new(validate)));
+        self
+    }
+
+    /// Runs `process` on the content of every element whose attribute `attr`
+    /// is `value`, such as `format="markdown"`.
+    ///
+    /// The processor sees the content after env substitution and its result
+    /// replaces it; an `Err` fails the parse. When several processors match an
+    /// element, the one registered first wins.
+    pub fn process_content(
+        mut self,
+        attr: &str,
+        value: &str,
+        process: impl Fn(&str) -> Result<String, String> + Send + Sync + 'static,
+    ) -> Self {
+        self.processors.push((
+            attr.to_string(),
+            value.to_string(),
+            Callback(Arc::new(process)),
+        ));
+        self
+    }
+
+    /// Renames attributes as they are read, given `(old, new)` pairs.
+    ///
+    /// Renaming happens first, so filters, projections and validators only see
+    /// the new names. Lets older documents on disk keep working after an
+    /// attribute is renamed; [`JtlWriter::rename_attrs`](crate::JtlWriter::rename_attrs)
+    /// does the same on output.
+    pub fn rename_attrs(mut self, renames: &[(&str, &str)]) -> Self {
+        self.renames.extend(
+            renames
+                .iter()
+                .map(|(old, new)| (old.to_string(), new.to_string())),
+        );
+        self
+    }
+
+    /// Returns the name attribute `name` is read as.
+    pub(crate) fn renamed<'a>(&'a self, name: &'a str) -> &'a str {
+        self.renames.get(name).map_or(name, String::as_str)
+    }
+
+    /// Turns attribute values written as lists, `tags="[a, b, c]"`, into JSON
+    /// arrays of strings in the parsed output. Items are separated by commas
+    /// and trimmed, and `[]` is an empty list.
+    ///
+    /// Elements themselves keep the text; use
+    /// [`JtlElement::attr_list`](crate::JtlElement::attr_list) to read it.
+    pub fn list_attrs(mut self, enabled: bool) -> Self {
+        self.lists = enabled;
+        self
+    }
+
+    /// Sets how attribute names are cased. Defaults to [`AttrCase::Preserve`].
+    ///
+    /// Folding happens before renaming, so [`rename_attrs`](ParseOptions::rename_attrs)
+    /// sees the folded names.
+    pub fn attr_case(mut self, case: AttrCase) -> Self {
+        self.attr_case = case;
+        self
+    }
+
+    /// Fails on elements with two attribute names that differ only in case,
+    /// such as `Port` and `port`, checked on the names as written.
+    pub fn reject_case_duplicates(mut self, reject: bool) -> Self {
+        self.reject_case_duplicates = reject;
+        self
+    }
+
+    /// Trims whitespace around attribute values, so `port=" 80 "` reads as
+    /// `80`.
+    pub fn trim_attr_values(mut self, trim: bool) -> Self {
+        self.trim_values = trim;
+        self
+    }
+
+    /// Shares resolved content between elements in
+    /// [`parse_values`](crate::parse_values) output instead of copying it into
+    /// each one.
+    ///
+    /// Every element whose content resolves to the same text, such as the same
+    /// `$env:` reference used across thousands of elements, then points at one
+    /// `Arc<str>`. Costs a lookup per element, so it is off by default.
+    pub fn cache_env(mut self, enabled: bool) -> Self {
+        self.cache_env = enabled;
+        self
+    }
+
+    /// Returns whether the field `name` survives the projection.
+    pub(crate) fn keeps(&self, name: &str) -> bool {
+        match &self.projection {
+            Some(fields) => fields.iter().any(|field| field == name),
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,127 @@
+#[cfg(feature = "json")]
+use serde_json::Value;
+
+/// The order attributes are emitted in by [`JtlWriter`](crate::JtlWriter) and
+/// [`stringify_ordered`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AttrOrder {
+    /// Keep the order the attributes already have.
+    #[default]
+    Source,
+    /// Sort attributes by name.
+    Alphabetical,
+    /// Emit the listed names first, in list order, then the rest in their
+    /// existing order.
+    Priority(Vec<String>),
+}
+
+impl AttrOrder {
+    /// Shorthand for [`AttrOrder::Priority`].
+    pub fn priority(names: &[&str]) -> Self {
+        AttrOrder::Priority(names.iter().map(|name| name.to_string()).collect())
+    }
+
+    /// Reorders `items` in place, reading each item's name with `name`.
+    pub(crate) fn apply<T>(&self, items: &mut [T], name: impl Fn(&T) -> &str) {
+        match self {
+            AttrOrder::Source => {}
+            AttrOrder::Alphabetical => items.sort_by(|a, b| name(a).cmp(name(b))),
+            AttrOrder::Priority(names) => items.sort_by_key(|item| {
+                names
+                    .iter()
+                    .position(|listed| listed == name(item))
+                    .unwrap_or(names.len())
+            }),
+        }
+    }
+}
+
+/// Converts a vector to a JSON string like [`stringify`](crate::stringify),
+/// ordering the fields of each top-level object by `order`.
+///
+/// JSON objects keep no source order, so [`AttrOrder::Source`] and the
+/// unlisted fields of a priority list come out in the map's own (sorted) order.
+#[cfg(feature = "json")]
+pub fn stringify_ordered(data: &[Value], order: &AttrOrder) -> Result<String, serde_json::Error> {
+    let mut out = String::from("[");
+    for (index, value) in data.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        match value {
+            Value::Object(map) => {
+                let mut fields: Vec<(&String, &Value)> = map.iter().collect();
+                order.apply(&mut fields, |(name, _)| name.as_str());
+                out.push('{');
+                for (position, (name, value)) in fields.into_iter().enumerate() {
+                    if position > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&serde_json::to_string(name)?);
+                    out.push(':');
+                    out.push_str(&serde_json::to_string(value)?);
+                }
+                out.push('}');
+            }
+            other => out.push_str(&serde_json::to_string(other)?),
+        }
+    }
+    out.push(']');
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{JtlElement, JtlWriter};
+
+    #[test]
+    fn test_attr_order() {
+        let element = JtlElement::new("web", "nginx")
+            .with_attr("port", "80")
+            .with_attr("host", "a")
+            .with_attr("id", "1");
+        let write = |order: AttrOrder| {
+            let out = JtlWriter::new(Vec::new())
+                .attr_order(order)
+                .begin()
+                .and_then(|writer| writer.element(&element))
+                .and_then(JtlWriter::finish)
+                .unwrap();
+            String::from_utf8(out)
+                .unwrap()
+                .lines()
+                .nth(2)
+                .unwrap()
+                .to_string()
+        };
+        assert_eq!(
+            write(AttrOrder::Source),
+            r#">port="80" host="a" id="1">web>nginx;"#
+        );
+        assert_eq!(
+            write(AttrOrder::Alphabetical),
+            r#">host="a" id="1" port="80">web>nginx;"#
+        );
+        assert_eq!(
+            write(AttrOrder::priority(&["id", "host"])),
+            r#">id="1" host="a" port="80">web>nginx;"#
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_stringify_ordered() {
+        use serde_json::json;
+
+        let data = [
+            json!({ "port": "80", "KEY": "web", "id": "1" }),
+            json!(null),
+        ];
+        assert_eq!(
+            stringify_ordered(&data, &AttrOrder::priority(&["KEY", "id"])).unwrap(),
+            r#"[{"KEY":"web","id":"1","port":"80"},null]"#
+        );
+    }
+}
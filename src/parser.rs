@@ -0,0 +1,15 @@
+#[cfg(feature = "json")]
+pub use crate::{parse, parse_file, parse_with_options, parse_with_warnings};
+#[cfg(feature = "async")]
+pub use crate::{parse_async, AsyncBufRead, AsyncElements};
+pub use crate::{
+    parse_borrowed, parse_chunks, parse_document, parse_from, parse_iter, parse_iter_with,
+    parse_lazy, parse_reader, parse_strict_roundtrip, parse_values, parse_values_with,
+    parse_with_deadline, parse_with_deadline_options, parse_with_visitor,
+    parse_with_visitor_options, read_frames, AttrCase, BorrowedElement, Checkpoint, Chunks,
+    DoctypeCheck, DuplicateEnv, Event, FileError, Frames, IndexedFile, JtlError, JtlFrameDecoder,
+    JtlVisitor, LazyDocument, Limits, ParseIter, ParseOptions, PartialParse, RawElement, Reader,
+    ReaderElements, Resumed, TimedOut, Warning,
+};
+#[cfg(feature = "tail")]
+pub use crate::{tail, Tail};
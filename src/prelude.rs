@@ -0,0 +1,6 @@
+#[cfg(feature = "json")]
+pub use crate::parse;
+pub use crate::{
+    parse_document, parse_iter, ElementMapping, JtlDocument, JtlElement, JtlError, JtlValue,
+    JtlVisitor, JtlWriter, ParseOptions,
+};
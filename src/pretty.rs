@@ -0,0 +1,170 @@
+use serde_json::Value;
+
+use crate::AttrOrder;
+
+/// Layout of the JSON written by [`stringify_with`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StringifyOptions {
+    indent: usize,
+    order: AttrOrder,
+}
+
+impl Default for StringifyOptions {
+    fn default() -> Self {
+        StringifyOptions {
+            indent: 2,
+            order: AttrOrder::Source,
+        }
+    }
+}
+
+impl StringifyOptions {
+    /// Creates options for two-space indentation in the map's own key order.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indents nested values by `width` spaces. A width of 0 writes compact
+    /// JSON on one line.
+    pub fn indent(mut self, width: usize) -> Self {
+        self.indent = width;
+        self
+    }
+
+    /// Orders the fields of each element like [`stringify_ordered`](crate::stringify_ordered).
+    pub fn order(mut self, order: AttrOrder) -> Self {
+        self.order = order;
+        self
+    }
+}
+
+/// Converts a vector to indented JSON, one field per line, for output that is
+/// meant to be read and diffed.
+pub fn stringify_pretty(data: &[Value]) -> Result<String, serde_json::Error> {
+    stringify_with(data, &StringifyOptions::default())
+}
+
+/// Converts a vector to JSON laid out according to `options`.
+pub fn stringify_with(
+    data: &[Value],
+    options: &StringifyOptions,
+) -> Result<String, serde_json::Error> {
+    let mut out = String::new();
+    let items: Vec<&Value> = data.iter().collect();
+    write_array(&mut out, &items, options, 0, true)?;
+    Ok(out)
+}
+
+fn write_value(
+    out: &mut String,
+    value: &Value,
+    options: &StringifyOptions,
+    depth: usize,
+    top: bool,
+) -> Result<(), serde_json::Error> {
+    match value {
+        Value::Array(items) => {
+            let items: Vec<&Value> = items.iter().collect();
+            write_array(out, &items, options, depth, false)
+        }
+        Value::Object(map) => {
+            let mut fields: Vec<(&String, &Value)> = map.iter().collect();
+            if top {
+                options.order.apply(&mut fields, |(name, _)| name.as_str());
+            }
+            write_items(
+                out,
+                ('{', '}'),
+                fields.len(),
+                options,
+                depth,
+                |out, index| {
+                    let (name, value) = fields[index];
+                    out.push_str(&serde_json::to_string(name)?);
+                    out.push_str(if options.indent == 0 { ":" } else { ": " });
+                    write_value(out, value, options, depth + 1, false)
+                },
+            )
+        }
+        scalar => {
+            out.push_str(&serde_json::to_string(scalar)?);
+            Ok(())
+        }
+    }
+}
+
+fn write_array(
+    out: &mut String,
+    items: &[&Value],
+    options: &StringifyOptions,
+    depth: usize,
+    top: bool,
+) -> Result<(), serde_json::Error> {
+    write_items(
+        out,
+        ('[', ']'),
+        items.len(),
+        options,
+        depth,
+        |out, index| write_value(out, items[index], options, depth + 1, top),
+    )
+}
+
+/// Writes `count` items between `brackets`, each on its own indented line.
+fn write_items(
+    out: &mut String,
+    brackets: (char, char),
+    count: usize,
+    options: &StringifyOptions,
+    depth: usize,
+    mut item: impl FnMut(&mut String, usize) -> Result<(), serde_json::Error>,
+) -> Result<(), serde_json::Error> {
+    out.push(brackets.0);
+    for index in 0..count {
+        if index > 0 {
+            out.push(',');
+        }
+        newline(out, options, depth + 1);
+        item(out, index)?;
+    }
+    if count > 0 {
+        newline(out, options, depth);
+    }
+    out.push(brackets.1);
+    Ok(())
+}
+
+fn newline(out: &mut String, options: &StringifyOptions, depth: usize) {
+    if options.indent > 0 {
+        out.push('\n');
+        out.push_str(&" ".repeat(options.indent * depth));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_stringify_pretty() {
+        let data = vec![
+            json!({"KEY": "web", "Content": "nginx", "port": "80"}),
+            json!({"KEY": "db", "Contents": ["a", "b"], "tags": []}),
+        ];
+        assert_eq!(
+            stringify_pretty(&data).unwrap(),
+            serde_json::to_string_pretty(&data).unwrap()
+        );
+        let options = StringifyOptions::new()
+            .indent(0)
+            .order(AttrOrder::priority(&["KEY"]));
+        assert_eq!(
+            stringify_with(&data, &options).unwrap(),
+            crate::stringify_ordered(&data, &AttrOrder::priority(&["KEY"])).unwrap()
+        );
+        let wide = stringify_with(&data[..1], &StringifyOptions::new().indent(4)).unwrap();
+        assert!(wide.starts_with("[\n    {\n        \"Content\": \"nginx\","));
+        assert_eq!(stringify_pretty(&[]).unwrap(), "[]");
+    }
+}
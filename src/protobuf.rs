@@ -0,0 +1,268 @@
+use crate::element::JtlElement;
+use std::error::Error;
+
+/// Scalar protobuf field types an element value can be converted to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FieldType {
+    String,
+    Bytes,
+    Bool,
+    Int32,
+    Int64,
+    UInt32,
+    UInt64,
+    SInt32,
+    SInt64,
+    Float,
+    Double,
+}
+
+const MAX_FIELD_NUMBER: u32 = (1 << 29) - 1;
+const RESERVED: std::ops::RangeInclusive<u32> = 19000..=19999;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+    number: u32,
+    name: String,
+    ty: FieldType,
+}
+
+/// The layout of a protobuf message, as the caller's `.proto` file declares it.
+///
+/// Descriptors are built in code with [`field`](MessageDescriptor::field);
+/// `.proto` and compiled descriptor files can't be loaded.
+///
+/// Fields are matched to elements by name: an attribute name, or `KEY` and
+/// `Content` for the element's key and content. Every attribute of an encoded
+/// element must have a field, or encoding fails. The key and content are
+/// different: without a `KEY` or `Content` field they are ignored and don't
+/// appear in the message. Fields with no matching value are left out, which
+/// protobuf reads as the default value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageDescriptor {
+    name: String,
+    fields: Vec<Field>,
+}
+
+impl MessageDescriptor {
+    /// Creates a descriptor for the message `name` with no fields.
+    pub fn new(name: impl Into<String>) -> Self {
+        MessageDescriptor {
+            name: name.into(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a field, failing if `number` is not a valid protobuf field number,
+    /// or if the number or the name is already used by another field.
+    ///
+    /// Valid numbers are 1 to 2^29 - 1, except 19000 to 19999, which
+    /// protobuf reserves for itself.
+    pub fn field(
+        mut self,
+        number: u32,
+        name: impl Into<String>,
+        ty: FieldType,
+    ) -> Result<Self, String> {
+        let name = name.into();
+        if !(1..=MAX_FIELD_NUMBER).contains(&number) || RESERVED.contains(&number) {
+            return Err(format!(
+                "protobuf: invalid number {} for field `{}`",
+                number, name
+            ));
+        }
+        if self.fields.iter().any(|field| field.name == name) {
+            return Err(format!(
+                "protobuf: message `{}` already has a field `{}`",
+                self.name, name
+            ));
+        }
+        if let Some(other) = self.fields.iter().find(|field| field.number == number) {
+            return Err(format!(
+                "protobuf: fields `{}` and `{}` both use number {}",
+                other.name, name, number
+            ));
+        }
+        self.fields.push(Field { number, name, ty });
+        Ok(self)
+    }
+
+    /// Returns the message name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Encodes `element` as a message in protobuf wire format.
+    pub fn encode(&self, element: &JtlElement) -> Result<Vec<u8>, Box<dyn Error>> {
+        for (attr, _) in &element.attrs {
+            if !self.fields.iter().any(|field| &field.name == attr) {
+                return Err(format!(
+                    "protobuf: message `{}` has no field for attribute `{}`",
+                    self.name, attr
+                )
+                .into());
+            }
+        }
+
+        let mut fields: Vec<&Field> = self.fields.iter().collect();
+        fields.sort_by_key(|field| field.number);
+        let mut out = Vec::new();
+        for field in fields {
+            let value = match field.name.as_str() {
+                "KEY" => Some(element.key.as_str()),
+                "Content" => Some(element.content.as_str()),
+                name => element.attr(name),
+            };
+            if let Some(value) = value {
+                write_field(&mut out, field, value).map_err(|err| {
+                    format!(
+                        "protobuf: element `{}`, field `{}`: {}",
+                        element.key, field.name, err
+                    )
+                })?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn write_field(out: &mut Vec<u8>, field: &Field, value: &str) -> Result<(), Box<dyn Error>> {
+    let tag = |wire_type: u64| ((field.number as u64) << 3) | wire_type;
+    match field.ty {
+        FieldType::String | FieldType::Bytes => {
+            write_varint(out, tag(2));
+            write_varint(out, value.len() as u64);
+            out.extend_from_slice(value.as_bytes());
+        }
+        FieldType::Bool => {
+            let b: bool = value.parse()?;
+            write_varint(out, tag(0));
+            write_varint(out, b as u64);
+        }
+        FieldType::Int32 => {
+            let n: i32 = value.parse()?;
+            write_varint(out, tag(0));
+            // Negative int32 values are sign-extended to 64 bits.
+            write_varint(out, n as i64 as u64);
+        }
+        FieldType::Int64 => {
+            let n: i64 = value.parse()?;
+            write_varint(out, tag(0));
+            write_varint(out, n as u64);
+        }
+        FieldType::UInt32 => {
+            let n: u32 = value.parse()?;
+            write_varint(out, tag(0));
+            write_varint(out, n as u64);
+        }
+        FieldType::UInt64 => {
+            let n: u64 = value.parse()?;
+            write_varint(out, tag(0));
+            write_varint(out, n);
+        }
+        FieldType::SInt32 => {
+            let n: i32 = value.parse()?;
+            write_varint(out, tag(0));
+            write_varint(out, ((n << 1) ^ (n >> 31)) as u32 as u64);
+        }
+        FieldType::SInt64 => {
+            let n: i64 = value.parse()?;
+            write_varint(out, tag(0));
+            write_varint(out, ((n << 1) ^ (n >> 63)) as u64);
+        }
+        FieldType::Float => {
+            let n: f32 = value.parse()?;
+            write_varint(out, tag(5));
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+        FieldType::Double => {
+            let n: f64 = value.parse()?;
+            write_varint(out, tag(1));
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+    Ok(())
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_descriptor() -> MessageDescriptor {
+        MessageDescriptor::new("User")
+            .field(1, "KEY", FieldType::String)
+            .and_then(|d| d.field(2, "id", FieldType::Int64))
+            .and_then(|d| d.field(3, "active", FieldType::Bool))
+            .and_then(|d| d.field(4, "delta", FieldType::SInt32))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_encode() {
+        let element = JtlElement::new("bob", "ignored")
+            .with_attr("delta", "-2")
+            .with_attr("id", "150")
+            .with_attr("active", "true");
+        let bytes = user_descriptor().encode(&element).unwrap();
+        assert_eq!(
+            bytes,
+            [0x0a, 3, b'b', b'o', b'b', 0x10, 0x96, 0x01, 0x18, 1, 0x20, 3]
+        );
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let descriptor = user_descriptor();
+        let err = descriptor
+            .encode(&JtlElement::new("bob", "x").with_attr("team", "core"))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "protobuf: message `User` has no field for attribute `team`"
+        );
+
+        let err = descriptor
+            .encode(&JtlElement::new("bob", "x").with_attr("id", "many"))
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("protobuf: element `bob`, field `id`: "));
+    }
+
+    #[test]
+    fn test_field_numbers() {
+        let new = || MessageDescriptor::new("User");
+        for number in [0, 19000, 19999, 1 << 29] {
+            assert_eq!(
+                new().field(number, "id", FieldType::Int64).unwrap_err(),
+                format!("protobuf: invalid number {} for field `id`", number)
+            );
+        }
+        assert!(new().field((1 << 29) - 1, "id", FieldType::Int64).is_ok());
+        assert_eq!(
+            user_descriptor()
+                .field(2, "team", FieldType::String)
+                .unwrap_err(),
+            "protobuf: fields `id` and `team` both use number 2"
+        );
+        assert_eq!(
+            user_descriptor()
+                .field(9, "id", FieldType::String)
+                .unwrap_err(),
+            "protobuf: message `User` already has a field `id`"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{JtlDocument, JtlElement};
+
+/// Where an element or env variable was declared.
+///
+/// Origins are kept when documents are combined with [`concat`](crate::concat)
+/// or layered with [`merge`](crate::merge), so a value in a document built from
+/// several files still points at the file and line it was written on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Origin {
+    source: Option<Arc<Path>>,
+    line: Option<usize>,
+}
+
+impl Origin {
+    /// Returns the file the declaration was read from, if known.
+    pub fn source(&self) -> Option<&Path> {
+        self.source.as_deref()
+    }
+
+    /// Returns the 1-based line of the declaration, if known.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+}
+
+/// Formats the origin as `path:line`, leaving out whichever is unknown.
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.source, self.line) {
+            (Some(source), Some(line)) => write!(f, "{}:{}", source.display(), line),
+            (Some(source), None) => write!(f, "{}", source.display()),
+            (None, Some(line)) => write!(f, "line {}", line),
+            (None, None) => f.write_str("unknown origin"),
+        }
+    }
+}
+
+impl JtlElement {
+    /// Returns where the element was declared.
+    pub fn origin(&self) -> Origin {
+        Origin {
+            source: self.source.clone(),
+            line: self.line,
+        }
+    }
+}
+
+impl JtlDocument {
+    /// Returns where env variable `name` was declared, if it is defined.
+    pub fn env_origin(&self, name: &str) -> Option<Origin> {
+        self.env.get(name)?;
+        Some(self.env_origins.get(name).cloned().unwrap_or_default())
+    }
+
+    /// Records `path` as the file every element and env variable of the
+    /// document was read from. [`load`](JtlDocument::load) does this itself.
+    pub fn set_source(&mut self, path: impl AsRef<Path>) {
+        let source: Arc<Path> = Arc::from(path.as_ref());
+        for element in &mut self.elements {
+            element.source = Some(Arc::clone(&source));
+        }
+        for name in self.env.keys() {
+            self.env_origins.entry(name.clone()).or_default().source = Some(Arc::clone(&source));
+        }
+    }
+
+    /// Records the line env variable `name` was declared on.
+    pub(crate) fn set_env_line(&mut self, name: &str, line: usize) {
+        self.env_origins.entry(name.to_string()).or_default().line = Some(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::tests::temp_path;
+    use crate::EnvConflict;
+    use std::fs;
+
+    #[test]
+    fn test_provenance() {
+        let base = temp_path("base.jtl");
+        let overlay = temp_path("overlay.jtl");
+        fs::write(
+            &base,
+            "DOCTYPE=JTL\n>>>ENV;\n>>>host=localhost;\n>>>BEGIN;\n>port=\"80\">web>nginx;\n>port=\"5432\">db>pg;\n>>>END;\n",
+        )
+        .unwrap();
+        fs::write(
+            &overlay,
+            "DOCTYPE=JTL\n>>>ENV;\n>>>region=eu;\n>>>host=example.com;\n>>>BEGIN;\n>port=\"443\">web>nginx;\n>>>END;\n",
+        )
+        .unwrap();
+        let (base_doc, overlay_doc) = (
+            JtlDocument::load(&base).unwrap(),
+            JtlDocument::load(&overlay).unwrap(),
+        );
+
+        let merged = crate::merge(&base_doc, &overlay_doc);
+        let web = merged.elements.iter().find(|e| e.key == "web").unwrap();
+        assert_eq!(web.origin().to_string(), format!("{}:6", overlay.display()));
+        let db = merged.elements.iter().find(|e| e.key == "db").unwrap();
+        assert_eq!(db.origin().source(), Some(base.as_path()));
+        assert_eq!(db.origin().line(), Some(6));
+        assert_eq!(
+            merged.env_origin("host").unwrap().to_string(),
+            format!("{}:4", overlay.display())
+        );
+
+        // Origins survive a second layer.
+        let joined = crate::concat(&[merged, JtlDocument::new()], EnvConflict::Error).unwrap();
+        assert_eq!(
+            joined.env_origin("region").unwrap().source(),
+            Some(overlay.as_path())
+        );
+        assert_eq!(JtlDocument::new().env_origin("host"), None);
+        assert_eq!(Origin::default().to_string(), "unknown origin");
+    }
+}
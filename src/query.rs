@@ -0,0 +1,7 @@
+#[cfg(feature = "json")]
+pub use crate::largest_elements;
+pub use crate::{
+    diff, doctor, doctor_with, explain, first_out_of_order, sort_records, Category, Difference,
+    ElementSize, Explanation, Finding, Lang, LogRecord, Matcher, MemoryUsage, Report, Schema,
+    SearchHit, Severity, Stage, Violation,
+};
@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+use std::iter::Enumerate;
+use std::str::Lines;
+
+use crate::encoding;
+use crate::error::JtlError;
+use crate::scan::{self, Decl, Scanner};
+use crate::RawElement;
+
+/// One piece of a JTL document, as returned by [`Reader`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event<'a> {
+    /// The `DOCTYPE` line.
+    Doctype(&'a str),
+    /// `>>>ENV;`.
+    EnvStart,
+    /// An env variable declaration.
+    EnvVar {
+        /// The variable's name.
+        name: &'a str,
+        /// The variable's value.
+        value: &'a str,
+    },
+    /// The attributes of a `>>>DEFAULTS` declaration, unparsed.
+    Defaults(&'a str),
+    /// `>>>BEGIN;`.
+    BodyStart,
+    /// An element, as written: env references are not substituted.
+    Element(RawElement<'a>),
+    /// A comment line, including its `/*`, `*/` or `>//>` marker.
+    Comment(&'a str),
+    /// `>>>END;`.
+    End,
+}
+
+/// Reads a JTL document as a sequence of [`Event`]s borrowed from the text,
+/// without building elements or JSON.
+///
+/// ```
+/// use jtl_rs::{Event, Reader};
+///
+/// let text = "DOCTYPE=JTL\n>>>BEGIN;\n>port=\"80\">web>nginx;\n>>>END;";
+/// let keys: Vec<&str> = Reader::new(text)
+///     .filter_map(|event| match event {
+///         Ok(Event::Element(element)) => Some(element.key),
+///         _ => None,
+///     })
+///     .collect();
+/// assert_eq!(keys, ["web"]);
+/// ```
+///
+/// Blank lines and text the parser would ignore produce no events. The reader
+/// stops after the first error.
+#[derive(Debug)]
+pub struct Reader<'a> {
+    lines: Enumerate<Lines<'a>>,
+    scanner: Scanner,
+    pending: VecDeque<Event<'a>>,
+    line: usize,
+    done: bool,
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a reader over `text`.
+    pub fn new(text: &'a str) -> Self {
+        let text = text.strip_prefix(encoding::BOM).unwrap_or(text);
+        Reader {
+            lines: text.lines().enumerate(),
+            scanner: Scanner::default(),
+            pending: VecDeque::new(),
+            line: 0,
+            done: false,
+        }
+    }
+
+    /// Returns the 1-based line of the last event returned.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    fn read_line(&mut self, index: usize, text: &'a str) -> Result<(), JtlError> {
+        let trimmed = text.trim();
+        if index == 0 {
+            scan::check_doctype(Some(text)).map_err(|err| JtlError::new(err, Some(1)))?;
+            self.pending.push_back(Event::Doctype(trimmed));
+            return Ok(());
+        }
+        let directive = match trimmed {
+            ">>>ENV;" => Some(Event::EnvStart),
+            ">>>BEGIN;" => Some(Event::BodyStart),
+            ">>>END;" => Some(Event::End),
+            _ => None,
+        };
+        if !trimmed.is_empty() && scan::is_skipped(trimmed) {
+            self.pending.push_back(Event::Comment(trimmed));
+        }
+        for decl in self.scanner.scan_line(text) {
+            self.pending.push_back(match decl {
+                Decl::Env(name, value) => Event::EnvVar { name, value },
+                Decl::Defaults(attrs) => Event::Defaults(attrs),
+                Decl::Element(decl) => Event::Element(
+                    crate::parse_raw_element(decl)
+                        .map_err(|err| JtlError::new(err, Some(index + 1)))?,
+                ),
+            });
+        }
+        self.pending.extend(directive);
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<Event<'a>, JtlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            let Some((index, text)) = self.lines.next() else {
+                if self.line == 0 {
+                    self.done = true;
+                    return Some(Err(JtlError::new(
+                        "invalid JTL document: missing DOCTYPE",
+                        None,
+                    )));
+                }
+                return None;
+            };
+            self.line = index + 1;
+            if let Err(err) = self.read_line(index, text) {
+                self.done = true;
+                return Some(Err(err));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reader_events() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>host=a;\n/* body */\n>>>BEGIN;\n\
+                    >port=\"80\">web>$env:host; >n=\"1\">db>pg;\n>>>END;";
+        let events: Vec<Event> = Reader::new(text).map(Result::unwrap).collect();
+        assert_eq!(events.len(), 8);
+        assert_eq!(events[0], Event::Doctype("DOCTYPE=JTL"));
+        assert_eq!(events[1], Event::EnvStart);
+        assert_eq!(
+            events[2],
+            Event::EnvVar {
+                name: "host",
+                value: "a"
+            }
+        );
+        assert_eq!(events[3], Event::Comment("/* body */"));
+        assert_eq!(events[4], Event::BodyStart);
+        let Event::Element(web) = &events[5] else {
+            panic!("expected an element");
+        };
+        assert_eq!((web.key, web.content), ("web", "$env:host"));
+        assert_eq!(web.attrs, [("port", "80")]);
+        assert!(matches!(&events[6], Event::Element(db) if db.key == "db"));
+        assert_eq!(events[7], Event::End);
+
+        let mut reader = Reader::new("DOCTYPE=JTL\n>>>BEGIN;\n>oops;\n");
+        assert!(reader.by_ref().take(2).all(|event| event.is_ok()));
+        assert_eq!(reader.next().unwrap().unwrap_err().line(), Some(3));
+        assert!(reader.next().is_none());
+        assert!(Reader::new("").next().unwrap().is_err());
+    }
+}
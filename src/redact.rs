@@ -0,0 +1,209 @@
+use regex::Regex;
+
+use crate::JtlDocument;
+
+/// What [`redact`] masks.
+#[derive(Debug, Clone)]
+pub struct RedactRules {
+    names: Vec<String>,
+    name_patterns: Vec<Regex>,
+    value_patterns: Vec<Regex>,
+    keys: Vec<String>,
+    mask: String,
+}
+
+impl Default for RedactRules {
+    fn default() -> Self {
+        RedactRules {
+            names: Vec::new(),
+            name_patterns: Vec::new(),
+            value_patterns: Vec::new(),
+            keys: Vec::new(),
+            mask: "[REDACTED]".to_string(),
+        }
+    }
+}
+
+impl RedactRules {
+    /// Creates rules that mask nothing, with `[REDACTED]` as the mask.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Masks the whole value of every attribute and env variable called `name`,
+    /// ignoring case.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.names.push(name.into());
+        self
+    }
+
+    /// Masks the whole value of every attribute and env variable whose name
+    /// contains a match for `pattern`.
+    pub fn name_pattern(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.name_patterns.push(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Masks every match of `pattern` in attribute values, content and env
+    /// values, leaving the rest of the text alone.
+    pub fn value_pattern(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        self.value_patterns.push(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Masks the content of every element with the key `key`.
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.keys.push(key.into());
+        self
+    }
+
+    /// Replaces `[REDACTED]` with `mask`.
+    pub fn mask(mut self, mask: impl Into<String>) -> Self {
+        self.mask = mask.into();
+        self
+    }
+
+    fn hides_name(&self, name: &str) -> bool {
+        self.names
+            .iter()
+            .any(|hidden| hidden.eq_ignore_ascii_case(name))
+            || self
+                .name_patterns
+                .iter()
+                .any(|pattern| pattern.is_match(name))
+    }
+
+    fn scrub(&self, value: &str) -> String {
+        self.value_patterns
+            .iter()
+            .fold(value.to_string(), |value, pattern| {
+                pattern.replace_all(&value, self.mask.as_str()).into_owned()
+            })
+    }
+
+    fn value(&self, name: &str, value: &str) -> String {
+        if self.hides_name(name) {
+            self.mask.clone()
+        } else {
+            self.scrub(value)
+        }
+    }
+}
+
+/// Returns a copy of `document` with everything matched by `rules` masked.
+///
+/// Values of masked env variables are masked wherever they turn up in content
+/// and attribute values too, including as the upper, lower or trimmed forms
+/// that interpolation functions produce, since they would otherwise give the
+/// value away.
+pub fn redact(document: &JtlDocument, rules: &RedactRules) -> JtlDocument {
+    let mut redacted = JtlDocument::new();
+    for (name, value) in &document.env {
+        redacted.env.insert(name.clone(), rules.value(name, value));
+    }
+    let mut leaked: Vec<String> = document
+        .env
+        .iter()
+        .filter(|(name, _)| rules.hides_name(name))
+        .flat_map(|(_, value)| {
+            let value = value.trim();
+            [
+                value.to_string(),
+                value.to_uppercase(),
+                value.to_lowercase(),
+            ]
+        })
+        .filter(|value| !value.is_empty())
+        .collect();
+    // Longest first, so a value containing another is masked whole.
+    leaked.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    leaked.dedup();
+    let unleak = |mut text: String| {
+        for value in &leaked {
+            if text.contains(value.as_str()) {
+                text = text.replace(value.as_str(), &rules.mask);
+            }
+        }
+        text
+    };
+
+    redacted.elements = document
+        .elements
+        .iter()
+        .map(|element| {
+            let mut element = element.clone();
+            for (name, value) in &mut element.attrs {
+                *value = unleak(rules.value(name, value));
+            }
+            element.content = if rules.keys.contains(&element.key) {
+                rules.mask.clone()
+            } else {
+                unleak(rules.scrub(&element.content))
+            };
+            element
+        })
+        .collect();
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let document = JtlDocument::parse(
+            r#"DOCTYPE=JTL
+>>>ENV;
+>>>db_password=hunter2;
+>>>host=db.internal;
+>>>BEGIN;
+>user="admin" Password="secret">db>$env:db_password;
+>api_token="abc123" note="key sk-live-42 leaked">api>https://example.com;
+>kind="pem">cert>-----BEGIN KEY-----;
+>>>END;"#,
+        )
+        .unwrap();
+        let rules = RedactRules::new()
+            .name("password")
+            .name_pattern("(?i)token|password")
+            .unwrap()
+            .value_pattern(r"sk-live-\w+")
+            .unwrap()
+            .key("cert");
+        let redacted = redact(&document, &rules);
+
+        assert_eq!(redacted.env["db_password"], "[REDACTED]");
+        assert_eq!(redacted.env["host"], "db.internal");
+        let db = &redacted.elements[0];
+        assert_eq!(db.attrs[0].1, "admin");
+        assert_eq!(db.attrs[1].1, "[REDACTED]");
+        assert_eq!(db.content, "[REDACTED]");
+        let api = &redacted.elements[1];
+        assert_eq!(api.attrs[0].1, "[REDACTED]");
+        assert_eq!(api.attrs[1].1, "key [REDACTED] leaked");
+        assert_eq!(api.content, "https://example.com");
+        assert_eq!(redacted.elements[2].content, "[REDACTED]");
+
+        let masked = redact(&document, &RedactRules::new().key("cert").mask("***"));
+        assert_eq!(masked.elements[2].content, "***");
+        assert!(masked.write_to(Vec::new()).is_ok());
+    }
+
+    #[test]
+    fn test_redact_interpolated_secret() {
+        let document = JtlDocument::parse(
+            r#"DOCTYPE=JTL
+>>>ENV;
+>>>db_password=hunter2;
+>>>BEGIN;
+>url="postgres://admin:hunter2@db">db>password is ${env:db_password|upper};
+>>>END;"#,
+        )
+        .unwrap();
+        let redacted = redact(&document, &RedactRules::new().name("db_password"));
+        let db = &redacted.elements[0];
+        assert_eq!(db.attrs[0].1, "postgres://admin:[REDACTED]@db");
+        assert_eq!(db.content, "password is [REDACTED]");
+    }
+}
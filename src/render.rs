@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::options::ParseOptions;
+use crate::scan::{self, Decl, Defaults, Scanner};
+use crate::write;
+use crate::JtlDocument;
+
+/// Resolves a JTL document and writes it back out as plain JTL.
+///
+/// Every `$env:NAME` reference is replaced by its value, with `overrides`
+/// taking precedence over the document's own `>>>ENV;` block. The output keeps
+/// the resolved environment, sorted by name, so it can be fed to any other JTL
+/// tool; comments and layout are not kept. The grammar has no includes or
+/// conditionals yet, so env substitution is the only resolution performed.
+pub fn render(text: &str, overrides: &HashMap<String, String>) -> Result<String, Box<dyn Error>> {
+    scan::check_doctype(text.lines().next())?;
+
+    let options = ParseOptions::default();
+    let mut document = JtlDocument::new();
+    document.env = overrides.clone();
+    let mut defaults = Defaults::default();
+    let mut scanner = Scanner::default();
+    for line in text.lines() {
+        for decl in scanner.scan_line(line) {
+            match decl {
+                Decl::Env(name, value) => {
+                    if !overrides.contains_key(name) {
+                        document.env.insert(name.to_string(), value.to_string());
+                    }
+                }
+                Decl::Defaults(attrs) => defaults.extend(attrs)?,
+                Decl::Element(decl) => {
+                    document.elements.extend(crate::parse_element(
+                        decl,
+                        &document.env,
+                        &defaults,
+                        &options,
+                    )?);
+                }
+            }
+        }
+    }
+
+    let written = document.write_to(Vec::new())?;
+    Ok(String::from_utf8(written)?)
+}
+
+/// Rewrites the values of the `>>>ENV;` block in place, leaving every other
+/// byte of `text` untouched.
+///
+/// `rewrite` is called with each declared name and value, in order, and
+/// returns the new value, or `None` to keep it. Comments, layout and the body
+/// are kept exactly as they are, which suits stamping deployment values into a
+/// template.
+pub fn rewrite_env(
+    text: &str,
+    mut rewrite: impl FnMut(&str, &str) -> Option<String>,
+) -> Result<String, Box<dyn Error>> {
+    scan::check_doctype(text.lines().next())?;
+
+    let mut out = String::with_capacity(text.len());
+    let mut scanner = Scanner::default();
+    for line in text.split_inclusive('\n') {
+        let mut copied = 0;
+        for decl in scanner.scan_line(line) {
+            let Decl::Env(name, value) = decl else {
+                continue;
+            };
+            let Some(new) = rewrite(name, value) else {
+                continue;
+            };
+            write::check_env(name, &new)?;
+            // Declarations are slices of `line`, so their offsets are exact.
+            let start = value.as_ptr() as usize - line.as_ptr() as usize;
+            out.push_str(&line[copied..start]);
+            out.push_str(&new);
+            copied = start + value.len();
+        }
+        out.push_str(&line[copied..]);
+    }
+    Ok(out)
+}
+
+/// Counts the `$env:NAME` and `${env:NAME}` references in the body of a JTL
+/// document, by name.
+///
+/// Names that are referenced but never declared are included, which makes
+/// this useful for spotting typos.
+pub fn env_references(text: &str) -> Result<HashMap<String, usize>, Box<dyn Error>> {
+    scan::check_doctype(text.lines().next())?;
+
+    let mut references = HashMap::new();
+    let mut scanner = Scanner::default();
+    for line in text.lines() {
+        for decl in scanner.scan_line(line) {
+            if let Decl::Element(decl) = decl {
+                let raw = crate::parse_raw_element(decl)?;
+                let names = crate::env_reference(raw.content)
+                    .into_iter()
+                    .chain(crate::interp::references(raw.content));
+                for name in names {
+                    *references.entry(name.to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+    Ok(references)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let text = r#"DOCTYPE=JTL
+>>>ENV;
+>>>host=localhost;
+>>>port=8080;
+>>>BEGIN;
+/* servers */
+>role="web">web>$env:host;
+>role="web">web_port>$env:port;
+>>>END;"#;
+        let overrides = HashMap::from([("host".to_string(), "example.com".to_string())]);
+        let rendered = render(text, &overrides).unwrap();
+        assert_eq!(
+            rendered,
+            "DOCTYPE=JTL\n>>>ENV;\n>>>host=example.com;\n>>>port=8080;\n>>>BEGIN;\n\
+             >role=\"web\">web>example.com;\n>role=\"web\">web_port>8080;\n>>>END;\n"
+        );
+        assert_eq!(render(&rendered, &HashMap::new()).unwrap(), rendered);
+    }
+
+    #[test]
+    fn test_rewrite_env() {
+        let text =
+            "DOCTYPE=JTL\r\n>>>ENV;\r\n/* stamped */\r\n>>>host = localhost;  >>>port=80;\r\n\
+                    >>>BEGIN;\r\n>host=\"x\">web>$env:host;\r\n>>>END;";
+        let mut seen = Vec::new();
+        let rewritten = rewrite_env(text, |name, value| {
+            seen.push(format!("{}={}", name, value));
+            (name == "host").then(|| "example.com".to_string())
+        })
+        .unwrap();
+        assert_eq!(seen, ["host=localhost", "port=80"]);
+        assert_eq!(rewritten, text.replacen("localhost", "example.com", 1));
+
+        let err = rewrite_env(text, |_, _| Some("a;b".to_string())).unwrap_err();
+        assert_eq!(err.to_string(), "invalid value for env variable `host`");
+    }
+
+    #[test]
+    fn test_env_references() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>a=1;\n>>>b=2;\n>>>BEGIN;\n\
+                    >n=\"1\">x>$env:a;\n>n=\"2\">y>$env:a;\n>n=\"3\">z>$env:typo;\n\
+                    >n=\"4\">w>${env:b|upper}-${env:a};\n>>>END;";
+        let references = env_references(text).unwrap();
+        assert_eq!(references.len(), 3);
+        assert_eq!(references["a"], 3);
+        assert_eq!(references["b"], 1);
+        assert_eq!(references["typo"], 1);
+    }
+}
@@ -0,0 +1,105 @@
+use regex::Regex;
+use std::error::Error;
+
+use crate::scan::{Decl, Scanner};
+use crate::JtlDocument;
+
+/// Parses `text`, failing if writing the document back out would lose or
+/// change anything.
+///
+/// Besides re-serializing and re-parsing the result, every line is checked for
+/// text the parser skips without a word: fragments after a stray `;`, attribute
+/// text that does not match `name="value"`, and `name="value"` pairs in a key or
+/// content, which the parser reads as extra attributes. Layout, comments and
+/// env references are not preserved and do not count as changes.
+pub fn parse_strict_roundtrip(text: &str) -> Result<JtlDocument, Box<dyn Error>> {
+    let document = JtlDocument::parse(text)?;
+
+    let attr_regex = Regex::new(r#"(\w+)="([^"]+)""#)?;
+    let mut scanner = Scanner::default();
+    for (index, line) in text.lines().enumerate().skip(1) {
+        let mismatch =
+            |detail: String| format!("round-trip mismatch on line {}: {}", index + 1, detail);
+        for decl in scanner.scan_line(line) {
+            if let Decl::Element(decl) = decl {
+                let body = &decl[1..];
+                let split = body.find('>').unwrap_or(body.len());
+                let leftover = attr_regex.replace_all(&body[..split], "");
+                if !leftover.trim().is_empty() {
+                    return Err(
+                        mismatch(format!("unparsed attribute text `{}`", leftover.trim())).into(),
+                    );
+                }
+                if let Some(found) = attr_regex.find(&body[split..]) {
+                    return Err(
+                        mismatch(format!("`{}` is read as an attribute", found.as_str())).into(),
+                    );
+                }
+            }
+        }
+        if let Some(unused) = scanner.unused(line).next() {
+            return Err(mismatch(format!("`{}` is not part of any declaration", unused)).into());
+        }
+    }
+
+    let written = document
+        .write_to(Vec::new())
+        .map_err(|err| format!("round-trip mismatch: {}", err))?;
+    let reparsed = JtlDocument::parse(&String::from_utf8(written)?)?;
+    if reparsed.env != document.env {
+        return Err("round-trip mismatch: environment reads back differently".into());
+    }
+    if let Some(element) = document
+        .elements
+        .iter()
+        .zip(&reparsed.elements)
+        .find(|(original, reread)| original != reread)
+        .map(|(original, _)| original)
+    {
+        return Err(format!(
+            "round-trip mismatch: element `{}` reads back differently",
+            element.key
+        )
+        .into());
+    }
+    if reparsed.elements.len() != document.elements.len() {
+        return Err("round-trip mismatch: element count changed".into());
+    }
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(body: &str) -> Result<JtlDocument, String> {
+        let text = format!(
+            "DOCTYPE=JTL\n>>>ENV;\n>>>foo=bar;\n>>>BEGIN;\n{}\n>>>END;",
+            body
+        );
+        parse_strict_roundtrip(&text).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_strict_roundtrip() {
+        let document = check(">a=\"1\" b=\"2\">k>$env:foo;\n/* note */\n>c=\"3\">x>a>b;").unwrap();
+        assert_eq!(document.elements.len(), 2);
+
+        assert_eq!(
+            check(">a=\"1\">k>x;y;").unwrap_err(),
+            "round-trip mismatch on line 5: `y` is not part of any declaration"
+        );
+        assert_eq!(
+            check(">a=\"1\" b>k>x;").unwrap_err(),
+            "round-trip mismatch on line 5: unparsed attribute text `b`"
+        );
+        assert_eq!(
+            check(">a=\"1\">k>see b=\"2\";").unwrap_err(),
+            "round-trip mismatch on line 5: `b=\"2\"` is read as an attribute"
+        );
+        assert_eq!(
+            check(">a=\"1\">k\"q>x;").unwrap_err(),
+            "round-trip mismatch: invalid element key `k\"q`"
+        );
+    }
+}
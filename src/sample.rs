@@ -0,0 +1,109 @@
+use crate::JtlDocument;
+
+impl JtlDocument {
+    /// Returns a document with the same environment and the first `n` elements.
+    pub fn head(&self, n: usize) -> JtlDocument {
+        self.with_elements((0..n.min(self.elements.len())).collect())
+    }
+
+    /// Returns a document with the same environment and the last `n` elements.
+    pub fn tail(&self, n: usize) -> JtlDocument {
+        let len = self.elements.len();
+        self.with_elements((len - n.min(len)..len).collect())
+    }
+
+    /// Returns a document with the same environment and `n` elements picked at
+    /// random, in their original order.
+    ///
+    /// The choice depends only on `seed` and the number of elements, so the
+    /// same seed always yields the same fixture from the same document.
+    pub fn sample(&self, n: usize, seed: u64) -> JtlDocument {
+        // Reservoir sampling.
+        let mut rng = SplitMix64(seed);
+        let mut picked: Vec<usize> = (0..n.min(self.elements.len())).collect();
+        for index in n..self.elements.len() {
+            let slot = rng.below(index + 1);
+            if slot < n {
+                picked[slot] = index;
+            }
+        }
+        picked.sort_unstable();
+        self.with_elements(picked)
+    }
+
+    fn with_elements(&self, indices: Vec<usize>) -> JtlDocument {
+        let mut document = JtlDocument::new();
+        document.env = self.env.clone();
+        document.format = self.format;
+        document.elements = indices
+            .into_iter()
+            .map(|index| self.elements[index].clone())
+            .collect();
+        document
+    }
+}
+
+/// The splitmix64 generator: tiny, fast and fully determined by its seed.
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{JtlDocument, JtlElement};
+
+    fn numbered(n: usize) -> JtlDocument {
+        let mut document = JtlDocument::new();
+        document.env.insert("kept".to_string(), "yes".to_string());
+        for index in 0..n {
+            document.elements.push(
+                JtlElement::new(format!("e{}", index), "x").with_attr("n", index.to_string()),
+            );
+        }
+        document
+    }
+
+    fn keys(document: &JtlDocument) -> Vec<&str> {
+        document.elements.iter().map(|e| e.key.as_str()).collect()
+    }
+
+    #[test]
+    fn test_head_tail() {
+        let document = numbered(5);
+        assert_eq!(keys(&document.head(2)), ["e0", "e1"]);
+        assert_eq!(keys(&document.tail(2)), ["e3", "e4"]);
+        assert_eq!(document.tail(9).elements.len(), 5);
+        assert_eq!(document.head(0).env["kept"], "yes");
+    }
+
+    #[test]
+    fn test_sample() {
+        let document = numbered(1000);
+        let sample = document.sample(10, 42);
+        assert_eq!(sample.elements.len(), 10);
+        assert_eq!(sample, document.sample(10, 42));
+        assert_ne!(sample, document.sample(10, 43));
+        let indices: Vec<usize> = sample
+            .elements
+            .iter()
+            .map(|e| e.attrs[0].1.parse().unwrap())
+            .collect();
+        assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(sample.env["kept"], "yes");
+        assert_eq!(numbered(3).sample(10, 1).elements.len(), 3);
+        assert!(sample.write_to(Vec::new()).is_ok());
+    }
+}
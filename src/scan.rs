@@ -0,0 +1,247 @@
+use crate::options::DoctypeCheck;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Matches one `name="value"` attribute, capturing the name and the value.
+pub(crate) static ATTR_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(\w+)="([^"]+)""#).expect("valid regex"));
+
+/// A declaration found by [`Scanner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decl<'a> {
+    /// `>>>name=value` inside the `>>>ENV;` block.
+    Env(&'a str, &'a str),
+    /// An element declaration inside the body, without its trailing `;`.
+    Element(&'a str),
+    /// The attributes of a `>>>DEFAULTS` declaration before the body.
+    Defaults(&'a str),
+}
+
+/// Splits JTL lines into declarations, tracking which section they are in.
+///
+/// The `DOCTYPE` line is not checked here.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Scanner {
+    in_env: bool,
+    in_body: bool,
+    ended: bool,
+}
+
+impl Scanner {
+    /// Returns the declarations on one line of input.
+    pub(crate) fn scan_line<'a>(&mut self, line: &'a str) -> impl Iterator<Item = Decl<'a>> {
+        let line = line.trim();
+        let skip = is_skipped(line) || self.directive(line);
+        let (in_env, in_body) = (self.in_env, self.in_body);
+
+        // Handle multiple declarations per line.
+        line.split(';')
+            .filter(move |_| !skip)
+            .filter_map(move |decl| {
+                let decl = decl.trim();
+                if decl.is_empty() || decl.starts_with(">//>") {
+                    return None;
+                }
+                if let Some(attrs) = defaults_decl(decl).filter(|_| !in_body) {
+                    Some(Decl::Defaults(attrs))
+                } else if in_env && decl.starts_with(">>>") {
+                    let content = &decl[3..];
+                    let eq_index = content.find('=')?;
+                    Some(Decl::Env(
+                        content[..eq_index].trim(),
+                        content[eq_index + 1..].trim(),
+                    ))
+                } else if in_body && decl.starts_with('>') {
+                    Some(Decl::Element(decl))
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Returns the parts of `line` that [`scan_line`](Scanner::scan_line) drops
+    /// without reading them, such as text after a `;` that does not start a new
+    /// declaration. Call it after `scan_line` so section changes are applied.
+    pub(crate) fn unused<'a>(&self, line: &'a str) -> impl Iterator<Item = &'a str> {
+        let line = line.trim();
+        let skip = is_skipped(line) || matches!(line, ">>>ENV;" | ">>>BEGIN;" | ">>>END;");
+        let (in_env, in_body) = (self.in_env, self.in_body);
+
+        line.split(';')
+            .filter(move |_| !skip)
+            .map(str::trim)
+            .filter(move |decl| {
+                let read = (!in_body && defaults_decl(decl).is_some())
+                    || (in_env && decl.starts_with(">>>") && decl.contains('='))
+                    || (in_body && decl.starts_with('>'));
+                !(decl.is_empty() || decl.starts_with(">//>") || read)
+            })
+    }
+
+    /// Returns whether the scanner is inside the `>>>BEGIN;` block.
+    pub(crate) fn in_body(&self) -> bool {
+        self.in_body
+    }
+
+    /// Returns whether a `>>>END;` line has been seen.
+    pub(crate) fn ended(&self) -> bool {
+        self.ended
+    }
+
+    /// Applies a section directive, returning whether `line` was one.
+    fn directive(&mut self, line: &str) -> bool {
+        match line {
+            ">>>ENV;" => self.in_env = true,
+            ">>>BEGIN;" => {
+                self.in_env = false;
+                self.in_body = true;
+            }
+            ">>>END;" => {
+                self.in_body = false;
+                self.ended = true;
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Returns the attribute text of a `>>>DEFAULTS` declaration.
+fn defaults_decl(decl: &str) -> Option<&str> {
+    decl.strip_prefix(">>>DEFAULTS")
+        .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+/// Attributes merged into every element that does not set them itself.
+///
+/// Several `>>>DEFAULTS` declarations add up, and a later value for the same
+/// attribute replaces an earlier one. `S` is `String`, or `&str` to borrow
+/// from the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Defaults<S = String>(Vec<(S, S)>);
+
+impl<S> Default for Defaults<S> {
+    fn default() -> Self {
+        Defaults(Vec::new())
+    }
+}
+
+impl<S: AsRef<str>> Defaults<S> {
+    /// Adds the attributes of a [`Decl::Defaults`] declaration.
+    pub(crate) fn extend<'a>(&mut self, attrs: &'a str) -> Result<(), String>
+    where
+        S: From<&'a str>,
+    {
+        let mut found = false;
+        for cap in ATTR_REGEX.captures_iter(attrs) {
+            found = true;
+            let (name, value) = (cap.get(1).unwrap().as_str(), cap.get(2).unwrap().as_str());
+            match self.0.iter_mut().find(|(known, _)| known.as_ref() == name) {
+                Some(entry) => entry.1 = value.into(),
+                None => self.0.push((name.into(), value.into())),
+            }
+        }
+        if !found {
+            return Err("invalid JTL document: >>>DEFAULTS without attributes".into());
+        }
+        Ok(())
+    }
+
+    /// Returns the defaults that `attrs` does not set, by name as written.
+    fn missing<'s>(&'s self, attrs: &[(&str, &str)]) -> Vec<&'s (S, S)> {
+        self.0
+            .iter()
+            .filter(|(name, _)| !attrs.iter().any(|(known, _)| *known == name.as_ref()))
+            .collect()
+    }
+}
+
+impl Defaults {
+    /// Returns the default attributes, in the order first declared.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Appends each default that `attrs` does not already set.
+    pub(crate) fn apply<'a>(&'a self, attrs: &mut Vec<(&'a str, &'a str)>) {
+        for (name, value) in self.missing(attrs) {
+            attrs.push((name, value));
+        }
+    }
+}
+
+impl<'a> Defaults<&'a str> {
+    /// Appends each default that `attrs` does not already set.
+    pub(crate) fn apply_borrowed(&self, attrs: &mut Vec<(&'a str, &'a str)>) {
+        for &(name, value) in self.missing(attrs) {
+            attrs.push((name, value));
+        }
+    }
+}
+
+/// Returns whether a trimmed line is blank or a comment.
+pub(crate) fn is_skipped(line: &str) -> bool {
+    line.is_empty() || line.starts_with("/*") || line.starts_with("*/") || line.starts_with(">//>")
+}
+
+/// Checks the first line of a document against a [`DoctypeCheck`].
+pub(crate) fn check_doctype_with(
+    first_line: Option<&str>,
+    check: DoctypeCheck,
+    versions: Option<&[String]>,
+) -> Result<(), String> {
+    const MARKER: &str = "DOCTYPE=JTL";
+    let missing = || "invalid JTL document: missing DOCTYPE".to_string();
+    let line = first_line.ok_or_else(missing)?;
+    let start = match check {
+        DoctypeCheck::Contains => line.find(MARKER),
+        DoctypeCheck::Prefix | DoctypeCheck::Exact => line.starts_with(MARKER).then_some(0),
+    }
+    .ok_or_else(missing)?;
+
+    let rest = &line[start + MARKER.len()..];
+    let versioned = rest.starts_with(char::is_whitespace);
+    let well_formed = match check {
+        DoctypeCheck::Contains => true,
+        DoctypeCheck::Prefix => rest.is_empty() || versioned,
+        DoctypeCheck::Exact => {
+            let rest = rest.trim_end();
+            rest.is_empty()
+                || rest.strip_prefix(' ').is_some_and(|version| {
+                    !version.is_empty() && !version.contains(char::is_whitespace)
+                })
+        }
+    };
+    if !well_formed {
+        return Err("invalid JTL document: unexpected text after DOCTYPE".into());
+    }
+
+    let version = doctype_version(line);
+    if let (Some(versions), Some(version)) = (versions, version) {
+        if !versions.iter().any(|accepted| accepted == version) {
+            return Err(format!(
+                "invalid JTL document: unsupported DOCTYPE version `{}`",
+                version
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the version of a `DOCTYPE=JTL <version>` line.
+pub(crate) fn doctype_version(first_line: &str) -> Option<&str> {
+    let (_, rest) = first_line.split_once("DOCTYPE=JTL")?;
+    rest.starts_with(char::is_whitespace)
+        .then(|| rest.split_whitespace().next())
+        .flatten()
+}
+
+/// Checks the first line of a document for the `DOCTYPE` marker.
+pub(crate) fn check_doctype(first_line: Option<&str>) -> Result<(), &'static str> {
+    match first_line {
+        Some(line) if line.contains("DOCTYPE=JTL") => Ok(()),
+        _ => Err("invalid JTL document: missing DOCTYPE"),
+    }
+}
@@ -0,0 +1,328 @@
+use crate::document::JtlDocument;
+use crate::element::JtlElement;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+enum Rule {
+    Required(String),
+    OneOf(String, Vec<String>),
+    Pattern(String, Regex),
+    KeyPattern(Regex),
+    KeyMaxLen(usize),
+    ReservedPrefix(String),
+    Embedded,
+}
+
+/// Attribute and key rules that every element of a document must satisfy.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    rules: Vec<Rule>,
+    unique_keys: bool,
+}
+
+impl Schema {
+    /// Creates a schema with no rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requires every element to have the attribute `name`.
+    pub fn require(mut self, name: impl Into<String>) -> Self {
+        self.rules.push(Rule::Required(name.into()));
+        self
+    }
+
+    /// Restricts the attribute `name`, where present, to one of `values`.
+    pub fn one_of(mut self, name: impl Into<String>, values: &[&str]) -> Self {
+        let values = values.iter().map(|value| value.to_string()).collect();
+        self.rules.push(Rule::OneOf(name.into(), values));
+        self
+    }
+
+    /// Requires the attribute `name`, where present, to match `pattern` in full.
+    pub fn pattern(mut self, name: impl Into<String>, pattern: &str) -> Result<Self, regex::Error> {
+        let regex = Regex::new(&format!("^(?:{})$", pattern))?;
+        self.rules.push(Rule::Pattern(name.into(), regex));
+        Ok(self)
+    }
+
+    /// Requires every `KEY` to match `pattern` in full, such as `[a-z0-9_]+`.
+    pub fn key_pattern(mut self, pattern: &str) -> Result<Self, regex::Error> {
+        let regex = Regex::new(&format!("^(?:{})$", pattern))?;
+        self.rules.push(Rule::KeyPattern(regex));
+        Ok(self)
+    }
+
+    /// Limits every `KEY` to `max` characters.
+    pub fn key_max_len(mut self, max: usize) -> Self {
+        self.rules.push(Rule::KeyMaxLen(max));
+        self
+    }
+
+    /// Forbids keys starting with `prefix`, which is kept for internal use.
+    pub fn reserved_key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.rules.push(Rule::ReservedPrefix(prefix.into()));
+        self
+    }
+
+    /// Requires the content of every element tagged with a `lang` attribute
+    /// to be well-formed in that language, as checked by
+    /// [`Lang::check`](crate::Lang::check).
+    pub fn check_embedded(mut self) -> Self {
+        self.rules.push(Rule::Embedded);
+        self
+    }
+
+    /// Requires every `KEY` to be used by one element only.
+    ///
+    /// Uniqueness is a property of the whole document, so it is checked by
+    /// [`validate`](Schema::validate) and
+    /// [`validate_insert`](Schema::validate_insert), and by the edit guards
+    /// of a document the schema is attached to, but not by
+    /// [`validate_element`](Schema::validate_element).
+    pub fn unique_keys(mut self) -> Self {
+        self.unique_keys = true;
+        self
+    }
+
+    /// Checks every element of `document`.
+    pub fn validate(&self, document: &JtlDocument) -> Vec<Violation> {
+        let mut first_use: HashMap<&str, usize> = HashMap::new();
+        let mut violations = Vec::new();
+        for (index, element) in document.elements.iter().enumerate() {
+            violations.extend(self.validate_element(index, element));
+            if !self.unique_keys {
+                continue;
+            }
+            let first = *first_use.entry(&element.key).or_insert(index);
+            if first != index {
+                violations.push(Violation {
+                    index,
+                    key: element.key.clone(),
+                    message: format!("duplicate key, first used by element {}", first),
+                });
+            }
+        }
+        violations
+    }
+
+    /// Checks `element` as if it were inserted into `document` at `index`:
+    /// its own rules, and that no element of `document` already uses its key.
+    pub fn validate_insert(
+        &self,
+        document: &JtlDocument,
+        index: usize,
+        element: &JtlElement,
+    ) -> Vec<Violation> {
+        self.validate_placed(document, index, element, false)
+    }
+
+    /// Like [`validate_insert`](Schema::validate_insert), or with `replaces`,
+    /// as if `element` replaced the element at `index`.
+    pub(crate) fn validate_placed(
+        &self,
+        document: &JtlDocument,
+        index: usize,
+        element: &JtlElement,
+        replaces: bool,
+    ) -> Vec<Violation> {
+        let mut violations = self.validate_element(index, element);
+        if !self.unique_keys {
+            return violations;
+        }
+        let other = document
+            .elements
+            .iter()
+            .enumerate()
+            .filter(|&(other, _)| !(replaces && other == index))
+            .find(|(_, other)| other.key == element.key);
+        if let Some((other, _)) = other {
+            // Position of the other element once `element` is in place.
+            let other = if !replaces && other >= index {
+                other + 1
+            } else {
+                other
+            };
+            violations.push(Violation {
+                index,
+                key: element.key.clone(),
+                message: format!("duplicate key, also used by element {}", other),
+            });
+        }
+        violations
+    }
+
+    /// Checks a single element, reported as being at `index`.
+    pub fn validate_element(&self, index: usize, element: &JtlElement) -> Vec<Violation> {
+        let attr = |name: &str| element.attr(name);
+        let violation = |message: String| Violation {
+            index,
+            key: element.key.clone(),
+            message,
+        };
+
+        let mut violations = Vec::new();
+        for rule in &self.rules {
+            match rule {
+                Rule::Required(name) if attr(name).is_none() => {
+                    violations.push(violation(format!("missing required attribute `{}`", name)));
+                }
+                Rule::OneOf(name, values) => {
+                    if let Some(value) =
+                        attr(name).filter(|value| !values.iter().any(|v| v == value))
+                    {
+                        violations.push(violation(format!(
+                            "attribute `{}` has value `{}`, expected one of {}",
+                            name,
+                            value,
+                            values.join(", ")
+                        )));
+                    }
+                }
+                Rule::Pattern(name, regex) => {
+                    if let Some(value) = attr(name).filter(|value| !regex.is_match(value)) {
+                        violations.push(violation(format!(
+                            "attribute `{}` has value `{}`, which does not match the pattern",
+                            name, value
+                        )));
+                    }
+                }
+                Rule::KeyPattern(regex) if !regex.is_match(&element.key) => {
+                    violations.push(violation("key does not match the pattern".to_string()));
+                }
+                Rule::KeyMaxLen(max) if element.key.chars().count() > *max => {
+                    violations.push(violation(format!("key is longer than {} characters", max)));
+                }
+                Rule::ReservedPrefix(prefix) if element.key.starts_with(prefix.as_str()) => {
+                    violations.push(violation(format!(
+                        "key uses the reserved prefix `{}`",
+                        prefix
+                    )));
+                }
+                Rule::Embedded => {
+                    if let Some(lang) = element.lang() {
+                        if let Err(err) = lang.check(&element.content) {
+                            violations
+                                .push(violation(format!("content is not valid {}: {}", lang, err)));
+                        }
+                    }
+                }
+                Rule::Required(_)
+                | Rule::KeyPattern(_)
+                | Rule::KeyMaxLen(_)
+                | Rule::ReservedPrefix(_) => {}
+            }
+        }
+        violations
+    }
+}
+
+/// A schema rule broken by an element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Violation {
+    /// Position of the element in the document.
+    pub index: usize,
+    /// The element's `KEY`.
+    pub key: String,
+    /// What is wrong with the element.
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "element `{}`: {}", self.key, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let schema = Schema::new()
+            .require("type")
+            .one_of("type", &["user", "group"])
+            .pattern("port", r"\d+")
+            .unwrap();
+
+        let mut doc = JtlDocument::new();
+        doc.elements.push(
+            JtlElement::new("ok", "x")
+                .with_attr("type", "user")
+                .with_attr("port", "80"),
+        );
+        doc.elements
+            .push(JtlElement::new("untyped", "x").with_attr("port", "80"));
+        doc.elements.push(
+            JtlElement::new("bad", "x")
+                .with_attr("type", "robot")
+                .with_attr("port", "eighty"),
+        );
+
+        let messages: Vec<String> = schema
+            .validate(&doc)
+            .iter()
+            .map(Violation::to_string)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "element `untyped`: missing required attribute `type`",
+                "element `bad`: attribute `type` has value `robot`, expected one of user, group",
+                "element `bad`: attribute `port` has value `eighty`, which does not match the pattern",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_rules() {
+        let schema = Schema::new()
+            .key_pattern("[a-z_]+")
+            .unwrap()
+            .key_max_len(8)
+            .reserved_key_prefix("_")
+            .unique_keys();
+
+        let mut doc = JtlDocument::new();
+        for key in ["web", "Web", "_internal", "web", "much_too_long"] {
+            doc.elements
+                .push(JtlElement::new(key, "x").with_attr("n", "1"));
+        }
+        let messages: Vec<String> = schema
+            .validate(&doc)
+            .iter()
+            .map(Violation::to_string)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "element `Web`: key does not match the pattern",
+                "element `_internal`: key is longer than 8 characters",
+                "element `_internal`: key uses the reserved prefix `_`",
+                "element `web`: duplicate key, first used by element 0",
+                "element `much_too_long`: key is longer than 8 characters",
+            ]
+        );
+        assert!(schema.validate_element(3, &doc.elements[3]).is_empty());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_check_embedded() {
+        let schema = Schema::new().check_embedded();
+        let ok = JtlElement::new("cfg", "{\"a\": 1}").with_attr("lang", "json");
+        let bad = JtlElement::new("cfg", "{\"a\": }").with_attr("lang", "json");
+        let sql = JtlElement::new("q", "select").with_attr("lang", "sql");
+        assert!(schema.validate_element(0, &ok).is_empty());
+        assert!(schema.validate_element(0, &sql).is_empty());
+        let violations = schema.validate_element(1, &bad);
+        assert_eq!(violations.len(), 1);
+        assert!(violations[0]
+            .to_string()
+            .starts_with("element `cfg`: content is not valid json: "));
+    }
+}
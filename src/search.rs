@@ -0,0 +1,105 @@
+use regex::Regex;
+use std::error::Error;
+use std::ops::Range;
+
+use crate::{JtlDocument, JtlElement};
+
+/// What [`JtlDocument::search`] looks for in element content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Matcher {
+    /// The text itself, ignoring case.
+    Text(String),
+    /// A regular expression, in the syntax of the `regex` crate.
+    Regex(String),
+}
+
+impl Matcher {
+    fn compile(&self) -> Result<Regex, Box<dyn Error>> {
+        let pattern = match self {
+            Matcher::Text(text) => format!("(?i){}", regex::escape(text)),
+            Matcher::Regex(pattern) => pattern.clone(),
+        };
+        Regex::new(&pattern).map_err(|err| format!("invalid search pattern: {}", err).into())
+    }
+}
+
+/// An element found by [`JtlDocument::search`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit<'a> {
+    /// The element's position in the document.
+    pub index: usize,
+    /// The matching element.
+    pub element: &'a JtlElement,
+    /// Byte ranges of the matches in the element's content, in order.
+    pub spans: Vec<Range<usize>>,
+}
+
+impl SearchHit<'_> {
+    /// Returns the content with every match wrapped in `open` and `close`.
+    pub fn highlight(&self, open: &str, close: &str) -> String {
+        let content = &self.element.content;
+        let mut out = String::with_capacity(content.len());
+        let mut end = 0;
+        for span in &self.spans {
+            out.push_str(&content[end..span.start]);
+            out.push_str(open);
+            out.push_str(&content[span.clone()]);
+            out.push_str(close);
+            end = span.end;
+        }
+        out.push_str(&content[end..]);
+        out
+    }
+}
+
+impl JtlDocument {
+    /// Returns the elements whose content matches, in document order, with the
+    /// location of each match. Empty matches are skipped.
+    pub fn search(&self, matcher: Matcher) -> Result<Vec<SearchHit<'_>>, Box<dyn Error>> {
+        let regex = matcher.compile()?;
+        Ok(self
+            .elements
+            .iter()
+            .enumerate()
+            .filter_map(|(index, element)| {
+                let spans: Vec<Range<usize>> = regex
+                    .find_iter(&element.content)
+                    .map(|found| found.range())
+                    .filter(|span| !span.is_empty())
+                    .collect();
+                (!spans.is_empty()).then_some(SearchHit {
+                    index,
+                    element,
+                    spans,
+                })
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search() {
+        let mut doc = JtlDocument::new();
+        for content in ["Error: disk full", "ok", "error, then ERROR"] {
+            doc.elements
+                .push(JtlElement::new("log", content).with_attr("n", "1"));
+        }
+        let hits = doc.search(Matcher::Text("error".into())).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[1].index, 2);
+        assert_eq!(hits[1].spans, [0..5, 12..17]);
+        assert_eq!(hits[1].highlight("[", "]"), "[error], then [ERROR]");
+
+        let hits = doc.search(Matcher::Regex(r"\bo\w".into())).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].element.content, "ok");
+
+        let err = doc.search(Matcher::Regex("(".into())).unwrap_err();
+        assert!(err.to_string().starts_with("invalid search pattern: "));
+    }
+}
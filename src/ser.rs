@@ -0,0 +1,718 @@
+use std::fmt;
+
+use serde::ser::{self, Impossible, Serialize, Serializer};
+
+use crate::{JtlDocument, JtlElement};
+
+/// Error returned when a value cannot be written as JTL elements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerError {
+    message: String,
+}
+
+impl fmt::Display for SerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SerError {}
+
+impl ser::Error for SerError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerError {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Serializes `value` into an element.
+///
+/// Structs and maps become elements the way [`from_element`](crate::from_element)
+/// reads them back: the `Content` field is the content, a `KEY` field the key,
+/// and every other field an attribute, in field order. Without a `KEY` field
+/// the key is the struct's name, or the variant's name for an enum variant.
+/// `None` fields are left out, and sequences of plain values become list
+/// attributes, `[a, b, c]`; nested structs are an error.
+pub fn to_element<T: Serialize + ?Sized>(value: &T) -> Result<JtlElement, SerError> {
+    JtlSerializer::new().to_element(value)
+}
+
+/// Serializes every item of `values` into an element. See [`to_element`].
+pub fn to_elements<T: Serialize>(values: &[T]) -> Result<Vec<JtlElement>, SerError> {
+    JtlSerializer::new().to_elements(values)
+}
+
+/// Serializes `value` into a JTL document.
+///
+/// A sequence becomes one element per item, and anything else a document with
+/// a single element, as written by [`to_element`]. Elements the parser could
+/// not read back, such as ones without attributes, are an error.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, SerError> {
+    JtlSerializer::new().to_string(value)
+}
+
+/// Serialization settings, for structs that do not name their fields `KEY`
+/// and `Content`.
+///
+/// ```
+/// use jtl_rs::JtlSerializer;
+/// use std::collections::BTreeMap;
+///
+/// let map = BTreeMap::from([("body", "frontend"), ("id", "web"), ("port", "80")]);
+/// let element = JtlSerializer::new()
+///     .key_field("id")
+///     .content_field("body")
+///     .to_element(&map)
+///     .unwrap();
+/// assert_eq!(element.key, "web");
+/// assert_eq!(element.content, "frontend");
+/// assert_eq!(element.attr("port"), Some("80"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JtlSerializer {
+    key_field: &'static str,
+    content_field: &'static str,
+}
+
+impl Default for JtlSerializer {
+    fn default() -> Self {
+        JtlSerializer {
+            key_field: "KEY",
+            content_field: "Content",
+        }
+    }
+}
+
+impl JtlSerializer {
+    /// Creates a serializer taking the key from `KEY` and the content from
+    /// `Content`.
+    pub fn new() -> Self {
+        JtlSerializer::default()
+    }
+
+    /// Sets the field the element key is taken from.
+    pub fn key_field(mut self, name: &'static str) -> Self {
+        self.key_field = name;
+        self
+    }
+
+    /// Sets the field the element content is taken from.
+    pub fn content_field(mut self, name: &'static str) -> Self {
+        self.content_field = name;
+        self
+    }
+
+    /// Serializes `value` into an element. See [`to_element`].
+    pub fn to_element<T: Serialize + ?Sized>(&self, value: &T) -> Result<JtlElement, SerError> {
+        let mut elements = value.serialize(ElementSerializer {
+            settings: *self,
+            top: false,
+            variant: None,
+        })?;
+        Ok(elements.remove(0))
+    }
+
+    /// Serializes every item of `values` into an element. See [`to_elements`].
+    pub fn to_elements<T: Serialize>(&self, values: &[T]) -> Result<Vec<JtlElement>, SerError> {
+        values.iter().map(|value| self.to_element(value)).collect()
+    }
+
+    /// Serializes `value` into a JTL document. See [`to_string`].
+    pub fn to_string<T: Serialize + ?Sized>(&self, value: &T) -> Result<String, SerError> {
+        let document = JtlDocument {
+            elements: value.serialize(ElementSerializer {
+                settings: *self,
+                top: true,
+                variant: None,
+            })?,
+            ..JtlDocument::default()
+        };
+        document.to_jtl().map_err(ser::Error::custom)
+    }
+}
+
+/// Serializes a struct, map or enum variant into one element, or at the top
+/// level of [`to_string`] a sequence into one element per item.
+struct ElementSerializer {
+    settings: JtlSerializer,
+    top: bool,
+    /// The name of the newtype variant being serialized, if any.
+    variant: Option<&'static str>,
+}
+
+impl ElementSerializer {
+    fn builder(self, key: Option<&'static str>) -> ElementBuilder {
+        ElementBuilder {
+            settings: self.settings,
+            element: JtlElement::new(key.unwrap_or_default(), ""),
+            has_key: key.is_some(),
+            has_content: false,
+            pending: None,
+        }
+    }
+}
+
+macro_rules! reject {
+    ($message:literal: $($method:ident($($ty:ty),*);)*) => {
+        $(fn $method(self, $(_: $ty),*) -> Result<Self::Ok, SerError> {
+            Err(ser::Error::custom($message))
+        })*
+    };
+}
+
+impl Serializer for ElementSerializer {
+    type Ok = Vec<JtlElement>;
+    type Error = SerError;
+    type SerializeSeq = Items;
+    type SerializeTuple = Items;
+    type SerializeTupleStruct = Impossible<Vec<JtlElement>, SerError>;
+    type SerializeTupleVariant = Impossible<Vec<JtlElement>, SerError>;
+    type SerializeMap = ElementBuilder;
+    type SerializeStruct = ElementBuilder;
+    type SerializeStructVariant = ElementBuilder;
+
+    reject! { "cannot write a single value as an element":
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+        serialize_bytes(&[u8]);
+        serialize_none();
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+        serialize_unit_variant(&'static str, u32, &'static str);
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, SerError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, SerError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, SerError> {
+        value.serialize(ElementSerializer {
+            settings: self.settings,
+            top: false,
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Items, SerError> {
+        if !self.top {
+            return Err(ser::Error::custom("cannot write a sequence as an element"));
+        }
+        Ok(Items {
+            settings: self.settings,
+            elements: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Items, SerError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerError> {
+        Err(ser::Error::custom(
+            "cannot write a tuple struct as an element",
+        ))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerError> {
+        Err(ser::Error::custom(
+            "cannot write a tuple variant as an element",
+        ))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<ElementBuilder, SerError> {
+        let variant = self.variant;
+        Ok(self.builder(variant))
+    }
+
+    fn serialize_struct(self, name: &'static str, _len: usize) -> Result<ElementBuilder, SerError> {
+        let key = self.variant.unwrap_or(name);
+        Ok(self.builder(Some(key)))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<ElementBuilder, SerError> {
+        Ok(self.builder(Some(variant)))
+    }
+}
+
+/// The items of a top-level sequence.
+struct Items {
+    settings: JtlSerializer,
+    elements: Vec<JtlElement>,
+}
+
+impl ser::SerializeSeq for Items {
+    type Ok = Vec<JtlElement>;
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.elements.push(self.settings.to_element(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Vec<JtlElement>, SerError> {
+        Ok(self.elements)
+    }
+}
+
+impl ser::SerializeTuple for Items {
+    type Ok = Vec<JtlElement>;
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Vec<JtlElement>, SerError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+/// Collects the fields of one element.
+struct ElementBuilder {
+    settings: JtlSerializer,
+    element: JtlElement,
+    has_key: bool,
+    has_content: bool,
+    /// The map key waiting for its value.
+    pending: Option<String>,
+}
+
+impl ElementBuilder {
+    fn field<T: Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), SerError> {
+        let in_field = |err: SerError| -> SerError {
+            ser::Error::custom(format!("field `{}`: {}", name, err))
+        };
+        let Some(value) = value.serialize(ValueSerializer).map_err(in_field)? else {
+            return Ok(());
+        };
+        if name == self.settings.key_field {
+            self.element.key = value;
+            self.has_key = true;
+        } else if name == self.settings.content_field {
+            self.element.content = value;
+            self.has_content = true;
+        } else {
+            self.element.attrs.push((name.to_string(), value));
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Vec<JtlElement>, SerError> {
+        if !self.has_key {
+            return Err(ser::Error::custom(format!(
+                "missing `{}` field",
+                self.settings.key_field
+            )));
+        }
+        if !self.has_content {
+            return Err(ser::Error::custom(format!(
+                "element `{}`: missing `{}` field",
+                self.element.key, self.settings.content_field
+            )));
+        }
+        Ok(vec![self.element])
+    }
+}
+
+impl ser::SerializeMap for ElementBuilder {
+    type Ok = Vec<JtlElement>;
+    type Error = SerError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerError> {
+        let key = key
+            .serialize(ValueSerializer)?
+            .ok_or_else(|| ser::Error::custom("map keys cannot be `None`"))?;
+        self.pending = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        let key = self
+            .pending
+            .take()
+            .ok_or_else(|| ser::Error::custom("map value without a key"))?;
+        self.field(&key, value)
+    }
+
+    fn end(self) -> Result<Vec<JtlElement>, SerError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for ElementBuilder {
+    type Ok = Vec<JtlElement>;
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), SerError> {
+        self.field(name, value)
+    }
+
+    fn end(self) -> Result<Vec<JtlElement>, SerError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for ElementBuilder {
+    type Ok = Vec<JtlElement>;
+    type Error = SerError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), SerError> {
+        self.field(name, value)
+    }
+
+    fn end(self) -> Result<Vec<JtlElement>, SerError> {
+        self.finish()
+    }
+}
+
+/// Serializes a field value as text, or `None` to leave the field out.
+struct ValueSerializer;
+
+macro_rules! display {
+    ($($method:ident($ty:ty);)*) => {
+        $(fn $method(self, value: $ty) -> Result<Option<String>, SerError> {
+            Ok(Some(value.to_string()))
+        })*
+    };
+}
+
+impl Serializer for ValueSerializer {
+    type Ok = Option<String>;
+    type Error = SerError;
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = Impossible<Option<String>, SerError>;
+    type SerializeTupleVariant = Impossible<Option<String>, SerError>;
+    type SerializeMap = Impossible<Option<String>, SerError>;
+    type SerializeStruct = Impossible<Option<String>, SerError>;
+    type SerializeStructVariant = Impossible<Option<String>, SerError>;
+
+    display! {
+        serialize_bool(bool);
+        serialize_i8(i8);
+        serialize_i16(i16);
+        serialize_i32(i32);
+        serialize_i64(i64);
+        serialize_i128(i128);
+        serialize_u8(u8);
+        serialize_u16(u16);
+        serialize_u32(u32);
+        serialize_u64(u64);
+        serialize_u128(u128);
+        serialize_f32(f32);
+        serialize_f64(f64);
+        serialize_char(char);
+        serialize_str(&str);
+    }
+
+    reject! { "cannot write a nested value as text":
+        serialize_bytes(&[u8]);
+        serialize_unit();
+        serialize_unit_struct(&'static str);
+    }
+
+    fn serialize_none(self) -> Result<Option<String>, SerError> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Option<String>, SerError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Option<String>, SerError> {
+        Ok(Some(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Option<String>, SerError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Option<String>, SerError> {
+        Err(nested())
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListSerializer, SerError> {
+        Ok(ListSerializer(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListSerializer, SerError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, SerError> {
+        Err(nested())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerError> {
+        Err(nested())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, SerError> {
+        Err(nested())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, SerError> {
+        Err(nested())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerError> {
+        Err(nested())
+    }
+}
+
+/// Collects a sequence field into a list attribute, `[a, b, c]`.
+struct ListSerializer(Vec<String>);
+
+impl ListSerializer {
+    fn item<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        let item = value
+            .serialize(ValueSerializer)?
+            .ok_or_else(|| ser::Error::custom("cannot write `None` in a list"))?;
+        self.0.push(item);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<String>, SerError> {
+        crate::list::format_list(&self.0)
+            .map(Some)
+            .map_err(ser::Error::custom)
+    }
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Option<String>;
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<Option<String>, SerError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Option<String>;
+    type Error = SerError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerError> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<Option<String>, SerError> {
+        self.finish()
+    }
+}
+
+fn nested() -> SerError {
+    ser::Error::custom("cannot write a nested value as text")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_element, JtlDocument};
+    use serde::ser::{SerializeStruct, SerializeStructVariant};
+    use std::collections::HashMap;
+
+    /// What `#[derive(Serialize)]` generates for `struct Service { name: String,
+    /// port: u16, #[serde(rename = "Content")] body: String, tls: Option<bool> }`.
+    struct Service {
+        name: String,
+        port: u16,
+        body: String,
+        tls: Option<bool>,
+    }
+
+    impl Serialize for Service {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut state = serializer.serialize_struct("Service", 4)?;
+            state.serialize_field("name", &self.name)?;
+            state.serialize_field("port", &self.port)?;
+            state.serialize_field("Content", &self.body)?;
+            state.serialize_field("tls", &self.tls)?;
+            state.end()
+        }
+    }
+
+    /// `enum Route { Static { path: String, #[serde(rename = "Content")] dir: String } }`.
+    struct Static(&'static str, &'static str);
+
+    impl Serialize for Static {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut state = serializer.serialize_struct_variant("Route", 0, "Static", 2)?;
+            state.serialize_field("path", self.0)?;
+            state.serialize_field("Content", self.1)?;
+            state.end()
+        }
+    }
+
+    /// `struct Tagged { tags: Vec<&str>, #[serde(rename = "Content")] body: &str }`.
+    struct Tagged(Vec<&'static str>);
+
+    impl Serialize for Tagged {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut state = serializer.serialize_struct("Tagged", 2)?;
+            state.serialize_field("tags", &self.0)?;
+            state.serialize_field("Content", "x")?;
+            state.end()
+        }
+    }
+
+    #[test]
+    fn test_list_attrs() {
+        let element = to_element(&Tagged(vec!["a", "b"])).unwrap();
+        assert_eq!(element.attr("tags"), Some("[a, b]"));
+        assert_eq!(element.attr_list("tags"), Some(vec!["a", "b"]));
+        let err = to_element(&Tagged(vec!["a,b"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "field `tags`: list item `a,b` cannot be written"
+        );
+    }
+
+    #[test]
+    fn test_to_element() {
+        let service = Service {
+            name: "web".into(),
+            port: 80,
+            body: "nginx".into(),
+            tls: None,
+        };
+        let element = to_element(&service).unwrap();
+        assert_eq!(
+            element,
+            JtlElement::new("Service", "nginx")
+                .with_attr("name", "web")
+                .with_attr("port", "80")
+        );
+        let map: HashMap<String, String> = from_element(&element).unwrap();
+        assert_eq!(map["port"], "80");
+
+        let element = to_element(&Static("/assets", "public")).unwrap();
+        assert_eq!(element.key, "Static");
+        assert_eq!(element.attr("path"), Some("/assets"));
+
+        let err = to_element(&HashMap::from([("a", "1")])).unwrap_err();
+        assert_eq!(err.to_string(), "missing `KEY` field");
+        let err = to_element(&HashMap::from([("KEY", "k"), ("a", "1")])).unwrap_err();
+        assert_eq!(err.to_string(), "element `k`: missing `Content` field");
+        assert!(to_element(&"text").is_err());
+    }
+
+    #[test]
+    fn test_to_string() {
+        let services = [
+            Service {
+                name: "web".into(),
+                port: 80,
+                body: "nginx".into(),
+                tls: Some(true),
+            },
+            Service {
+                name: "db".into(),
+                port: 5432,
+                body: "postgres".into(),
+                tls: None,
+            },
+        ];
+        let text = to_string(&services[..]).unwrap();
+        assert!(text.contains(">name=\"web\" port=\"80\" tls=\"true\">Service>nginx;"));
+        let parsed = JtlDocument::parse(&text).unwrap();
+        assert_eq!(parsed.elements.len(), 2);
+        assert_eq!(parsed.elements[1].content, "postgres");
+
+        assert_eq!(to_string(&services[1]).unwrap().lines().count(), 4);
+        let err = to_string(&[HashMap::from([("KEY", "k"), ("Content", "v")])]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid element `k`: at least one attribute is required"
+        );
+    }
+}
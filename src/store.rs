@@ -0,0 +1,270 @@
+use crate::document::JtlDocument;
+use crate::element::JtlElement;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug)]
+struct StoredDocument {
+    path: PathBuf,
+    stamp: Option<(SystemTime, u64)>,
+    document: JtlDocument,
+}
+
+/// A set of JTL documents loaded from disk and indexed by element key.
+///
+/// Call [`refresh`](JtlStore::refresh) (or run [`JtlStore::watch`]) to pick up
+/// changes to the backing files.
+#[derive(Debug, Default)]
+pub struct JtlStore {
+    documents: Vec<StoredDocument>,
+    index: HashMap<String, Vec<(usize, usize)>>,
+}
+
+impl JtlStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a store holding the documents at `paths`.
+    pub fn open<P: AsRef<Path>>(
+        paths: impl IntoIterator<Item = P>,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut store = Self::new();
+        for path in paths {
+            store.load(path)?;
+        }
+        Ok(store)
+    }
+
+    /// Loads the document at `path`, replacing it if it is already in the store.
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), Box<dyn Error>> {
+        let path = path.as_ref();
+        let stored = read_document(path)?;
+        match self.documents.iter_mut().find(|doc| doc.path == path) {
+            Some(existing) => *existing = stored,
+            None => self.documents.push(stored),
+        }
+        self.reindex();
+        Ok(())
+    }
+
+    /// Reloads every document whose file changed since it was loaded.
+    ///
+    /// Documents whose file has been deleted are dropped. Returns the paths that
+    /// were reloaded or dropped.
+    ///
+    /// A file that fails to load keeps its previous contents and is retried on
+    /// the next refresh. Every other file is still refreshed, and the first
+    /// error is returned once the store is consistent again.
+    pub fn refresh(&mut self) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let mut changed = Vec::new();
+        let mut failure = None;
+        let mut index = 0;
+        while index < self.documents.len() {
+            let path = self.documents[index].path.clone();
+            match fs::metadata(&path) {
+                Ok(metadata) => {
+                    if file_stamp(&metadata) != self.documents[index].stamp {
+                        match read_document(&path) {
+                            Ok(stored) => {
+                                self.documents[index] = stored;
+                                changed.push(path);
+                            }
+                            Err(err) => {
+                                failure.get_or_insert(err);
+                            }
+                        }
+                    }
+                    index += 1;
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    self.documents.remove(index);
+                    changed.push(path);
+                }
+                Err(err) => {
+                    failure.get_or_insert(err.into());
+                    index += 1;
+                }
+            }
+        }
+        if !changed.is_empty() {
+            self.reindex();
+        }
+        match failure {
+            Some(err) => Err(err),
+            None => Ok(changed),
+        }
+    }
+
+    /// Refreshes `store` every `interval` on a background thread.
+    ///
+    /// The thread exits once every other handle to the store has been dropped.
+    /// Refresh errors (for example a file caught mid-edit) are skipped and retried
+    /// on the next tick.
+    pub fn watch(store: Arc<RwLock<JtlStore>>, interval: Duration) -> JoinHandle<()> {
+        thread::spawn(move || {
+            while Arc::strong_count(&store) > 1 {
+                thread::sleep(interval);
+                if let Ok(mut store) = store.write() {
+                    let _ = store.refresh();
+                }
+            }
+        })
+    }
+
+    /// Returns the first element with the given key, in load order.
+    pub fn get(&self, key: &str) -> Option<&JtlElement> {
+        self.get_all(key).next().map(|(_, element)| element)
+    }
+
+    /// Returns every element with the given key, with the path it came from.
+    pub fn get_all<'a>(
+        &'a self,
+        key: &str,
+    ) -> impl Iterator<Item = (&'a Path, &'a JtlElement)> + 'a {
+        self.index
+            .get(key)
+            .into_iter()
+            .flatten()
+            .map(move |&(doc, element)| {
+                let stored = &self.documents[doc];
+                (stored.path.as_path(), &stored.document.elements[element])
+            })
+    }
+
+    /// Returns every element, across all documents, matching `predicate`.
+    pub fn query<'a>(
+        &'a self,
+        predicate: impl Fn(&JtlElement) -> bool + 'a,
+    ) -> impl Iterator<Item = (&'a Path, &'a JtlElement)> + 'a {
+        self.documents
+            .iter()
+            .flat_map(|stored| {
+                let path = stored.path.as_path();
+                stored
+                    .document
+                    .elements
+                    .iter()
+                    .map(move |element| (path, element))
+            })
+            .filter(move |(_, element)| predicate(element))
+    }
+
+    /// Returns the document loaded from `path`.
+    pub fn document(&self, path: impl AsRef<Path>) -> Option<&JtlDocument> {
+        let path = path.as_ref();
+        self.documents
+            .iter()
+            .find(|stored| stored.path == path)
+            .map(|stored| &stored.document)
+    }
+
+    /// Returns the paths of all loaded documents, in load order.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.documents.iter().map(|stored| stored.path.as_path())
+    }
+
+    fn reindex(&mut self) {
+        self.index.clear();
+        for (doc, stored) in self.documents.iter().enumerate() {
+            for (element, el) in stored.document.elements.iter().enumerate() {
+                self.index
+                    .entry(el.key.clone())
+                    .or_default()
+                    .push((doc, element));
+            }
+        }
+    }
+}
+
+fn read_document(path: &Path) -> Result<StoredDocument, Box<dyn Error>> {
+    let stamp = file_stamp(&fs::metadata(path)?);
+    let document = JtlDocument::load(path)?;
+    Ok(StoredDocument {
+        path: path.to_path_buf(),
+        stamp,
+        document,
+    })
+}
+
+fn file_stamp(metadata: &fs::Metadata) -> Option<(SystemTime, u64)> {
+    metadata
+        .modified()
+        .ok()
+        .map(|modified| (modified, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::tests::temp_path;
+
+    #[test]
+    fn test_store_query_and_refresh() {
+        let first = temp_path("store-a.jtl");
+        let second = temp_path("store-b.jtl");
+        fs::write(
+            &first,
+            "DOCTYPE=JTL\n>>>BEGIN;\n>port=\"80\">web>frontend;\n>>>END;\n",
+        )
+        .unwrap();
+        fs::write(
+            &second,
+            "DOCTYPE=JTL\n>>>BEGIN;\n>port=\"5432\">db>database;\n>>>END;\n",
+        )
+        .unwrap();
+
+        let mut store = JtlStore::open([&first, &second]).unwrap();
+        assert_eq!(store.get("db").unwrap().content, "database");
+        let with_port: Vec<_> = store
+            .query(|element| element.attrs.iter().any(|(name, _)| name == "port"))
+            .map(|(_, element)| element.key.as_str())
+            .collect();
+        assert_eq!(with_port, ["web", "db"]);
+        assert!(store.refresh().unwrap().is_empty());
+
+        fs::write(
+            &second,
+            "DOCTYPE=JTL\n>>>BEGIN;\n>port=\"5433\">db>replica database;\n>>>END;\n",
+        )
+        .unwrap();
+        assert_eq!(store.refresh().unwrap(), vec![second.clone()]);
+        assert_eq!(store.get("db").unwrap().content, "replica database");
+
+        fs::remove_file(&first).unwrap();
+        assert_eq!(store.refresh().unwrap(), vec![first]);
+        assert!(store.get("web").is_none());
+        assert_eq!(store.paths().count(), 1);
+    }
+
+    #[test]
+    fn test_refresh_error_keeps_index_consistent() {
+        let paths = [
+            temp_path("store-err-a.jtl"),
+            temp_path("store-err-b.jtl"),
+            temp_path("store-err-c.jtl"),
+        ];
+        for (path, key) in paths.iter().zip(["x", "y", "z"]) {
+            fs::write(
+                path,
+                format!("DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">{}>v;\n>>>END;\n", key),
+            )
+            .unwrap();
+        }
+        let mut store = JtlStore::open(&paths).unwrap();
+
+        fs::remove_file(&paths[0]).unwrap();
+        fs::write(&paths[1], "DOCTYPE=JTL\n>>>BEGIN;\n>broken;\n>>>END;\n").unwrap();
+        assert!(store.refresh().is_err());
+        assert!(store.get("x").is_none());
+        assert_eq!(store.get("y").unwrap().content, "v");
+        assert_eq!(store.get("z").unwrap().key, "z");
+        assert_eq!(store.paths().count(), 2);
+    }
+}
@@ -0,0 +1,499 @@
+use crate::element::JtlElement;
+use crate::options::ParseOptions;
+use crate::scan::{self, Decl, Defaults, Scanner};
+use std::collections::HashMap;
+use std::error::Error;
+#[cfg(feature = "json")]
+use std::io::Write;
+use std::io::{BufRead, Seek, SeekFrom};
+
+/// Parses JTL from `reader` in batches of up to `chunk_size` elements.
+///
+/// Input is read line by line, so memory use is bounded by one batch plus the
+/// environment, whatever the size of the document. The iterator stops after
+/// the first error.
+///
+/// # Panics
+///
+/// Panics if `chunk_size` is zero.
+pub fn parse_chunks<R: BufRead>(reader: R, chunk_size: usize) -> Chunks<R> {
+    assert!(chunk_size > 0, "chunk_size must be non-zero");
+    Chunks {
+        lines: LineReader::new(reader),
+        chunk_size,
+        done: false,
+    }
+}
+
+/// Iterator returned by [`parse_chunks`].
+#[derive(Debug)]
+pub struct Chunks<R> {
+    lines: LineReader<R>,
+    chunk_size: usize,
+    done: bool,
+}
+
+impl<R: BufRead> Iterator for Chunks<R> {
+    type Item = Result<Vec<JtlElement>, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+        while chunk.len() < self.chunk_size {
+            match self.lines.next_element() {
+                Ok(Some(element)) => chunk.push(element),
+                Ok(None) => {
+                    self.done = true;
+                    break;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(Ok(chunk))
+        }
+    }
+}
+
+/// Parses JTL from `reader` one element at a time, reading it line by line.
+///
+/// Unlike [`parse_iter`](crate::parse_iter), the document never has to be in
+/// memory as a whole, so it can come straight from a socket or a pipe; wrap
+/// plain [`Read`](std::io::Read) sources in a
+/// [`BufReader`](std::io::BufReader). I/O errors are returned as
+/// [`io::Error`](std::io::Error)s. The iterator stops after the first error.
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// let input = Cursor::new("DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">a>x;\n>>>END;\n");
+/// let keys: Vec<String> = jtl_rs::parse_reader(input)
+///     .map(|element| element.map(|element| element.key))
+///     .collect::<Result<_, _>>()?;
+/// assert_eq!(keys, ["a"]);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn parse_reader<R: BufRead>(reader: R) -> ReaderElements<R> {
+    ReaderElements {
+        lines: LineReader::new(reader),
+        done: false,
+    }
+}
+
+/// Iterator returned by [`parse_reader`].
+#[derive(Debug)]
+pub struct ReaderElements<R> {
+    lines: LineReader<R>,
+    done: bool,
+}
+
+impl<R> ReaderElements<R> {
+    /// Returns the variables declared so far.
+    pub fn env(&self) -> &HashMap<String, String> {
+        &self.lines.parser.env
+    }
+}
+
+impl<R: BufRead> Iterator for ReaderElements<R> {
+    type Item = Result<JtlElement, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let result = self.lines.next_element().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+/// Output format of [`convert_stream`].
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Format {
+    /// One JSON object per line.
+    Ndjson,
+    /// A single JSON array, as [`stringify`](crate::stringify) writes it.
+    JsonArray,
+}
+
+/// Converts the JTL read from `reader` to JSON on `writer`, one element at a
+/// time, and returns the number of elements written.
+///
+/// Only the current element and the environment are held in memory, so files
+/// of any size convert in constant space. Elements have the shape produced by
+/// [`parse`](crate::parse). On error, the elements before it have already
+/// been written.
+#[cfg(feature = "json")]
+pub fn convert_stream<R: BufRead, W: Write>(
+    reader: R,
+    mut writer: W,
+    format: Format,
+) -> Result<usize, Box<dyn Error>> {
+    let mut lines = LineReader::new(reader);
+    let mut count = 0;
+    if format == Format::JsonArray {
+        writer.write_all(b"[")?;
+    }
+    while let Some(element) = lines.next_element()? {
+        if format == Format::JsonArray && count > 0 {
+            writer.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut writer, &element.to_value())?;
+        if format == Format::Ndjson {
+            writer.write_all(b"\n")?;
+        }
+        count += 1;
+    }
+    if format == Format::JsonArray {
+        writer.write_all(b"]")?;
+    }
+    writer.flush()?;
+    Ok(count)
+}
+
+/// Reads elements one at a time from a line-oriented source.
+#[derive(Debug)]
+pub(crate) struct LineReader<R> {
+    reader: R,
+    line: String,
+    parser: LineParser,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        LineReader {
+            reader,
+            line: String::new(),
+            parser: LineParser::default(),
+        }
+    }
+
+    /// Returns the next element, or `None` at the end of the input.
+    pub(crate) fn next_element(&mut self) -> Result<Option<JtlElement>, Box<dyn Error>> {
+        loop {
+            if let Some(element) = self.parser.next_pending()? {
+                return Ok(Some(element));
+            }
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                self.parser.finish()?;
+                return Ok(None);
+            }
+            self.parser.push_line(&self.line)?;
+        }
+    }
+}
+
+/// The parsing state of a [`LineReader`], fed one line at a time by whoever
+/// does the reading.
+#[derive(Debug, Default)]
+pub(crate) struct LineParser {
+    number: usize,
+    started: bool,
+    scanner: Scanner,
+    pub(crate) env: HashMap<String, String>,
+    defaults: Defaults,
+    pending: Vec<String>,
+    options: ParseOptions,
+}
+
+impl LineParser {
+    /// Returns the next element declared on the lines pushed so far.
+    pub(crate) fn next_pending(&mut self) -> Result<Option<JtlElement>, Box<dyn Error>> {
+        while let Some(decl) = self.pending.pop() {
+            if let Some(mut element) =
+                crate::parse_element(&decl, &self.env, &self.defaults, &self.options)?
+            {
+                element.line = Some(self.number);
+                return Ok(Some(element));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the declarations on one line, with or without its line ending.
+    pub(crate) fn push_line(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        self.number += 1;
+        let line = line.trim_end_matches(['\n', '\r']);
+        if !self.started {
+            scan::check_doctype(Some(line))?;
+            self.started = true;
+        }
+
+        for decl in self.scanner.scan_line(line) {
+            match decl {
+                Decl::Env(name, value) => {
+                    self.env.insert(name.to_string(), value.to_string());
+                }
+                Decl::Defaults(attrs) => self.defaults.extend(attrs)?,
+                Decl::Element(decl) => self.pending.push(decl.to_string()),
+            }
+        }
+        // Elements are popped from the back.
+        self.pending.reverse();
+        Ok(())
+    }
+
+    /// Checks the input once it has ended.
+    pub(crate) fn finish(&self) -> Result<(), Box<dyn Error>> {
+        if !self.started {
+            scan::check_doctype(None)?;
+        }
+        Ok(())
+    }
+}
+
+/// A position in the body of a document where parsing can resume.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Checkpoint {
+    offset: u64,
+}
+
+impl Checkpoint {
+    /// A checkpoint at the start of the document.
+    pub const START: Checkpoint = Checkpoint { offset: 0 };
+
+    /// Rebuilds a checkpoint from a stored [`offset`](Checkpoint::offset).
+    pub fn from_offset(offset: u64) -> Self {
+        Checkpoint { offset }
+    }
+
+    /// Returns the byte offset of the checkpoint, for storing between runs.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+/// Elements parsed by [`parse_from`], and where to continue next time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resumed {
+    /// Elements found after the previous checkpoint.
+    pub elements: Vec<JtlElement>,
+    /// Checkpoint after the last complete line that was parsed.
+    pub checkpoint: Checkpoint,
+    /// Whether `>>>END;` was reached, so no further elements will follow.
+    pub finished: bool,
+}
+
+/// Parses the elements added to a document since `checkpoint`.
+///
+/// The header is re-read on every call to pick up the environment; the body is
+/// then read from the checkpoint onwards. Only complete lines are consumed, so
+/// a line that is still being written is picked up by the next call. Parsing
+/// stops in front of `>>>END;`, which means the returned checkpoint never moves
+/// past it. Checkpoints must come from an earlier call on the same file.
+pub fn parse_from<R: BufRead + Seek>(
+    mut reader: R,
+    checkpoint: Checkpoint,
+) -> Result<Resumed, Box<dyn Error>> {
+    let len = reader.seek(SeekFrom::End(0))?;
+    if checkpoint.offset > len {
+        return Err("invalid checkpoint: past the end of the input".into());
+    }
+    reader.seek(SeekFrom::Start(0))?;
+
+    let mut env = HashMap::new();
+    let mut defaults = Defaults::default();
+    let mut scanner = Scanner::default();
+    let mut line = String::new();
+    let mut offset = 0;
+    let nothing_yet = Resumed {
+        elements: Vec::new(),
+        checkpoint: Checkpoint::START,
+        finished: false,
+    };
+
+    // Header, up to and including `>>>BEGIN;`.
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 && offset == 0 {
+            scan::check_doctype(None)?;
+        }
+        if read == 0 || !line.ends_with('\n') {
+            return Ok(nothing_yet);
+        }
+        let text = line.trim_end_matches(['\n', '\r']);
+        if offset == 0 {
+            scan::check_doctype(Some(text))?;
+        }
+        offset += read as u64;
+        for decl in scanner.scan_line(text) {
+            match decl {
+                Decl::Env(name, value) => {
+                    env.insert(name.to_string(), value.to_string());
+                }
+                Decl::Defaults(attrs) => defaults.extend(attrs)?,
+                Decl::Element(_) => {}
+            }
+        }
+        if text.trim() == ">>>BEGIN;" {
+            break;
+        }
+    }
+
+    if checkpoint.offset > offset {
+        offset = reader.seek(SeekFrom::Start(checkpoint.offset))?;
+    }
+
+    let options = ParseOptions::default();
+    let mut elements = Vec::new();
+    let mut finished = false;
+    loop {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if line.trim() == ">>>END;" {
+            finished = true;
+            break;
+        }
+        if read == 0 || !line.ends_with('\n') {
+            break;
+        }
+        let text = line.trim_end_matches(['\n', '\r']);
+        for decl in scanner.scan_line(text) {
+            if let Decl::Element(decl) = decl {
+                elements.extend(crate::parse_element(decl, &env, &defaults, &options)?);
+            }
+        }
+        offset += read as u64;
+    }
+
+    Ok(Resumed {
+        elements,
+        checkpoint: Checkpoint { offset },
+        finished,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_convert_stream() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>foo=bar;\n>>>BEGIN;\n>n=\"1\">a>$env:foo;\n>n=\"2\">b>x;\n>>>END;\n";
+        let mut out = Vec::new();
+        assert_eq!(
+            convert_stream(Cursor::new(text), &mut out, Format::Ndjson).unwrap(),
+            2
+        );
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines, crate::parse(text).unwrap());
+
+        let mut out = Vec::new();
+        convert_stream(Cursor::new(text), &mut out, Format::JsonArray).unwrap();
+        let array: Vec<serde_json::Value> = serde_json::from_slice(&out).unwrap();
+        assert_eq!(array, crate::parse(text).unwrap());
+
+        let mut out = Vec::new();
+        let err = convert_stream(Cursor::new("nope\n"), &mut out, Format::Ndjson).unwrap_err();
+        assert_eq!(err.to_string(), "invalid JTL document: missing DOCTYPE");
+    }
+
+    #[test]
+    fn test_parse_chunks() {
+        let mut text = String::from("DOCTYPE=JTL\n>>>ENV;\n>>>foo=bar;\n>>>BEGIN;\n");
+        for i in 0..7 {
+            text.push_str(&format!(">n=\"{}\">e{}>$env:foo; ", i, i));
+            if i % 2 == 1 {
+                text.push('\n');
+            }
+        }
+        text.push_str("\n>>>END;\n");
+
+        let chunks: Vec<Vec<JtlElement>> = parse_chunks(Cursor::new(text), 3)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let sizes: Vec<usize> = chunks.iter().map(Vec::len).collect();
+        assert_eq!(sizes, [3, 3, 1]);
+        let keys: Vec<&str> = chunks.iter().flatten().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["e0", "e1", "e2", "e3", "e4", "e5", "e6"]);
+        assert!(chunks.iter().flatten().all(|e| e.content == "bar"));
+    }
+
+    #[test]
+    fn test_parse_reader() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>foo=bar;\n>>>BEGIN;\n>n=\"1\">a>$env:foo; >n=\"2\">b>x;\n>bad;\n";
+        let mut elements = parse_reader(Cursor::new(text));
+        let a = elements.next().unwrap().unwrap();
+        assert_eq!((a.content.as_str(), a.line), ("bar", Some(5)));
+        assert_eq!(elements.env()["foo"], "bar");
+        assert_eq!(elements.next().unwrap().unwrap().key, "b");
+        assert!(elements.next().unwrap().is_err());
+        assert!(elements.next().is_none());
+
+        struct Broken;
+        impl std::io::Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("connection reset"))
+            }
+        }
+        let err = parse_reader(std::io::BufReader::new(Broken))
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(err.downcast_ref::<std::io::Error>().is_some());
+    }
+
+    #[test]
+    fn test_parse_chunks_errors() {
+        let mut chunks = parse_chunks(Cursor::new(""), 10);
+        let err = chunks.next().unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "invalid JTL document: missing DOCTYPE");
+        assert!(chunks.next().is_none());
+
+        let text = "DOCTYPE=JTL\n>>>BEGIN;\n>a=\"1\">ok>x;\n>ab;\n>>>END;\n";
+        let results: Vec<_> = parse_chunks(Cursor::new(text), 1).collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+    }
+
+    #[test]
+    fn test_parse_from_checkpoint() {
+        let mut text =
+            String::from("DOCTYPE=JTL\n>>>ENV;\n>>>foo=bar;\n>>>BEGIN;\n>n=\"1\">a>$env:foo;\n");
+        let first = parse_from(Cursor::new(text.clone()), Checkpoint::START).unwrap();
+        assert_eq!(first.elements.len(), 1);
+        assert_eq!(first.elements[0].content, "bar");
+        assert_eq!(first.checkpoint.offset(), text.len() as u64);
+
+        // A partially written line is left for the next run.
+        text.push_str(">n=\"2\">b>two;\n>n=\"3\">c>th");
+        let second = parse_from(Cursor::new(text.clone()), first.checkpoint).unwrap();
+        let keys: Vec<&str> = second.elements.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["b"]);
+
+        text.push_str("ree;\n>>>END;\n");
+        let stored = Checkpoint::from_offset(second.checkpoint.offset());
+        let third = parse_from(Cursor::new(text.clone()), stored).unwrap();
+        assert_eq!(third.elements[0].content, "three");
+        let end = parse_from(Cursor::new(text.clone()), third.checkpoint).unwrap();
+        assert!(end.elements.is_empty());
+        assert!(end.finished && !second.finished);
+        assert_eq!(end.checkpoint, third.checkpoint);
+
+        let err = parse_from(Cursor::new("DOCTYPE=JTL\n"), third.checkpoint).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid checkpoint: past the end of the input"
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::stream::{parse_from, Checkpoint};
+use crate::JtlElement;
+
+/// Follows a growing JTL file, yielding elements as they are appended.
+///
+/// This is `tail -f` for JTL: the iterator blocks, polling the file until new
+/// complete lines show up. It keeps polling after `>>>END;`, since an
+/// [`Appender`](crate::Appender) inserts new elements in front of it; use
+/// [`stop_at_end`](Tail::stop_at_end) to end the iterator there instead. A
+/// file that shrinks below the last checkpoint is assumed to have been rotated
+/// and is followed again from the start. A missing file is waited for.
+pub fn tail(path: impl AsRef<Path>) -> Tail {
+    Tail {
+        path: path.as_ref().to_path_buf(),
+        checkpoint: Checkpoint::START,
+        interval: Duration::from_millis(250),
+        pending: VecDeque::new(),
+        stop_at_end: false,
+        finished: false,
+    }
+}
+
+/// Iterator returned by [`tail`].
+#[derive(Debug)]
+pub struct Tail {
+    path: PathBuf,
+    checkpoint: Checkpoint,
+    interval: Duration,
+    pending: VecDeque<JtlElement>,
+    stop_at_end: bool,
+    finished: bool,
+}
+
+impl Tail {
+    /// Sets how long to wait between polls of the file. Defaults to 250ms.
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Ends the iterator once the document is closed with `>>>END;`, for files
+    /// that are written once from start to end.
+    pub fn stop_at_end(mut self, stop: bool) -> Self {
+        self.stop_at_end = stop;
+        self
+    }
+
+    /// Starts following from `checkpoint` instead of the start of the file.
+    pub fn from_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        self.checkpoint = checkpoint;
+        self
+    }
+
+    /// Returns the checkpoint after the last element read from the file.
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.checkpoint
+    }
+
+    /// Reads whatever was appended since the last poll, without blocking.
+    pub fn poll(&mut self) -> Result<Vec<JtlElement>, Box<dyn Error>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        if fs::metadata(&self.path)?.len() < self.checkpoint.offset() {
+            self.checkpoint = Checkpoint::START;
+        }
+        let resumed = parse_from(BufReader::new(file), self.checkpoint)?;
+        self.checkpoint = resumed.checkpoint;
+        self.finished = resumed.finished;
+        Ok(resumed.elements)
+    }
+}
+
+impl Iterator for Tail {
+    type Item = Result<JtlElement, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(element) = self.pending.pop_front() {
+                return Some(Ok(element));
+            }
+            if self.finished && self.stop_at_end {
+                return None;
+            }
+            match self.poll() {
+                Ok(elements) if elements.is_empty() && !(self.finished && self.stop_at_end) => {
+                    thread::sleep(self.interval)
+                }
+                Ok(elements) => self.pending.extend(elements),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::tests::temp_path;
+    use std::io::Write;
+
+    #[test]
+    fn test_tail_follows_appends() {
+        let path = temp_path("tail.jtl");
+        fs::write(&path, "DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">a>one;\n").unwrap();
+
+        let mut tail = tail(&path)
+            .poll_interval(Duration::from_millis(5))
+            .stop_at_end(true);
+        assert_eq!(tail.next().unwrap().unwrap().key, "a");
+        assert!(tail.poll().unwrap().is_empty());
+
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+                for line in [">n=\"2\">b>two;\n", ">n=\"3\">c>three;\n", ">>>END;\n"] {
+                    thread::sleep(Duration::from_millis(20));
+                    file.write_all(line.as_bytes()).unwrap();
+                }
+            })
+        };
+        let keys: Vec<String> = tail.map(|element| element.unwrap().key).collect();
+        writer.join().unwrap();
+        assert_eq!(keys, ["b", "c"]);
+    }
+
+    #[test]
+    fn test_tail_follows_appender() {
+        let path = temp_path("tail_appender.jtl");
+        fs::write(&path, "DOCTYPE=JTL\n>>>BEGIN;\n>>>END;\n").unwrap();
+        let mut appender = crate::Appender::open(&path).unwrap();
+        appender
+            .append(&JtlElement::new("a", "one").with_attr("n", "1"))
+            .unwrap();
+
+        let mut tail = tail(&path).poll_interval(Duration::from_millis(5));
+        assert_eq!(tail.next().unwrap().unwrap().key, "a");
+        let writer = thread::spawn(move || {
+            for n in 2..4 {
+                thread::sleep(Duration::from_millis(20));
+                let element = JtlElement::new(format!("e{}", n), "x").with_attr("n", n.to_string());
+                appender.append(&element).unwrap();
+            }
+        });
+        let keys: Vec<String> = tail.take(2).map(|element| element.unwrap().key).collect();
+        writer.join().unwrap();
+        assert_eq!(keys, ["e2", "e3"]);
+    }
+
+    #[test]
+    fn test_tail_restarts_after_rotation() {
+        let path = temp_path("tail_rotate.jtl");
+        fs::write(&path, "DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">old>one;\n").unwrap();
+        let mut tail = tail(&path);
+        assert_eq!(tail.poll().unwrap().len(), 1);
+
+        fs::write(&path, "DOCTYPE=JTL\n>>>BEGIN;\n").unwrap();
+        assert!(tail.poll().unwrap().is_empty());
+        assert_eq!(tail.checkpoint().offset(), 22);
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use crate::options::ParseOptions;
+use crate::JtlElement;
+
+/// A parsed value in the shape [`parse_values`](crate::parse_values) produces,
+/// without depending on `serde_json`.
+///
+/// Elements are objects with their attributes, `KEY`, `Content` and
+/// `Contents` as fields; attribute values are strings, or arrays of strings
+/// with [`ParseOptions::list_attrs`]. Strings are reference counted, so
+/// cloning a value is cheap and equal strings can share one allocation (see
+/// [`ParseOptions::cache_env`]). With the `json` feature, values convert to
+/// and from `serde_json::Value`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum JtlValue {
+    /// A string.
+    String(Arc<str>),
+    /// An array of values.
+    Array(Vec<JtlValue>),
+    /// An object, with fields sorted by name.
+    Object(BTreeMap<String, JtlValue>),
+}
+
+impl JtlValue {
+    /// Returns the string, if this is one.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JtlValue::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Returns the items, if this is an array.
+    pub fn as_array(&self) -> Option<&[JtlValue]> {
+        match self {
+            JtlValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Returns the fields, if this is an object.
+    pub fn as_object(&self) -> Option<&BTreeMap<String, JtlValue>> {
+        match self {
+            JtlValue::Object(fields) => Some(fields),
+            _ => None,
+        }
+    }
+
+    /// Returns field `name`, if this is an object that has it.
+    pub fn get(&self, name: &str) -> Option<&JtlValue> {
+        self.as_object()?.get(name)
+    }
+}
+
+impl From<&str> for JtlValue {
+    fn from(text: &str) -> Self {
+        JtlValue::String(Arc::from(text))
+    }
+}
+
+impl From<String> for JtlValue {
+    fn from(text: String) -> Self {
+        JtlValue::String(Arc::from(text))
+    }
+}
+
+impl PartialEq<str> for JtlValue {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == Some(other)
+    }
+}
+
+impl PartialEq<&str> for JtlValue {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == Some(*other)
+    }
+}
+
+impl JtlElement {
+    /// Converts the element to the object shape produced by
+    /// [`parse_values`](crate::parse_values), with attribute values as strings.
+    pub fn to_jtl_value(&self) -> JtlValue {
+        let mut fields = BTreeMap::new();
+        for (name, value) in &self.attrs {
+            fields.insert(name.clone(), JtlValue::from(value.as_str()));
+        }
+        fields.insert("KEY".to_string(), JtlValue::from(self.key.as_str()));
+        let content = JtlValue::from(self.content.as_str());
+        fields.insert("Content".to_string(), content.clone());
+        fields.insert("Contents".to_string(), content);
+        JtlValue::Object(fields)
+    }
+}
+
+/// Shares one allocation between equal strings.
+#[derive(Debug, Default)]
+pub(crate) struct Interner(HashSet<Arc<str>>);
+
+impl Interner {
+    pub(crate) fn intern(&mut self, text: &str) -> Arc<str> {
+        match self.0.get(text) {
+            Some(shared) => Arc::clone(shared),
+            None => {
+                let shared: Arc<str> = Arc::from(text);
+                self.0.insert(Arc::clone(&shared));
+                shared
+            }
+        }
+    }
+}
+
+/// Converts a parsed element to a value, leaving out fields dropped by the
+/// projection. With `contents`, equal contents share one allocation.
+pub(crate) fn element_value(
+    element: &JtlElement,
+    options: &ParseOptions,
+    contents: Option<&mut Interner>,
+) -> JtlValue {
+    let mut value = element.to_jtl_value();
+    if let JtlValue::Object(fields) = &mut value {
+        if let Some(contents) = contents {
+            let shared = JtlValue::String(contents.intern(&element.content));
+            fields.insert("Content".to_string(), shared.clone());
+            fields.insert("Contents".to_string(), shared);
+        }
+        if !options.keeps("KEY") {
+            fields.remove("KEY");
+        }
+        if !options.keeps("Content") {
+            fields.remove("Content");
+            fields.remove("Contents");
+        }
+        if options.lists {
+            for (name, value) in &element.attrs {
+                if let (Some(field), Some(items)) =
+                    (fields.get_mut(name), crate::list::parse_list(value))
+                {
+                    *field = JtlValue::Array(items.into_iter().map(JtlValue::from).collect());
+                }
+            }
+        }
+    }
+    value
+}
+
+#[cfg(feature = "json")]
+mod json {
+    use super::JtlValue;
+    use serde_json::Value;
+
+    impl From<JtlValue> for Value {
+        fn from(value: JtlValue) -> Self {
+            match value {
+                JtlValue::String(text) => Value::String(text.to_string()),
+                JtlValue::Array(items) => items.into_iter().map(Value::from).collect(),
+                JtlValue::Object(fields) => Value::Object(
+                    fields
+                        .into_iter()
+                        .map(|(name, value)| (name, Value::from(value)))
+                        .collect(),
+                ),
+            }
+        }
+    }
+
+    /// Numbers and booleans become strings; `null` is rejected.
+    impl TryFrom<&Value> for JtlValue {
+        type Error = String;
+
+        fn try_from(value: &Value) -> Result<Self, String> {
+            Ok(match value {
+                Value::Null => return Err("invalid value: null".into()),
+                Value::Bool(flag) => JtlValue::from(flag.to_string()),
+                Value::Number(number) => JtlValue::from(number.to_string()),
+                Value::String(text) => JtlValue::from(text.as_str()),
+                Value::Array(items) => JtlValue::Array(
+                    items
+                        .iter()
+                        .map(JtlValue::try_from)
+                        .collect::<Result<_, _>>()?,
+                ),
+                Value::Object(fields) => JtlValue::Object(
+                    fields
+                        .iter()
+                        .map(|(name, value)| Ok((name.clone(), JtlValue::try_from(value)?)))
+                        .collect::<Result<_, String>>()?,
+                ),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_values() {
+        let text = "DOCTYPE=JTL\n>>>BEGIN;\n>tags=\"[a, b]\" n=\"1\">web>nginx;\n>>>END;";
+        let values = crate::parse_values(text).unwrap();
+        assert_eq!(values[0].get("KEY").unwrap(), "web");
+        assert_eq!(values[0].get("tags").unwrap(), "[a, b]");
+
+        let options = ParseOptions::new().list_attrs(true);
+        let values = crate::parse_values_with(text, &options).unwrap();
+        let tags = values[0].get("tags").and_then(JtlValue::as_array).unwrap();
+        assert_eq!(tags, [JtlValue::from("a"), JtlValue::from("b")]);
+        assert_eq!(values[0].get("missing"), None);
+
+        let shared = |values: &[JtlValue], index: usize| match values[index].get("Content") {
+            Some(JtlValue::String(text)) => Arc::clone(text),
+            _ => panic!("content is a string"),
+        };
+        let templated = "DOCTYPE=JTL\n>>>ENV;\n>>>motd=hello;\n>>>BEGIN;\n\
+                         >n=\"1\">a>$env:motd;\n>n=\"2\">b>$env:motd;\n>>>END;";
+        let cached = crate::parse_values_with(templated, &ParseOptions::new().cache_env(true));
+        let cached = cached.unwrap();
+        assert!(Arc::ptr_eq(&shared(&cached, 0), &shared(&cached, 1)));
+        let plain = crate::parse_values(templated).unwrap();
+        assert!(!Arc::ptr_eq(&shared(&plain, 0), &shared(&plain, 1)));
+        assert_eq!(cached, plain);
+
+        #[cfg(feature = "json")]
+        {
+            let json = crate::parse_with_options(text, &options).unwrap();
+            assert_eq!(serde_json::Value::from(values[0].clone()), json[0]);
+            assert_eq!(JtlValue::try_from(&json[0]).unwrap(), values[0]);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::error::JtlError;
+use crate::iter::parse_iter_with;
+use crate::options::ParseOptions;
+use crate::JtlElement;
+
+/// Callbacks for [`parse_with_visitor`]. Every method does nothing by default.
+pub trait JtlVisitor {
+    /// Called for each env variable as it is declared, with its 1-based line.
+    /// Redefinitions ignored by [`DuplicateEnv`](crate::DuplicateEnv) are not
+    /// reported.
+    fn visit_env(&mut self, _name: &str, _value: &str, _line: usize) {}
+
+    /// Called for each element, built as [`parse_iter`](crate::parse_iter)
+    /// returns it.
+    fn visit_element(&mut self, _element: JtlElement) {}
+
+    /// Called for each comment line, including its `/*`, `*/` or `>//>`
+    /// marker, with its 1-based line.
+    fn visit_comment(&mut self, _text: &str, _line: usize) {}
+}
+
+/// Parses JTL text in a single pass, handing env variables, elements and
+/// comments to `visitor` in document order instead of collecting them.
+///
+/// ```
+/// use jtl_rs::{parse_with_visitor, JtlElement, JtlVisitor};
+/// use std::collections::HashMap;
+///
+/// #[derive(Default)]
+/// struct ByRole(HashMap<String, Vec<String>>);
+///
+/// impl JtlVisitor for ByRole {
+///     fn visit_element(&mut self, element: JtlElement) {
+///         if let Some(role) = element.attr("role") {
+///             self.0.entry(role.to_string()).or_default().push(element.key);
+///         }
+///     }
+/// }
+///
+/// let mut index = ByRole::default();
+/// parse_with_visitor("DOCTYPE=JTL\n>>>BEGIN;\n>role=\"web\">a>1;\n>>>END;", &mut index)?;
+/// assert_eq!(index.0["web"], ["a"]);
+/// # Ok::<(), jtl_rs::JtlError>(())
+/// ```
+///
+/// Callbacks made before an error are not undone.
+pub fn parse_with_visitor(text: &str, visitor: &mut impl JtlVisitor) -> Result<(), JtlError> {
+    parse_with_visitor_options(text, &ParseOptions::default(), visitor)
+}
+
+/// Like [`parse_with_visitor`], applying `options`.
+pub fn parse_with_visitor_options(
+    text: &str,
+    options: &ParseOptions,
+    visitor: &mut impl JtlVisitor,
+) -> Result<(), JtlError> {
+    let mut elements = parse_iter_with(text, options);
+    while let Some(element) = elements.next_visiting(visitor) {
+        visitor.visit_element(element?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Record(Vec<String>);
+
+    impl JtlVisitor for Record {
+        fn visit_env(&mut self, name: &str, value: &str, line: usize) {
+            self.0.push(format!("env {}={} @{}", name, value, line));
+        }
+
+        fn visit_element(&mut self, element: JtlElement) {
+            self.0
+                .push(format!("element {}={}", element.key, element.content));
+        }
+
+        fn visit_comment(&mut self, text: &str, line: usize) {
+            self.0.push(format!("comment {} @{}", text, line));
+        }
+    }
+
+    #[test]
+    fn test_parse_with_visitor() {
+        let text = "DOCTYPE=JTL\n>>>ENV;\n>>>host=a;\n>>>BEGIN;\n/* web */\n\
+                    >n=\"1\">web>$env:host;\n>n=\"2\">db>pg;\n>>>END;";
+        let mut record = Record::default();
+        parse_with_visitor(text, &mut record).unwrap();
+        assert_eq!(
+            record.0,
+            [
+                "env host=a @3",
+                "comment /* web */ @5",
+                "element web=a",
+                "element db=pg"
+            ]
+        );
+
+        let mut record = Record::default();
+        let err = parse_with_visitor("DOCTYPE=JTL\n>>>BEGIN;\n>n=\"1\">a>b;\n>oops;", &mut record)
+            .unwrap_err();
+        assert_eq!(err.line(), Some(4));
+        assert_eq!(record.0, ["element a=b"]);
+    }
+}
@@ -0,0 +1,18 @@
+use std::fmt;
+
+/// Something questionable the parser accepted, reported by
+/// [`parse_with_warnings`](crate::parse_with_warnings).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Warning {
+    /// The 1-based line the warning refers to.
+    pub line: usize,
+    /// What was found, e.g. "env variable `foo` redefined".
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
@@ -0,0 +1,383 @@
+use crate::annotation::{annotation_line, is_annotation_name};
+use crate::element::JtlElement;
+use crate::encoding::{TextFormat, BOM};
+use crate::order::AttrOrder;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::LazyLock;
+
+/// Matches text the parser would read as an attribute.
+static ATTR_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\w+="[^"]+""#).expect("valid regex"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Start,
+    Env,
+    Body,
+    Ended,
+}
+
+/// Writes a JTL document incrementally.
+///
+/// Each call emits its directive or element immediately, so documents of any size
+/// can be produced without holding them in memory:
+///
+/// ```
+/// use jtl_rs::{JtlElement, JtlWriter};
+///
+/// let out = JtlWriter::new(Vec::new())
+///     .env("foo", "bar")?
+///     .begin()?
+///     .element(&JtlElement::new("id", "$env:foo").with_attr("key", "value"))?
+///     .finish()?;
+/// assert!(String::from_utf8(out).unwrap().ends_with(">>>END;\n"));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// The `write_*` methods do the same through `&mut self`, for loops and for
+/// writers kept in a struct:
+///
+/// ```
+/// use jtl_rs::{JtlElement, JtlWriter};
+///
+/// let mut writer = JtlWriter::new(Vec::new());
+/// writer.write_env("foo", "bar")?;
+/// writer.write_begin()?;
+/// for n in 0..3 {
+///     writer.write_element(&JtlElement::new(format!("e{}", n), "x").with_attr("n", n.to_string()))?;
+/// }
+/// writer.write_end()?;
+/// assert_eq!(String::from_utf8(writer.into_inner()).unwrap().lines().count(), 8);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct JtlWriter<W: Write> {
+    inner: W,
+    state: State,
+    started: bool,
+    order: AttrOrder,
+    renames: HashMap<String, String>,
+    format: TextFormat,
+}
+
+impl<W: Write> JtlWriter<W> {
+    /// Creates a writer emitting to `inner`.
+    pub fn new(inner: W) -> Self {
+        JtlWriter {
+            inner,
+            state: State::Start,
+            started: false,
+            order: AttrOrder::Source,
+            renames: HashMap::new(),
+            format: TextFormat::default(),
+        }
+    }
+
+    /// Sets the order attributes are written in. Defaults to
+    /// [`AttrOrder::Source`].
+    pub fn attr_order(mut self, order: AttrOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Sets the line ending and whether a byte order mark is written. Defaults
+    /// to `\n` and no BOM.
+    pub fn text_format(mut self, format: TextFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Renames attributes as they are written, given `(old, new)` pairs.
+    /// Renaming happens before attributes are ordered.
+    pub fn rename_attrs(mut self, renames: &[(&str, &str)]) -> Self {
+        self.renames.extend(
+            renames
+                .iter()
+                .map(|(old, new)| (old.to_string(), new.to_string())),
+        );
+        self
+    }
+
+    /// Writes an environment variable, opening the `>>>ENV;` block if needed.
+    pub fn env(mut self, name: &str, value: &str) -> io::Result<Self> {
+        self.write_env(name, value)?;
+        Ok(self)
+    }
+
+    /// Writes `>>>BEGIN;`.
+    pub fn begin(mut self) -> io::Result<Self> {
+        self.write_begin()?;
+        Ok(self)
+    }
+
+    /// Writes one element line.
+    pub fn element(mut self, element: &JtlElement) -> io::Result<Self> {
+        self.write_element(element)?;
+        Ok(self)
+    }
+
+    /// Writes `>>>END;`, flushes, and returns the underlying writer.
+    ///
+    /// A document with no body gets an empty `>>>BEGIN;` block.
+    pub fn finish(mut self) -> io::Result<W> {
+        if self.state != State::Ended {
+            self.write_end()?;
+        }
+        Ok(self.inner)
+    }
+
+    /// Writes an environment variable like [`env`](JtlWriter::env).
+    pub fn write_env(&mut self, name: &str, value: &str) -> io::Result<()> {
+        match self.state {
+            State::Body => return Err(invalid_input("env variable written after >>>BEGIN;")),
+            State::Ended => return Err(invalid_input("env variable written after >>>END;")),
+            State::Start | State::Env => {}
+        }
+        check_env(name, value).map_err(invalid_input)?;
+        self.doctype()?;
+        if self.state == State::Start {
+            self.line(">>>ENV;")?;
+            self.state = State::Env;
+        }
+        self.line(&format!(">>>{}={};", name, value))
+    }
+
+    /// Writes `>>>BEGIN;` like [`begin`](JtlWriter::begin).
+    pub fn write_begin(&mut self) -> io::Result<()> {
+        if matches!(self.state, State::Body | State::Ended) {
+            return Err(invalid_input(">>>BEGIN; written twice"));
+        }
+        self.doctype()?;
+        self.line(">>>BEGIN;")?;
+        self.state = State::Body;
+        Ok(())
+    }
+
+    /// Writes one element line like [`element`](JtlWriter::element).
+    pub fn write_element(&mut self, element: &JtlElement) -> io::Result<()> {
+        match self.state {
+            State::Body => {}
+            State::Ended => return Err(invalid_input("element written after >>>END;")),
+            State::Start | State::Env => {
+                return Err(invalid_input("element written before >>>BEGIN;"))
+            }
+        }
+        let line = if self.order == AttrOrder::Source && self.renames.is_empty() {
+            element_line(element)
+        } else {
+            let mut element = element.clone();
+            for (name, _) in &mut element.attrs {
+                if let Some(new) = self.renames.get(name.as_str()) {
+                    name.clone_from(new);
+                }
+            }
+            self.order
+                .apply(&mut element.attrs, |(name, _)| name.as_str());
+            element_line(&element)
+        }
+        .map_err(invalid_input)?;
+        if !element.annotations.is_empty() {
+            self.line(&annotation_line(&element.annotations))?;
+        }
+        self.line(&line)
+    }
+
+    /// Writes `>>>END;` and flushes, opening an empty body first if needed.
+    /// Nothing can be written afterwards.
+    pub fn write_end(&mut self) -> io::Result<()> {
+        match self.state {
+            State::Ended => return Err(invalid_input(">>>END; written twice")),
+            State::Start | State::Env => self.write_begin()?,
+            State::Body => {}
+        }
+        self.line(">>>END;")?;
+        self.state = State::Ended;
+        self.inner.flush()
+    }
+
+    /// Returns the underlying writer, whether or not the document was finished.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn doctype(&mut self) -> io::Result<()> {
+        if !self.started {
+            self.started = true;
+            if self.format.bom {
+                write!(self.inner, "{}", BOM)?;
+            }
+            self.line("DOCTYPE=JTL")?;
+        }
+        Ok(())
+    }
+
+    fn line(&mut self, line: &str) -> io::Result<()> {
+        write!(self.inner, "{}{}", line, self.format.newline.as_str())
+    }
+}
+
+fn invalid_input(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message.into())
+}
+
+/// Renders an element as a single JTL line, rejecting values the parser can't read back.
+pub(crate) fn element_line(element: &JtlElement) -> Result<String, String> {
+    check_element(element)?;
+    Ok(element.to_string())
+}
+
+fn check_element(element: &JtlElement) -> Result<(), String> {
+    let key = &element.key;
+    if element.attrs.is_empty() {
+        return Err(format!(
+            "invalid element `{}`: at least one attribute is required",
+            key
+        ));
+    }
+    for (name, value) in &element.attrs {
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!(
+                "invalid element `{}`: bad attribute name `{}`",
+                key, name
+            ));
+        }
+        if value.is_empty() || value.contains(['"', '>', ';', '\n', '\r']) {
+            return Err(format!(
+                "invalid element `{}`: bad value for attribute `{}`",
+                key, name
+            ));
+        }
+    }
+    if key.is_empty() || key.contains(['"', '>', ';', '\n', '\r']) {
+        return Err(format!("invalid element key `{}`", key));
+    }
+    let content = &element.content;
+    if content.is_empty()
+        || content.contains([';', '\n', '\r'])
+        || content.ends_with(char::is_whitespace)
+    {
+        return Err(format!(
+            "invalid element `{}`: content cannot be written",
+            key
+        ));
+    }
+    for (name, value) in &element.annotations {
+        if !is_annotation_name(name) || value.contains(|c: char| c == ';' || c.is_whitespace()) {
+            return Err(format!(
+                "invalid element `{}`: bad annotation `{}`",
+                key, name
+            ));
+        }
+    }
+    if ATTR_REGEX.is_match(content) {
+        return Err(format!(
+            "invalid element `{}`: content would be read back as an attribute",
+            key
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn check_env(name: &str, value: &str) -> Result<(), String> {
+    if name.is_empty() || name.trim() != name || name.contains(['=', ';', '\n', '\r']) {
+        return Err(format!("invalid env variable name `{}`", name));
+    }
+    if value.trim() != value || value.contains([';', '\n', '\r']) {
+        return Err(format!("invalid value for env variable `{}`", name));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writer_round_trip() {
+        let out = JtlWriter::new(Vec::new())
+            .env("foo", "bar")
+            .unwrap()
+            .begin()
+            .unwrap()
+            .element(&JtlElement::new("first", "$env:foo").with_attr("key", "value"))
+            .unwrap()
+            .element(
+                &JtlElement::new("second", "plain")
+                    .with_attr("a", "1")
+                    .with_attr("b", "2"),
+            )
+            .unwrap()
+            .finish()
+            .unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text,
+            "DOCTYPE=JTL\n>>>ENV;\n>>>foo=bar;\n>>>BEGIN;\n>key=\"value\">first>$env:foo;\n>a=\"1\" b=\"2\">second>plain;\n>>>END;\n"
+        );
+
+        let parsed = crate::JtlDocument::parse(&text).unwrap();
+        assert_eq!(parsed.elements.len(), 2);
+        assert_eq!(parsed.elements[0].content, "bar");
+        assert_eq!(parsed.elements[1].key, "second");
+    }
+
+    #[test]
+    fn test_writer_rejects_unreadable_output() {
+        let writer = JtlWriter::new(Vec::new()).begin().unwrap();
+        let err = writer
+            .element(&JtlElement::new("id", "a;b").with_attr("key", "value"))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let err = JtlWriter::new(Vec::new())
+            .element(&JtlElement::new("id", "content").with_attr("key", "value"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "element written before >>>BEGIN;");
+    }
+
+    #[test]
+    fn test_writer_rename_attrs() {
+        let out = JtlWriter::new(Vec::new())
+            .rename_attrs(&[("colour", "color")])
+            .begin()
+            .unwrap()
+            .element(
+                &JtlElement::new("sky", "blue")
+                    .with_attr("colour", "b")
+                    .with_attr("n", "1"),
+            )
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains(">color=\"b\" n=\"1\">sky>blue;"));
+    }
+
+    #[test]
+    fn test_write_methods() {
+        let mut writer = JtlWriter::new(Vec::new());
+        writer.write_env("foo", "bar").unwrap();
+        writer.write_end().unwrap();
+        let err = writer
+            .write_element(&JtlElement::new("id", "x").with_attr("a", "1"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "element written after >>>END;");
+        assert!(writer.write_end().is_err());
+        assert_eq!(
+            String::from_utf8(writer.finish().unwrap()).unwrap(),
+            "DOCTYPE=JTL\n>>>ENV;\n>>>foo=bar;\n>>>BEGIN;\n>>>END;\n"
+        );
+    }
+
+    #[test]
+    fn test_writer_empty_document() {
+        let out = JtlWriter::new(Vec::new()).finish().unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "DOCTYPE=JTL\n>>>BEGIN;\n>>>END;\n"
+        );
+    }
+}
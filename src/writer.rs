@@ -0,0 +1,9 @@
+pub use crate::{
+    append_to_file, format_source, generate, with_locked_document, Appender, AttrOrder,
+    DocumentBuilder, GenOptions, JtlWriter, Newline, TextFormat,
+};
+#[cfg(feature = "json")]
+pub use crate::{
+    stringify, stringify_ordered, stringify_pretty, stringify_with, to_jtl, to_jtl_with_env,
+    StringifyOptions,
+};